@@ -0,0 +1,79 @@
+// (C) Copyright IBM Corp. 2024.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use appconfiguration_rust_sdk::client::AppConfigurationClient;
+use appconfiguration_rust_sdk::{AttrValue, Entity};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+struct Customer(u32);
+
+impl Entity for Customer {
+    fn get_id(&self) -> String {
+        format!("customer-{}", self.0)
+    }
+
+    fn get_attributes(&self) -> HashMap<String, AttrValue> {
+        // Doesn't match the first two segment rules of "f1", only the last one
+        HashMap::from([
+            ("code".to_string(), AttrValue::from("XYZ".to_string())),
+            ("size".to_string(), AttrValue::from("9UK".to_string())),
+            (
+                "email".to_string(),
+                AttrValue::from("adi@ibm.com".to_string()),
+            ),
+            ("number".to_string(), AttrValue::from(12i64)),
+            ("numbers".to_string(), AttrValue::from(12i64)),
+        ])
+    }
+}
+
+fn client() -> AppConfigurationClient {
+    AppConfigurationClient::from_file(
+        Path::new("data/data-dump-enterprise-plan-sdk-testing.json"),
+        "dev",
+        "blue-charge",
+    )
+    .unwrap()
+}
+
+fn bench_get_value(c: &mut Criterion) {
+    let client = client();
+    let entity = Customer(2);
+
+    c.bench_function("get_feature", |b| {
+        b.iter(|| client.get_feature(black_box("f1")).unwrap())
+    });
+
+    let feature = client.get_feature("f1").unwrap();
+    c.bench_function("feature get_value", |b| {
+        b.iter(|| feature.get_value(black_box(&entity)).unwrap())
+    });
+
+    let feature_proxy = client.get_feature_proxy("f1").unwrap();
+    c.bench_function("feature proxy get_current_value", |b| {
+        b.iter(|| feature_proxy.get_current_value(black_box(&entity)).unwrap())
+    });
+
+    let customers = (0..1000).map(Customer).collect::<Vec<_>>();
+    let customers = customers.iter().collect::<Vec<_>>();
+    c.bench_function("feature get_values 1000 entities", |b| {
+        b.iter(|| feature.get_values(black_box(&customers)))
+    });
+}
+
+criterion_group!(benches, bench_get_value);
+criterion_main!(benches);
@@ -37,9 +37,20 @@ impl Entity for CustomerEntity {
 
         HashMap::from_iter(vec![
             ("city".to_string(), AttrValue::String(self.city.clone())),
-            ("radius".to_string(), AttrValue::Numeric(self.radius as f64)),
+            (
+                "radius".to_string(),
+                AttrValue::from(u64::from(self.radius)),
+            ),
         ])
     }
+
+    fn get_attribute(&self, name: &str) -> Option<AttrValue> {
+        match name {
+            "city" => Some(AttrValue::String(self.city.clone())),
+            "radius" => Some(AttrValue::from(u64::from(self.radius))),
+            _ => None,
+        }
+    }
 }
 
 fn main() -> Result<()> {
@@ -69,14 +80,14 @@ fn main() -> Result<()> {
 
         match client.get_feature_proxy(&feature_id) {
             Ok(feature) => {
-                println!("Feature name: {}", feature.get_name());
-                println!("Feature id: {}", feature.get_id());
-                println!("Feature data type: {}", feature.get_data_type());
-                println!("Is feature enabled: {}", feature.is_enabled());
-                println!(
-                    "Feature evaluated value is: {}",
-                    feature.get_current_value(&entity)
-                );
+                println!("Feature name: {}", feature.get_name()?);
+                println!("Feature id: {}", feature.get_id()?);
+                println!("Feature data type: {}", feature.get_data_type()?);
+                println!("Is feature enabled: {}", feature.is_enabled()?);
+                match feature.get_current_value(&entity) {
+                    Ok(value) => println!("Feature evaluated value is: {value}"),
+                    Err(error) => println!("Cannot evaluate the Feature Flag. Error {error}"),
+                }
             }
             Err(error) => {
                 println!("There was an error getting the Feature Flag. Error {error}",);
@@ -86,13 +97,13 @@ fn main() -> Result<()> {
         println!("\n\nPROPERTY OPERATIONS\n");
         match client.get_property_proxy(&property_id) {
             Ok(property) => {
-                println!("Property name: {}", property.get_name());
-                println!("Property id: {}", property.get_id());
-                println!("Property data type: {}", property.get_data_type());
-                println!(
-                    "Property evaluated value is: {}",
-                    property.get_current_value(&entity)
-                );
+                println!("Property name: {}", property.get_name()?);
+                println!("Property id: {}", property.get_id()?);
+                println!("Property data type: {}", property.get_data_type()?);
+                match property.get_current_value(&entity) {
+                    Ok(value) => println!("Property evaluated value is: {value}"),
+                    Err(error) => println!("Cannot evaluate the Property. Error {error}"),
+                }
             }
             Err(error) => {
                 println!("There was an error getting the Property. Error {error}",);
@@ -12,29 +12,132 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::client::cache::ConfigurationSnapshot;
+use crate::client::builder::AppConfigurationClientBuilder;
+use crate::client::cache::{self, ConfigurationIssue, ConfigurationSnapshot};
 use crate::client::feature::Feature;
 pub use crate::client::feature_proxy::FeatureProxy;
-use crate::client::http;
+use crate::client::http::{self, Credentials};
+use crate::client::metrics::Metrics;
 use crate::client::property::Property;
 pub use crate::client::property_proxy::PropertyProxy;
-use crate::errors::{ConfigurationAccessError, Result};
-use crate::models::Segment;
+use crate::client::segment;
+use crate::client::status::{ClientStatus, ConfigurationUpdate, ConnectionState, UpdateCallbacks};
+use crate::client::typed_feature::{BoolFeature, NumericFeature, StringFeature, TypedFeature};
+use crate::client::usage::{self, UsageRecorder};
+use crate::client::value::Value;
+use crate::entity::Entity;
+use crate::errors::{ConfigurationAccessError, Error, ResourceKind, Result};
+use crate::models::{has_tag, Configuration, Segment, TargetingRule};
+use crate::segment_evaluation::{self, EvaluationOptions, SegmentMemo};
 use std::collections::{HashMap, HashSet};
 use std::net::TcpStream;
-use std::sync::{Arc, Mutex};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime};
 use tungstenite::stream::MaybeTlsStream;
 use tungstenite::Message;
 use tungstenite::WebSocket;
 
+/// Identifies the collection and environment of an App Configuration
+/// instance that a client takes its configuration from.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ConfigurationId {
+    pub region: String,
+    pub guid: String,
+    pub environment_id: String,
+    pub collection_id: String,
+}
+
+/// How long [`AppConfigurationClient::close`] waits for the background
+/// thread to stop.
+pub const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long reading from the WebSocket blocks the background thread, before
+/// it checks whether the client was closed or dropped.
+const SOCKET_READ_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// How long an access token about to expire is kept, if it cannot be
+/// renewed, before trying again.
+const ACCESS_TOKEN_RENEWAL_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// Default upper bound for the delay between attempts to reconnect the
+/// WebSocket used to monitor configuration changes.
+pub const DEFAULT_MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+
+/// Default for how long the WebSocket used to monitor configuration changes
+/// may stay idle before the client pings the server, so that idle
+/// connections are not dropped by proxies and load balancers.
+pub const DEFAULT_PING_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How a client keeps its configuration up to date.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UpdateMode {
+    /// The server notifies configuration changes on a WebSocket, so they
+    /// are received as they happen.
+    #[default]
+    WebSocket,
+    /// The configuration is fetched from the server every `interval`. For
+    /// networks where WebSockets are blocked.
+    Poll { interval: Duration },
+}
+
+/// Optional settings of a client, see [`AppConfigurationClientBuilder`].
+#[derive(Debug, Clone)]
+pub(crate) struct ClientOptions {
+    pub persistent_cache: Option<PathBuf>,
+    pub evaluation_options: EvaluationOptions,
+    pub http_options: http::HttpOptions,
+    pub max_reconnect_delay: Duration,
+    pub ping_interval: Duration,
+    pub update_mode: UpdateMode,
+    pub metrics: Metrics,
+    pub report_usage: bool,
+    pub require_live_updates: bool,
+}
+
+impl Default for ClientOptions {
+    fn default() -> Self {
+        Self {
+            persistent_cache: None,
+            evaluation_options: EvaluationOptions::default(),
+            http_options: http::HttpOptions::default(),
+            max_reconnect_delay: DEFAULT_MAX_RECONNECT_DELAY,
+            ping_interval: DEFAULT_PING_INTERVAL,
+            update_mode: UpdateMode::default(),
+            metrics: Metrics::default(),
+            report_usage: false,
+            require_live_updates: true,
+        }
+    }
+}
+
 /// App Configuration client for browsing, and evaluating features and
 /// properties.
-#[derive(Debug)]
+///
+/// Clones of a client are cheap and share its configuration, status and
+/// update callbacks: they can be handed to different threads. The
+/// background thread keeping the configuration up to date stops when the
+/// last clone is dropped. Evaluation options are not shared, see
+/// [`AppConfigurationClient::set_evaluation_options`].
+#[derive(Debug, Clone)]
 pub struct AppConfigurationClient {
-    pub(crate) latest_config_snapshot: Arc<Mutex<ConfigurationSnapshot>>,
-    pub(crate) _thread_terminator: std::sync::mpsc::Sender<()>,
+    pub(crate) latest_config_snapshot: Arc<RwLock<ConfigurationSnapshot>>,
+    // The background thread stops once all clones of the sender are dropped,
+    // or a message is sent on it
+    pub(crate) thread_terminator: std::sync::mpsc::Sender<()>,
+    pub(crate) background_thread: Arc<Mutex<Option<thread::JoinHandle<()>>>>,
+    pub(crate) evaluation_options: EvaluationOptions,
+    pub(crate) status: Arc<Mutex<ClientStatus>>,
+    pub(crate) update_callbacks: UpdateCallbacks,
+    pub(crate) metrics: Metrics,
+    pub(crate) usage: UsageRecorder,
+    // The environment is the one the client was created for, see
+    // `environment_id()` for the current one
+    pub(crate) configuration_id: ConfigurationId,
+    // The environment the client switched to, until the background thread
+    // monitors it
+    pub(crate) environment_change: Arc<Mutex<Option<String>>>,
 }
 
 impl AppConfigurationClient {
@@ -52,279 +155,1662 @@ impl AppConfigurationClient {
         environment_id: &str,
         collection_id: &str,
     ) -> Result<Self> {
-        let access_token = http::get_access_token(&apikey)?;
+        AppConfigurationClientBuilder::default()
+            .apikey(apikey)
+            .region(region)
+            .guid(guid)
+            .environment_id(environment_id)
+            .collection_id(collection_id)
+            .build()
+    }
+
+    /// Returns a builder to configure and create a client.
+    pub fn builder() -> AppConfigurationClientBuilder {
+        AppConfigurationClientBuilder::default()
+    }
 
+    /// Like [`AppConfigurationClient::new`], but every configuration received
+    /// from the server is also written to `persistent_cache`.
+    ///
+    /// If the configuration cannot be retrieved from the server when
+    /// creating the client, the configuration stored in `persistent_cache`
+    /// is used instead. Such a configuration can be arbitrarily old: it is
+    /// the last one this client (or a previous run of the application)
+    /// received. If live updates cannot be started either, the client keeps
+    /// using the cached configuration and will not receive any updates.
+    pub fn new_with_persistent_cache(
+        apikey: &str,
+        region: &str,
+        guid: &str,
+        environment_id: &str,
+        collection_id: &str,
+        persistent_cache: &Path,
+    ) -> Result<Self> {
+        AppConfigurationClientBuilder::default()
+            .apikey(apikey)
+            .region(region)
+            .guid(guid)
+            .environment_id(environment_id)
+            .collection_id(collection_id)
+            .persistent_cache(persistent_cache)
+            .build()
+    }
+
+    pub(crate) fn create(
+        credentials: Credentials,
+        configuration_id: ConfigurationId,
+        options: ClientOptions,
+    ) -> Result<Self> {
         // Populate initial configuration
-        let latest_config_snapshot: Arc<Mutex<ConfigurationSnapshot>> =
-            Arc::new(Mutex::new(Self::get_configuration_snapshot(
-                &access_token,
-                region,
-                guid,
-                environment_id,
-                collection_id,
-            )?));
+        let access_token = credentials.initial_access_token();
+        let access_token = if access_token.needs_renewal() {
+            http::get_access_token(&credentials, &options.http_options)
+        } else {
+            Ok(access_token)
+        };
+        let result = access_token.and_then(|access_token| {
+            let configuration = http::get_configuration(
+                &access_token.token,
+                &configuration_id.region,
+                &configuration_id.guid,
+                &configuration_id.collection_id,
+                &configuration_id.environment_id,
+                &options.http_options,
+            )?;
+            Self::snapshot_from_configuration(configuration, &configuration_id, &options)
+        });
+        let (mut client, from_persistent_cache) =
+            Self::with_initial_configuration(result, &credentials, configuration_id, &options)?;
+        let updates_optional = from_persistent_cache || !options.require_live_updates;
 
         // start monitoring configuration
-        let terminator = Self::update_cache_in_background(
-            latest_config_snapshot.clone(),
-            apikey,
-            region,
-            guid,
-            environment_id,
-            collection_id,
-        )?;
+        let monitor = client.configuration_monitor(&credentials, options);
+        match Self::update_cache_in_background(monitor) {
+            Ok((terminator, background_thread)) => {
+                client.thread_terminator = terminator;
+                client.background_thread = Arc::new(Mutex::new(Some(background_thread)));
+            }
+            Err(e) => client.monitoring_failed(e, updates_optional)?,
+        }
+        Ok(client)
+    }
+
+    /// Creates a client around the configuration received from the server
+    /// (the `result` of fetching it), or the one in the persistent cache if
+    /// that failed. It is not updated until monitoring is started.
+    ///
+    /// Returns whether the configuration was taken from the persistent cache.
+    pub(crate) fn with_initial_configuration(
+        result: Result<ConfigurationSnapshot>,
+        credentials: &Credentials,
+        configuration_id: ConfigurationId,
+        options: &ClientOptions,
+    ) -> Result<(Self, bool)> {
+        let (configuration_snapshot, from_persistent_cache) = match (
+            result,
+            &options.persistent_cache,
+        ) {
+            (Ok(configuration_snapshot), _) => (configuration_snapshot, false),
+            (Err(e), Some(path)) => {
+                log::warn!(
+                    "Cannot get configuration from server ({e}). Using configuration cached in '{}'.",
+                    path.display()
+                );
+                let configuration = cache::read_configuration_file(path)?;
+                (
+                    ConfigurationSnapshot::new(
+                        &configuration_id.environment_id,
+                        Some(&configuration_id.collection_id),
+                        configuration,
+                    )?,
+                    true,
+                )
+            }
+            (Err(e), None) => return Err(e),
+        };
+        let usage = if options.report_usage {
+            usage::start_usage_reporting(
+                credentials,
+                configuration_id.clone(),
+                options.http_options.clone(),
+                usage::USAGE_REPORT_INTERVAL,
+            )
+        } else {
+            UsageRecorder::default()
+        };
 
         let client = AppConfigurationClient {
-            latest_config_snapshot,
-            _thread_terminator: terminator,
+            status: Arc::new(Mutex::new(ClientStatus {
+                connection: ConnectionState::Disconnected,
+                last_update: (!from_persistent_cache).then(SystemTime::now),
+            })),
+            evaluation_options: options.evaluation_options,
+            metrics: options.metrics.clone(),
+            usage,
+            configuration_id,
+            ..Self::new_offline(configuration_snapshot)
         };
+        Ok((client, from_persistent_cache))
+    }
 
-        Ok(client)
+    /// Handles the failure `e` to start monitoring configuration changes:
+    /// unless `updates_optional` (the client uses the persistent cache, or
+    /// was built with `require_live_updates(false)`), creating the client
+    /// fails. Otherwise it keeps working without updates.
+    pub(crate) fn monitoring_failed(&self, e: Error, updates_optional: bool) -> Result<()> {
+        if !updates_optional {
+            return Err(e);
+        }
+        log::warn!(
+            "Cannot monitor configuration changes ({e}). Configuration will not be updated."
+        );
+        self.status.lock()?.connection = ConnectionState::Failed;
+        Ok(())
     }
 
-    fn get_configuration_snapshot(
-        access_token: &String,
-        region: &str,
-        guid: &str,
+    /// Creates a client from a configuration dump stored in a local JSON
+    /// file, without any network access. The file has the same content as
+    /// the response of the App Configuration `/config` endpoint.
+    ///
+    /// The configuration is never updated: features and properties
+    /// evaluate the same as in a live client, but always against the
+    /// contents of the file.
+    pub fn from_file(path: &Path, environment_id: &str, collection_id: &str) -> Result<Self> {
+        let configuration = cache::read_configuration_file(path)?;
+        Self::from_configuration(configuration, environment_id, collection_id)
+    }
+
+    /// Like [`AppConfigurationClient::from_file`], with the configuration
+    /// given as a JSON string. Meant for tests: features and properties can
+    /// be evaluated against any configuration, without network access.
+    ///
+    /// ```
+    /// # use appconfiguration_rust_sdk::client::AppConfigurationClient;
+    /// let json = r#"{
+    ///     "environments": [{
+    ///         "name": "Dev",
+    ///         "environment_id": "dev",
+    ///         "features": [{
+    ///             "name": "Discount",
+    ///             "feature_id": "discount",
+    ///             "type": "NUMERIC",
+    ///             "enabled_value": 10,
+    ///             "disabled_value": 0,
+    ///             "segment_rules": [],
+    ///             "enabled": true,
+    ///             "rollout_percentage": 100
+    ///         }],
+    ///         "properties": []
+    ///     }],
+    ///     "segments": []
+    /// }"#;
+    /// let client = AppConfigurationClient::from_configuration_json(json, "dev", "shop")?;
+    /// assert!(client.get_feature("discount").is_ok());
+    /// # Ok::<(), appconfiguration_rust_sdk::errors::Error>(())
+    /// ```
+    pub fn from_configuration_json(
+        json: &str,
         environment_id: &str,
         collection_id: &str,
+    ) -> Result<Self> {
+        let configuration = cache::parse_configuration(json)?;
+        Self::from_configuration(configuration, environment_id, collection_id)
+    }
+
+    fn from_configuration(
+        configuration: Configuration,
+        environment_id: &str,
+        collection_id: &str,
+    ) -> Result<Self> {
+        let configuration_snapshot =
+            ConfigurationSnapshot::new(environment_id, Some(collection_id), configuration)?;
+        Ok(AppConfigurationClient {
+            configuration_id: ConfigurationId {
+                environment_id: environment_id.to_string(),
+                collection_id: collection_id.to_string(),
+                ..ConfigurationId::default()
+            },
+            ..Self::new_offline(configuration_snapshot)
+        })
+    }
+
+    /// Creates a client around `configuration_snapshot` which is never
+    /// updated.
+    pub(crate) fn new_offline(configuration_snapshot: ConfigurationSnapshot) -> Self {
+        // Nobody listens on the other end: there is no background thread to stop.
+        let (sender, _) = std::sync::mpsc::channel();
+        AppConfigurationClient {
+            latest_config_snapshot: Arc::new(RwLock::new(configuration_snapshot)),
+            thread_terminator: sender,
+            background_thread: Arc::new(Mutex::new(None)),
+            evaluation_options: EvaluationOptions::default(),
+            status: Arc::new(Mutex::new(ClientStatus {
+                connection: ConnectionState::Offline,
+                last_update: None,
+            })),
+            update_callbacks: UpdateCallbacks::default(),
+            metrics: Metrics::default(),
+            usage: UsageRecorder::default(),
+            configuration_id: ConfigurationId::default(),
+            environment_change: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Returns the region of the App Configuration instance. Empty for
+    /// clients created from a file.
+    pub fn region(&self) -> &str {
+        &self.configuration_id.region
+    }
+
+    /// Returns the GUID of the App Configuration instance. Empty for clients
+    /// created from a file.
+    pub fn guid(&self) -> &str {
+        &self.configuration_id.guid
+    }
+
+    /// Returns the environment the configuration is taken from, the same
+    /// for all clones of the client.
+    pub fn environment_id(&self) -> String {
+        cache::read_snapshot(&self.latest_config_snapshot)
+            .environment_id
+            .clone()
+    }
+
+    /// Returns the collection the configuration is taken from.
+    pub fn collection_id(&self) -> &str {
+        &self.configuration_id.collection_id
+    }
+
+    /// Switches to the environment `environment_id` of the same instance
+    /// and collection, without creating a new client. Features and
+    /// properties are taken from the configuration already received, so the
+    /// switch happens right away; the background thread then reconnects to
+    /// monitor changes of the new environment. Configurations still received
+    /// for the previous environment are dropped, and usage of evaluations
+    /// from now on is reported for the new one.
+    ///
+    /// Fails with [`ConfigurationAccessError::EnvironmentNotFound`] if the
+    /// configuration received does not contain the environment.
+    ///
+    /// Clones of the client share its configuration and switch along. The
+    /// callbacks registered with
+    /// [`AppConfigurationClient::on_configuration_update`] are called on the
+    /// calling thread before returning, so they must not wait for locks the
+    /// caller holds.
+    pub fn set_environment(&self, environment_id: &str) -> Result<()> {
+        let update = {
+            let mut config_snapshot = cache::write_snapshot(&self.latest_config_snapshot);
+            config_snapshot.switch_environment(environment_id)?;
+            ConfigurationUpdate {
+                received_at: config_snapshot.last_updated,
+                feature_ids: config_snapshot.features.keys().cloned().collect(),
+                property_ids: config_snapshot.properties.keys().cloned().collect(),
+            }
+        };
+        *self.environment_change.lock()? = Some(environment_id.to_string());
+        self.usage.set_environment(environment_id);
+        self.update_callbacks.notify(&update);
+        Ok(())
+    }
+
+    /// Returns whether the configuration of this client is kept up to date,
+    /// and when it was last received from the server.
+    pub fn status(&self) -> Result<ClientStatus> {
+        Ok(self.status.lock()?.clone())
+    }
+
+    /// Registers `callback` to be called each time a new configuration is
+    /// received from the server. Features and properties retrieved from the
+    /// client afterwards evaluate against the new configuration.
+    ///
+    /// Callbacks are called on the background thread monitoring the
+    /// configuration (or on the thread calling
+    /// [`AppConfigurationClient::set_environment`]), and should return
+    /// quickly: no further updates are received while they run.
+    pub fn on_configuration_update(
+        &self,
+        callback: impl Fn(&ConfigurationUpdate) + Send + Sync + 'static,
+    ) -> Result<()> {
+        self.update_callbacks.register(callback)
+    }
+
+    /// Returns a channel receiving a [`ConfigurationUpdate`] each time a new
+    /// configuration is received from the server, like the callbacks of
+    /// [`AppConfigurationClient::on_configuration_update`]. Any number of
+    /// receivers can be created; each gets every update, and stops being
+    /// sent updates once dropped.
+    pub fn updates(&self) -> Result<std::sync::mpsc::Receiver<ConfigurationUpdate>> {
+        self.update_callbacks.subscribe()
+    }
+
+    /// Returns when the current configuration was received from the server,
+    /// or read from a file or the persistent cache.
+    pub fn last_updated(&self) -> Result<SystemTime> {
+        Ok(cache::read_snapshot(&self.latest_config_snapshot).last_updated)
+    }
+
+    /// Whether configuration changes are currently received from the
+    /// server. If not, features and properties might evaluate to outdated
+    /// values (see [`AppConfigurationClient::status`]).
+    pub fn is_live(&self) -> bool {
+        self.status()
+            .is_ok_and(|status| status.connection == ConnectionState::Connected)
+    }
+
+    /// Stops keeping the configuration up to date, and waits up to
+    /// [`CLOSE_TIMEOUT`] for the background thread to exit. Fails if it does
+    /// not exit in time.
+    ///
+    /// This affects all clones of the client: they keep evaluating against
+    /// the last configuration received, with the status
+    /// [`ConnectionState::Closed`]. For clients created with
+    /// `new_async`, the background task is told to stop but not waited for.
+    pub fn close(self) -> Result<()> {
+        // The background thread might have stopped already
+        let _ = self.thread_terminator.send(());
+        let background_thread = self.background_thread.lock()?.take();
+        let result = match background_thread {
+            Some(handle) => Self::join_with_timeout(handle, CLOSE_TIMEOUT),
+            None => Ok(()),
+        };
+        self.status.lock()?.connection = ConnectionState::Closed;
+        result
+    }
+
+    fn join_with_timeout(handle: thread::JoinHandle<()>, timeout: Duration) -> Result<()> {
+        let deadline = Instant::now() + timeout;
+        while !handle.is_finished() {
+            if Instant::now() >= deadline {
+                return Err(Error::Other(format!(
+                    "Background thread did not stop within {timeout:?}"
+                )));
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        handle
+            .join()
+            .map_err(|_| Error::Other("Background thread panicked".to_string()))
+    }
+
+    /// Makes a snapshot of `configuration` received from the server, and
+    /// stores it in the persistent cache. A configuration no snapshot can be
+    /// made of does not replace the one in the cache.
+    pub(crate) fn snapshot_from_configuration(
+        configuration: Configuration,
+        configuration_id: &ConfigurationId,
+        options: &ClientOptions,
     ) -> Result<ConfigurationSnapshot> {
-        let configuration = http::get_configuration(
-            // TODO: access_token might expire. This will cause issues with long-running apps
-            &access_token,
-            &region,
-            &guid,
-            &collection_id,
-            &environment_id,
+        // Serialized before the snapshot takes over the configuration
+        let cache_content = options
+            .persistent_cache
+            .as_ref()
+            .map(|path| (path, cache::serialize_configuration(&configuration)));
+        let snapshot = ConfigurationSnapshot::new(
+            &configuration_id.environment_id,
+            Some(&configuration_id.collection_id),
+            configuration,
         )?;
-        ConfigurationSnapshot::new(environment_id, configuration)
+        if let Some((path, content)) = cache_content {
+            if let Err(e) =
+                content.and_then(|content| cache::write_configuration_file(path, &content))
+            {
+                log::warn!("Cannot write configuration cache '{}': {e}", path.display());
+            }
+        }
+        Ok(snapshot)
     }
 
     fn update_configuration_on_change(
-        mut socket: WebSocket<MaybeTlsStream<TcpStream>>,
-        latest_config_snapshot: Arc<Mutex<ConfigurationSnapshot>>,
-        access_token: String,
-        region: String,
-        guid: String,
-        collection_id: String,
-        environment_id: String,
-    ) -> std::sync::mpsc::Sender<()> {
+        socket: WebSocket<MaybeTlsStream<TcpStream>>,
+        mut monitor: ConfigurationMonitor,
+    ) -> (std::sync::mpsc::Sender<()>, thread::JoinHandle<()>) {
         let (sender, receiver) = std::sync::mpsc::channel();
 
-        thread::spawn(move || loop {
-            // If the sender has gone (AppConfiguration instance is dropped), then finish this thread
-            if let Err(e) = receiver.try_recv() {
-                if e == std::sync::mpsc::TryRecvError::Disconnected {
+        let handle = thread::spawn(move || {
+            #[cfg(feature = "tracing")]
+            let _span = monitor.span("configuration_monitoring").entered();
+            let mut socket = socket;
+            let mut last_activity = Instant::now();
+            loop {
+                // If the client was closed, or the sender has gone (AppConfiguration instance is dropped), then finish this thread
+                if receiver.try_recv() != Err(std::sync::mpsc::TryRecvError::Empty) {
                     break;
                 }
-            }
 
-            // Wait for new data
-            match socket.read() {
-                Ok(Message::Text(text)) => match text.as_str() {
-                    "test message" => {
-                        println!("\t*** Test message received.");
+                monitor.renew_access_token();
+                let reconnect = if monitor.take_environment_change() {
+                    true
+                } else if let Err(e) =
+                    monitor.wait_for_configuration_update(&mut socket, &mut last_activity)
+                {
+                    log::warn!(
+                        "Lost connection to monitor configuration changes ({e}). Reconnecting."
+                    );
+                    monitor.set_connection_state(ConnectionState::Disconnected);
+                    true
+                } else {
+                    false
+                };
+                if reconnect {
+                    match monitor.reconnect(&receiver) {
+                        Some(new_socket) => socket = new_socket,
+                        None => break,
                     }
-                    _ => {
-                        let config_result = Self::get_configuration_snapshot(
-                            &access_token,
-                            &region,
-                            &guid,
-                            &environment_id,
-                            &collection_id,
-                        );
-                        let mut config_snapshot = latest_config_snapshot.lock().unwrap();
-                        match config_result {
-                            Ok(config) => *config_snapshot = config,
-                            Err(e) => println!("Error getting config snapshot: {}", e),
-                        }
-                    }
-                },
-                Ok(Message::Close(_)) => {
-                    println!("Connection closed by the server.");
-                    break;
-                }
-                Ok(Message::Binary(data)) => {
-                    println!("\t*** Received a message that has binary data {:?}", data);
-                }
-                Ok(Message::Ping(data)) => {
-                    println!("\t*** Received a ping message {:?}", data);
-                }
-                Ok(Message::Pong(data)) => {
-                    println!("\t*** Received a pong message {:?}", data);
+                    last_activity = Instant::now();
                 }
-                Ok(Message::Frame(frame)) => {
-                    println!("\t*** Received a frame message {:?}", frame);
-                }
-                Err(e) => {
-                    // TODO: how to handle temporary connectivity issues / errors?
-                    // In current implementation we would terminate this thread.
-                    // Effectively freezing the configuration.
-                    println!("Error: {}", e);
-                    break;
-                }
-            }
 
-            thread::sleep(Duration::from_millis(100));
+                thread::sleep(Duration::from_millis(100));
+            }
         });
 
-        sender
+        (sender, handle)
+    }
+
+    /// Sets the options used when evaluating features and properties for an
+    /// entity. Only features and properties retrieved afterwards use them,
+    /// and only from this client (not from clones made before).
+    pub fn set_evaluation_options(&mut self, evaluation_options: EvaluationOptions) {
+        self.evaluation_options = evaluation_options;
+    }
+
+    /// Returns the problems of the current configuration which would only
+    /// show when evaluating some entities, like segment rules with unknown
+    /// operators. They are also logged as warnings when a configuration is
+    /// received.
+    pub fn validate_configuration(&self) -> Vec<ConfigurationIssue> {
+        cache::read_snapshot(&self.latest_config_snapshot).validate()
     }
 
     pub fn get_feature_ids(&self) -> Result<Vec<String>> {
-        Ok(self
-            .latest_config_snapshot
-            .lock()?
+        Ok(cache::read_snapshot(&self.latest_config_snapshot)
             .features
             .keys()
             .cloned()
             .collect())
     }
 
+    /// Returns the ids of the features tagged with `tag`.
+    pub fn get_feature_ids_by_tag(&self, tag: &str) -> Result<Vec<String>> {
+        Ok(cache::read_snapshot(&self.latest_config_snapshot)
+            .features
+            .iter()
+            .filter(|(_, feature)| has_tag(feature.tags.as_deref(), tag))
+            .map(|(feature_id, _)| feature_id.clone())
+            .collect())
+    }
+
     pub fn get_feature(&self, feature_id: &str) -> Result<Feature> {
-        let config_snapshot = self.latest_config_snapshot.lock()?;
+        let config_snapshot = cache::read_snapshot(&self.latest_config_snapshot);
 
         // Get the feature from the snapshot
         let feature = config_snapshot.get_feature(feature_id)?;
 
         // Get the segment rules that apply to this feature
-        let segments = {
-            let all_segment_ids = feature
-                .segment_rules
-                .iter()
-                .flat_map(|targeting_rule| {
-                    targeting_rule
-                        .rules
-                        .iter()
-                        .flat_map(|segment| &segment.segments)
-                })
-                .cloned()
-                .collect::<HashSet<String>>();
-            let segments: HashMap<String, Segment> = config_snapshot
-                .segments
+        let segments =
+            Self::referenced_segments(&config_snapshot, feature_id, &feature.segment_rules)?;
+
+        Ok(Feature::new(feature.clone(), segments)
+            .with_evaluation_options(self.evaluation_options)
+            .with_metrics(self.metrics.clone())
+            .with_usage(self.usage.clone()))
+    }
+
+    /// Like [`AppConfigurationClient::get_feature`], for the feature named
+    /// `name`. Names are not guaranteed to be unique: fails with
+    /// [`ConfigurationAccessError::AmbiguousName`] if several features have
+    /// the name.
+    pub fn get_feature_by_name(&self, name: &str) -> Result<Feature> {
+        let feature_id = Self::id_by_name(
+            ResourceKind::Feature,
+            name,
+            cache::read_snapshot(&self.latest_config_snapshot)
+                .features
                 .iter()
-                .filter(|&(key, _)| all_segment_ids.contains(key))
-                .map(|(k, v)| (k.clone(), v.clone()))
-                .collect();
-
-            // Integrity DB check: all segment_ids should be available in the snapshot
-            if all_segment_ids.len() != segments.len() {
-                return Err(ConfigurationAccessError::MissingSegments {
-                    resource_id: feature_id.to_string(),
+                .map(|(feature_id, feature)| (feature_id, &feature.name)),
+        )?;
+        self.get_feature(&feature_id)
+    }
+
+    /// The id of the only resource named `name`, among `resources` given as
+    /// `(id, name)` pairs.
+    fn id_by_name<'a>(
+        resource_kind: ResourceKind,
+        name: &str,
+        resources: impl Iterator<Item = (&'a String, &'a String)>,
+    ) -> Result<String> {
+        let mut ids = resources
+            .filter(|(_, resource_name)| *resource_name == name)
+            .map(|(id, _)| id.clone())
+            .collect::<Vec<_>>();
+        match ids.len() {
+            0 => Err(ConfigurationAccessError::NameNotFound {
+                resource_kind,
+                name: name.to_string(),
+            }
+            .into()),
+            1 => Ok(ids.remove(0)),
+            _ => {
+                ids.sort();
+                Err(ConfigurationAccessError::AmbiguousName {
+                    resource_kind,
+                    name: name.to_string(),
+                    ids,
                 }
-                .into());
+                .into())
             }
+        }
+    }
+
+    /// Like [`AppConfigurationClient::get_feature`], for a boolean feature.
+    /// Fails with [`ConfigurationAccessError::FeatureTypeMismatch`] if the
+    /// feature is of another type.
+    pub fn get_bool_feature(&self, feature_id: &str) -> Result<BoolFeature> {
+        TypedFeature::new(feature_id, self.get_feature(feature_id)?)
+    }
+
+    /// Like [`AppConfigurationClient::get_bool_feature`], for a numeric
+    /// feature.
+    pub fn get_numeric_feature(&self, feature_id: &str) -> Result<NumericFeature> {
+        TypedFeature::new(feature_id, self.get_feature(feature_id)?)
+    }
+
+    /// Like [`AppConfigurationClient::get_bool_feature`], for a string
+    /// feature.
+    pub fn get_string_feature(&self, feature_id: &str) -> Result<StringFeature> {
+        TypedFeature::new(feature_id, self.get_feature(feature_id)?)
+    }
+
+    /// Evaluates the boolean feature `feature_id` for `entity`. Fails with
+    /// [`ConfigurationAccessError::FeatureTypeMismatch`] if the feature is
+    /// of another type.
+    pub fn get_bool(&self, feature_id: &str, entity: &impl Entity) -> Result<bool> {
+        self.get_bool_feature(feature_id)?.get_value(entity)
+    }
 
-            segments
+    /// Like [`AppConfigurationClient::get_bool`], for a string feature.
+    pub fn get_string(&self, feature_id: &str, entity: &impl Entity) -> Result<String> {
+        self.get_string_feature(feature_id)?.get_value(entity)
+    }
+
+    /// Like [`AppConfigurationClient::get_bool`], for a numeric feature.
+    /// Also fails with [`Error::NumericValueOutOfRange`] if the value is not
+    /// an `i64`, like `2.5`.
+    pub fn get_numeric_i64(&self, feature_id: &str, entity: &impl Entity) -> Result<i64> {
+        self.get_numeric_feature(feature_id)?
+            .get_value(entity)?
+            .as_i64()
+            .ok_or_else(|| Error::NumericValueOutOfRange {
+                target: "i64",
+                resource_id: feature_id.to_string(),
+            })
+    }
+
+    /// Like [`AppConfigurationClient::get_bool`], for a numeric feature.
+    /// Also fails with [`Error::NumericValueOutOfRange`] if the value cannot
+    /// be represented as an `f64`.
+    pub fn get_numeric_f64(&self, feature_id: &str, entity: &impl Entity) -> Result<f64> {
+        self.get_numeric_feature(feature_id)?
+            .get_value(entity)?
+            .as_f64()
+            .ok_or_else(|| Error::NumericValueOutOfRange {
+                target: "f64",
+                resource_id: feature_id.to_string(),
+            })
+    }
+
+    /// Like [`AppConfigurationClient::get_feature`] for several features at
+    /// once, returned in the order of `feature_ids`. The configuration is
+    /// locked only once, so all features are taken from the same
+    /// configuration.
+    ///
+    /// Fails if any of the features does not exist.
+    pub fn get_features(&self, feature_ids: &[&str]) -> Result<Vec<Feature>> {
+        let config_snapshot = cache::read_snapshot(&self.latest_config_snapshot);
+        self.features_of_snapshot(&config_snapshot, feature_ids)
+    }
+
+    /// Evaluates all features for `entity`, keyed by feature id. Features
+    /// are taken from the same configuration, and each segment is evaluated
+    /// once for the entity, however many features target it.
+    ///
+    /// Fails if any of the features cannot be evaluated.
+    pub fn evaluate_all_features(&self, entity: &impl Entity) -> Result<HashMap<String, Value>> {
+        let (feature_ids, features) = {
+            let config_snapshot = cache::read_snapshot(&self.latest_config_snapshot);
+            let feature_ids = config_snapshot.features.keys().cloned().collect::<Vec<_>>();
+            let features = self.features_of_snapshot(
+                &config_snapshot,
+                &feature_ids.iter().map(String::as_str).collect::<Vec<_>>(),
+            )?;
+            (feature_ids, features)
         };
+        let memo = SegmentMemo::default();
+        feature_ids
+            .into_iter()
+            .zip(features)
+            .map(|(feature_id, feature)| {
+                Ok((feature_id, feature.get_value_memoized(entity, &memo)?))
+            })
+            .collect()
+    }
 
-        Ok(Feature::new(feature.clone(), segments))
+    fn features_of_snapshot(
+        &self,
+        config_snapshot: &ConfigurationSnapshot,
+        feature_ids: &[&str],
+    ) -> Result<Vec<Feature>> {
+        let features = feature_ids
+            .iter()
+            .map(|feature_id| config_snapshot.get_feature(feature_id))
+            .collect::<Result<Vec<_>>>()?;
+        let all_segment_ids = features
+            .iter()
+            .map(|feature| Self::referenced_segment_ids(&feature.segment_rules))
+            .collect::<Vec<_>>();
+
+        // Collect the segments of all features in a single pass
+        let mut segments = vec![HashMap::new(); features.len()];
+        for (segment_id, segment) in &config_snapshot.segments {
+            for (segment_ids, segments) in all_segment_ids.iter().zip(segments.iter_mut()) {
+                if segment_ids.contains(segment_id) {
+                    segments.insert(segment_id.clone(), Arc::clone(segment));
+                }
+            }
+        }
+
+        features
+            .into_iter()
+            .zip(all_segment_ids)
+            .zip(segments)
+            .map(|((feature, segment_ids), segments)| {
+                // Integrity DB check: all segment_ids should be available in the snapshot
+                if segment_ids.len() != segments.len() {
+                    return Err(ConfigurationAccessError::MissingSegments {
+                        resource_id: feature.feature_id.clone(),
+                    }
+                    .into());
+                }
+                Ok(Feature::new(feature.clone(), segments)
+                    .with_evaluation_options(self.evaluation_options)
+                    .with_metrics(self.metrics.clone())
+                    .with_usage(self.usage.clone()))
+            })
+            .collect()
     }
 
-    /// Searches for the feature `feature_id` inside the current configured
-    /// collection, and environment.
+    /// Returns the segments of `config_snapshot` used by `segment_rules` of
+    /// the feature or property `resource_id`.
+    pub(crate) fn referenced_segments(
+        config_snapshot: &ConfigurationSnapshot,
+        resource_id: &str,
+        segment_rules: &[TargetingRule],
+    ) -> Result<HashMap<String, Arc<Segment>>> {
+        let all_segment_ids = Self::referenced_segment_ids(segment_rules);
+        let segments: HashMap<String, Arc<Segment>> = config_snapshot
+            .segments
+            .iter()
+            .filter(|&(key, _)| all_segment_ids.contains(key))
+            .map(|(k, v)| (k.clone(), Arc::clone(v)))
+            .collect();
+
+        // Integrity DB check: all segment_ids should be available in the snapshot
+        if all_segment_ids.len() != segments.len() {
+            // FIXME: Return some kind of DBIntegrity error
+            return Err(ConfigurationAccessError::MissingSegments {
+                resource_id: resource_id.to_string(),
+            }
+            .into());
+        }
+        Ok(segments)
+    }
+
+    /// Returns the ids of all segments used by `segment_rules`.
+    fn referenced_segment_ids(segment_rules: &[TargetingRule]) -> HashSet<String> {
+        segment_rules
+            .iter()
+            .flat_map(|targeting_rule| {
+                targeting_rule
+                    .rules
+                    .iter()
+                    .flat_map(|segment| &segment.segments)
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the ids of all segments of the configuration.
+    pub fn get_segment_ids(&self) -> Result<Vec<String>> {
+        Ok(cache::read_snapshot(&self.latest_config_snapshot)
+            .segments
+            .keys()
+            .cloned()
+            .collect())
+    }
+
+    /// Returns the segment `segment_id`, to inspect its rules.
+    pub fn get_segment(&self, segment_id: &str) -> Result<segment::Segment> {
+        let config_snapshot = cache::read_snapshot(&self.latest_config_snapshot);
+        let segment = config_snapshot.get_segment(segment_id)?;
+        Ok(segment::Segment::new(segment.clone()))
+    }
+
+    /// Whether `entity` belongs to the segment `segment_id`, regardless of
+    /// any feature or property targeting it. Fails if the segment does not
+    /// exist, or the entity cannot be evaluated against its rules.
+    pub fn entity_in_segment(&self, entity: &impl Entity, segment_id: &str) -> Result<bool> {
+        let config_snapshot = cache::read_snapshot(&self.latest_config_snapshot);
+        let segment = config_snapshot.get_segment(segment_id)?;
+        Ok(segment_evaluation::entity_in_segment(
+            segment,
+            entity,
+            &self.evaluation_options,
+        )?)
+    }
+
+    /// Returns all targeting rules of the feature `feature_id` matching
+    /// `entity`, in the order they are evaluated. Only the first one
+    /// determines the value of the feature; the others are shadowed by it.
     ///
-    /// Return `Ok(feature)` if the feature exists or `Err` if it does not.
+    /// Rollout percentages are not taken into account, and neither is
+    /// whether the feature is enabled.
+    pub fn explain_feature(
+        &self,
+        entity: &impl Entity,
+        feature_id: &str,
+    ) -> Result<Vec<TargetingRule>> {
+        let config_snapshot = cache::read_snapshot(&self.latest_config_snapshot);
+        let feature = config_snapshot.get_feature(feature_id)?;
+        let segments =
+            Self::referenced_segments(&config_snapshot, feature_id, &feature.segment_rules)?;
+        segment_evaluation::find_all_applicable_segment_rules_for_entity(
+            ResourceKind::Feature,
+            feature_id,
+            &segments,
+            &feature.segment_rules,
+            entity,
+            &self.evaluation_options,
+        )
+    }
+
+    /// Returns a proxy evaluating the feature `feature_id` against the latest
+    /// configuration of the collection and environment.
+    ///
+    /// The feature is looked up lazily: this never fails, and methods of the
+    /// proxy fail with [`ConfigurationAccessError::FeatureNotFound`] if the
+    /// feature does not exist when they are called. Use
+    /// [`AppConfigurationClient::get_feature_proxy_checked`] to fail right
+    /// away for unknown features.
     pub fn get_feature_proxy(&self, feature_id: &str) -> Result<FeatureProxy> {
-        // FIXME: there is and was no validation happening if the feature exists.
-        // Comments and error messages in FeatureProxy suggest that this should happen here.
-        // same applies for properties.
         Ok(FeatureProxy::new(
             self.latest_config_snapshot.clone(),
             feature_id.to_string(),
-        ))
+            self.evaluation_options,
+        )
+        .with_metrics(self.metrics.clone())
+        .with_usage(self.usage.clone()))
+    }
+
+    /// Like [`AppConfigurationClient::get_feature_proxy`], failing with
+    /// [`ConfigurationAccessError::FeatureNotFound`] if the feature is not in
+    /// the current configuration.
+    pub fn get_feature_proxy_checked(&self, feature_id: &str) -> Result<FeatureProxy> {
+        cache::read_snapshot(&self.latest_config_snapshot).get_feature(feature_id)?;
+        self.get_feature_proxy(feature_id)
     }
 
     pub fn get_property_ids(&self) -> Result<Vec<String>> {
-        Ok(self
-            .latest_config_snapshot
-            .lock()
-            .map_err(|_| ConfigurationAccessError::LockAcquisitionError)?
+        Ok(cache::read_snapshot(&self.latest_config_snapshot)
             .properties
             .keys()
             .cloned()
             .collect())
     }
 
+    /// Returns the ids of the properties tagged with `tag`.
+    pub fn get_property_ids_by_tag(&self, tag: &str) -> Result<Vec<String>> {
+        Ok(cache::read_snapshot(&self.latest_config_snapshot)
+            .properties
+            .iter()
+            .filter(|(_, property)| has_tag(property.tags.as_deref(), tag))
+            .map(|(property_id, _)| property_id.clone())
+            .collect())
+    }
+
     pub fn get_property(&self, property_id: &str) -> Result<Property> {
-        let config_snapshot = self.latest_config_snapshot.lock()?;
+        let config_snapshot = cache::read_snapshot(&self.latest_config_snapshot);
 
         // Get the property from the snapshot
         let property = config_snapshot.get_property(property_id)?;
 
         // Get the segment rules that apply to this property
-        let segments = {
-            let all_segment_ids = property
-                .segment_rules
-                .iter()
-                .flat_map(|targeting_rule| {
-                    targeting_rule
-                        .rules
-                        .iter()
-                        .flat_map(|segment| &segment.segments)
-                })
-                .cloned()
-                .collect::<HashSet<String>>();
-            let segments: HashMap<String, Segment> = config_snapshot
-                .segments
-                .iter()
-                .filter(|&(key, _)| all_segment_ids.contains(key))
-                .map(|(k, v)| (k.clone(), v.clone()))
-                .collect();
-
-            // Integrity DB check: all segment_ids should be available in the snapshot
-            if all_segment_ids.len() != segments.len() {
-                // FIXME: Return some kind of DBIntegrity error
-                return Err(ConfigurationAccessError::MissingSegments {
-                    resource_id: property_id.to_string(),
-                }
-                .into());
-            }
+        let segments =
+            Self::referenced_segments(&config_snapshot, property_id, &property.segment_rules)?;
 
-            segments
-        };
+        Ok(Property::new(property.clone(), segments)
+            .with_evaluation_options(self.evaluation_options))
+    }
 
-        Ok(Property::new(property.clone(), segments))
+    /// Like [`AppConfigurationClient::get_feature_by_name`], for the property
+    /// named `name`.
+    pub fn get_property_by_name(&self, name: &str) -> Result<Property> {
+        let property_id = Self::id_by_name(
+            ResourceKind::Property,
+            name,
+            cache::read_snapshot(&self.latest_config_snapshot)
+                .properties
+                .iter()
+                .map(|(property_id, property)| (property_id, &property.name)),
+        )?;
+        self.get_property(&property_id)
     }
 
-    /// Searches for the property `property_id` inside the current configured
-    /// collection, and environment.
+    /// Returns a proxy evaluating the property `property_id` against the
+    /// latest configuration of the collection and environment.
     ///
-    /// Return `Ok(property)` if the feature exists or `Err` if it does not.
+    /// The property is looked up lazily: this never fails, and methods of
+    /// the proxy fail with [`ConfigurationAccessError::PropertyNotFound`] if
+    /// the property does not exist when they are called. Use [`AppConfigurationClient::get_property_proxy_checked`] to fail
+    /// right away for unknown properties.
     pub fn get_property_proxy(&self, property_id: &str) -> Result<PropertyProxy> {
         Ok(PropertyProxy::new(
             self.latest_config_snapshot.clone(),
             property_id.to_string(),
+            self.evaluation_options,
         ))
     }
 
+    /// Like [`AppConfigurationClient::get_property_proxy`], failing with
+    /// [`ConfigurationAccessError::PropertyNotFound`] if the property is not
+    /// in the current configuration.
+    pub fn get_property_proxy_checked(&self, property_id: &str) -> Result<PropertyProxy> {
+        cache::read_snapshot(&self.latest_config_snapshot).get_property(property_id)?;
+        self.get_property_proxy(property_id)
+    }
+
+    /// State for keeping the configuration of this client up to date.
+    pub(crate) fn configuration_monitor(
+        &self,
+        credentials: &Credentials,
+        options: ClientOptions,
+    ) -> ConfigurationMonitor {
+        ConfigurationMonitor {
+            latest_config_snapshot: self.latest_config_snapshot.clone(),
+            status: self.status.clone(),
+            update_callbacks: self.update_callbacks.clone(),
+            environment_change: self.environment_change.clone(),
+            credentials: credentials.clone(),
+            access_token: credentials.initial_access_token(),
+            configuration_id: ConfigurationId {
+                environment_id: self.environment_id(),
+                ..self.configuration_id.clone()
+            },
+            options,
+        }
+    }
+
     fn update_cache_in_background(
-        latest_config_snapshot: Arc<Mutex<ConfigurationSnapshot>>,
-        apikey: &str,
-        region: &str,
-        guid: &str,
-        environment_id: &str,
-        collection_id: &str,
-    ) -> Result<std::sync::mpsc::Sender<()>> {
-        let access_token = http::get_access_token(&apikey)?;
-        let (socket, _response) = http::get_configuration_monitoring_websocket(
-            &access_token,
-            &region,
-            &guid,
-            &collection_id,
-            &environment_id,
+        mut monitor: ConfigurationMonitor,
+    ) -> Result<(std::sync::mpsc::Sender<()>, thread::JoinHandle<()>)> {
+        match monitor.options.update_mode {
+            UpdateMode::WebSocket => {
+                let socket = monitor.connect()?;
+                Ok(Self::update_configuration_on_change(socket, monitor))
+            }
+            UpdateMode::Poll { interval } => {
+                if monitor.access_token.needs_renewal() {
+                    monitor.access_token = http::get_access_token(
+                        &monitor.credentials,
+                        &monitor.options.http_options,
+                    )?;
+                }
+                monitor.set_connection_state(ConnectionState::Connected);
+                Ok(Self::poll_configuration(monitor, interval))
+            }
+        }
+    }
+
+    fn poll_configuration(
+        mut monitor: ConfigurationMonitor,
+        interval: Duration,
+    ) -> (std::sync::mpsc::Sender<()>, thread::JoinHandle<()>) {
+        let (sender, receiver) = std::sync::mpsc::channel();
+
+        let handle = thread::spawn(move || {
+            #[cfg(feature = "tracing")]
+            let _span = monitor.span("configuration_polling").entered();
+            // Finish this thread once the client is closed, or the sender has gone (AppConfiguration instance is dropped)
+            while receiver.recv_timeout(interval) == Err(std::sync::mpsc::RecvTimeoutError::Timeout)
+            {
+                monitor.take_environment_change();
+                if let Err(e) = monitor.poll() {
+                    log::warn!("Cannot poll configuration ({e}). Retrying in {interval:?}.");
+                    monitor.set_connection_state(ConnectionState::Disconnected);
+                }
+            }
+        });
+
+        (sender, handle)
+    }
+}
+
+/// State of the background thread keeping the configuration of a client up
+/// to date.
+pub(crate) struct ConfigurationMonitor {
+    latest_config_snapshot: Arc<RwLock<ConfigurationSnapshot>>,
+    status: Arc<Mutex<ClientStatus>>,
+    update_callbacks: UpdateCallbacks,
+    environment_change: Arc<Mutex<Option<String>>>,
+    pub(crate) credentials: Credentials,
+    pub(crate) access_token: http::AccessToken,
+    pub(crate) configuration_id: ConfigurationId,
+    pub(crate) options: ClientOptions,
+}
+
+impl ConfigurationMonitor {
+    /// Opens the WebSocket on which the server notifies configuration
+    /// changes, with a new access token if the current one is about to
+    /// expire.
+    fn connect(&mut self) -> Result<WebSocket<MaybeTlsStream<TcpStream>>> {
+        if self.access_token.needs_renewal() {
+            self.access_token =
+                http::get_access_token(&self.credentials, &self.options.http_options)?;
+        }
+        let result = http::get_configuration_monitoring_websocket(
+            &self.access_token.token,
+            &self.configuration_id.region,
+            &self.configuration_id.guid,
+            &self.configuration_id.collection_id,
+            &self.configuration_id.environment_id,
+            &self.options.http_options,
+        );
+        let (socket, _response) = result.inspect_err(|_| {
+            // The access token might have expired
+            self.access_token = http::AccessToken::default();
+        })?;
+        http::set_read_timeout(&socket, Some(SOCKET_READ_TIMEOUT))?;
+        self.set_connection_state(ConnectionState::Connected);
+        Ok(socket)
+    }
+
+    /// Span for the background thread, identifying the monitored collection.
+    #[cfg(feature = "tracing")]
+    pub(crate) fn span(&self, name: &'static str) -> tracing::Span {
+        tracing::info_span!(
+            "app_configuration",
+            task = name,
+            region = %self.configuration_id.region,
+            guid = %self.configuration_id.guid,
+            collection_id = %self.configuration_id.collection_id,
+            environment_id = %self.configuration_id.environment_id,
+        )
+    }
+
+    /// Renews the access token if it is about to expire, so that changes
+    /// notified on the WebSocket can still be fetched. The WebSocket itself
+    /// stays open.
+    fn renew_access_token(&mut self) {
+        if self.access_token.needs_renewal() {
+            let result = http::get_access_token(&self.credentials, &self.options.http_options);
+            self.access_token_renewed(result);
+        }
+    }
+
+    /// Uses the renewed access token, or keeps the current one for a while
+    /// if it could not be renewed.
+    pub(crate) fn access_token_renewed(&mut self, result: Result<http::AccessToken>) {
+        match result {
+            Ok(access_token) => {
+                log::debug!("Access token renewed.");
+                self.access_token = access_token;
+            }
+            Err(e) => {
+                log::warn!(
+                    "Cannot renew access token ({e}). Retrying in {ACCESS_TOKEN_RENEWAL_RETRY_DELAY:?}."
+                );
+                self.access_token
+                    .postpone_renewal(ACCESS_TOKEN_RENEWAL_RETRY_DELAY);
+            }
+        }
+    }
+
+    /// Whether the client switched to another environment since the last
+    /// call. If so, that environment is monitored from now on.
+    pub(crate) fn take_environment_change(&mut self) -> bool {
+        let environment_id = match self.environment_change.lock() {
+            Ok(mut environment_change) => environment_change.take(),
+            Err(_) => None,
+        };
+        match environment_id {
+            Some(environment_id) => {
+                log::info!("Switching to environment '{environment_id}'.");
+                self.configuration_id.environment_id = environment_id;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub(crate) fn set_connection_state(&self, connection: ConnectionState) {
+        if let Ok(mut status) = self.status.lock() {
+            status.connection = connection;
+        }
+    }
+
+    /// Fetches the current configuration from the server, with a new access
+    /// token if the previous poll failed or the token is about to expire.
+    fn poll(&mut self) -> Result<()> {
+        if self.access_token.needs_renewal() {
+            self.access_token =
+                http::get_access_token(&self.credentials, &self.options.http_options)?;
+        }
+        if let Err(e) = self.update_configuration() {
+            // The access token might have expired
+            self.access_token = http::AccessToken::default();
+            return Err(e);
+        }
+        self.set_connection_state(ConnectionState::Connected);
+        Ok(())
+    }
+
+    /// Fetches the current configuration from the server and makes it the
+    /// latest snapshot, if it differs from it.
+    fn update_configuration(&self) -> Result<()> {
+        let configuration = http::get_configuration(
+            &self.access_token.token,
+            &self.configuration_id.region,
+            &self.configuration_id.guid,
+            &self.configuration_id.collection_id,
+            &self.configuration_id.environment_id,
+            &self.options.http_options,
         )?;
+        self.apply_configuration(configuration)
+    }
+
+    /// Like [`ConfigurationMonitor::update_configuration`], retrying once
+    /// with a new access token if the server rejects the current one, e.g.
+    /// because a token obtained by the application expired.
+    fn update_configuration_with_valid_token(&mut self) -> Result<()> {
+        match self.update_configuration() {
+            Err(Error::AuthenticationError { status, .. }) => {
+                log::warn!("Access token rejected ({status}). Renewing it.");
+                self.access_token =
+                    http::get_access_token(&self.credentials, &self.options.http_options)?;
+                self.update_configuration()
+            }
+            result => result,
+        }
+    }
 
-        let sender = Self::update_configuration_on_change(
-            socket,
-            latest_config_snapshot,
-            access_token,
-            region.to_string(),
-            guid.to_string(),
-            collection_id.to_string(),
-            environment_id.to_string(),
+    /// Makes `configuration`, just received from the server, the latest
+    /// snapshot if it differs from it.
+    ///
+    /// The configuration is dropped if the client switched to another
+    /// environment since it was requested: the switch is picked up by the
+    /// next call of [`ConfigurationMonitor::take_environment_change`].
+    pub(crate) fn apply_configuration(&self, configuration: Configuration) -> Result<()> {
+        if self.environment_change.lock()?.is_some() {
+            log::debug!("Environment changed. Dropping the configuration received.");
+            return Ok(());
+        }
+        let mut configuration = AppConfigurationClient::snapshot_from_configuration(
+            configuration,
+            &self.configuration_id,
+            &self.options,
+        )?;
+        let received_at = configuration.last_updated;
+        self.options.metrics.record_config_refresh();
+        let update = {
+            let mut latest_config_snapshot = cache::write_snapshot(&self.latest_config_snapshot);
+            if latest_config_snapshot.environment_id != configuration.environment_id {
+                // Switched while the configuration was being processed
+                log::debug!("Environment changed. Dropping the configuration received.");
+                return Ok(());
+            }
+            if *latest_config_snapshot == configuration {
+                // Unchanged: only record that it is still up to date
+                latest_config_snapshot.last_updated = received_at;
+                latest_config_snapshot.other_environments =
+                    std::mem::take(&mut configuration.other_environments);
+                None
+            } else {
+                let update = ConfigurationUpdate {
+                    received_at,
+                    feature_ids: configuration.features.keys().cloned().collect(),
+                    property_ids: configuration.properties.keys().cloned().collect(),
+                };
+                *latest_config_snapshot = configuration;
+                Some(update)
+            }
+        };
+        self.status.lock()?.last_update = Some(received_at);
+        if let Some(update) = update {
+            self.update_callbacks.notify(&update);
+        }
+        Ok(())
+    }
+
+    /// Waits for the next message on `socket` and handles it. Returns an
+    /// error if the connection is lost.
+    ///
+    /// `last_activity` is when the last message was received or a ping was
+    /// sent. If nothing is received for the ping interval, the server is
+    /// pinged.
+    fn wait_for_configuration_update(
+        &mut self,
+        socket: &mut WebSocket<MaybeTlsStream<TcpStream>>,
+        last_activity: &mut Instant,
+    ) -> Result<()> {
+        let message = match socket.read() {
+            Ok(message) => message,
+            // Nothing received within the read timeout
+            Err(tungstenite::Error::Io(e))
+                if matches!(
+                    e.kind(),
+                    std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                ) =>
+            {
+                if last_activity.elapsed() >= self.options.ping_interval {
+                    log::debug!("Connection idle, sending a ping.");
+                    socket.send(Message::Ping(Vec::new()))?;
+                    *last_activity = Instant::now();
+                }
+                return Ok(());
+            }
+            Err(e) => return Err(e.into()),
+        };
+        *last_activity = Instant::now();
+        if message.is_ping() {
+            // tungstenite queues the pong when reading the ping, send it now
+            socket.flush()?;
+        }
+        if Self::is_configuration_change(message)? {
+            if let Err(e) = self.update_configuration_with_valid_token() {
+                log::error!("Error getting config snapshot: {}", e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether `message`, received on the WebSocket, notifies a
+    /// configuration change. Returns an error if the server closed the
+    /// connection.
+    pub(crate) fn is_configuration_change(message: Message) -> Result<bool> {
+        match message {
+            Message::Text(text) => match text.as_str() {
+                "test message" => {
+                    log::debug!("Test message received.");
+                }
+                _ => return Ok(true),
+            },
+            Message::Close(_) => {
+                log::warn!("Connection closed by the server.");
+                return Err(tungstenite::Error::ConnectionClosed.into());
+            }
+            // Like text messages, empty ones being keep-alives as well
+            Message::Binary(data) => match data.as_slice() {
+                b"" | b"test message" => {
+                    log::debug!("Test message received.");
+                }
+                _ => {
+                    log::debug!("Received a binary message of {} bytes.", data.len());
+                    return Ok(true);
+                }
+            },
+            Message::Ping(data) => {
+                log::debug!("Received a ping message {:?}, answering with a pong", data);
+            }
+            Message::Pong(data) => {
+                log::debug!("Received a pong message {:?}", data);
+            }
+            Message::Frame(frame) => {
+                log::debug!("Received a frame message {:?}", frame);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Opens a new WebSocket, retrying with exponential backoff (starting at
+    /// the retry base delay, up to the maximum reconnect delay) until it
+    /// succeeds. Changes missed while disconnected are fetched right after.
+    ///
+    /// Returns `None` if the client is dropped in the meantime.
+    fn reconnect(
+        &mut self,
+        terminator: &std::sync::mpsc::Receiver<()>,
+    ) -> Option<WebSocket<MaybeTlsStream<TcpStream>>> {
+        let mut delay = self.options.http_options.retry_base_delay;
+        loop {
+            if terminator.recv_timeout(delay) != Err(std::sync::mpsc::RecvTimeoutError::Timeout) {
+                return None;
+            }
+            match self.connect() {
+                Ok(socket) => {
+                    if let Err(e) = self.update_configuration_with_valid_token() {
+                        log::error!("Error getting config snapshot: {}", e);
+                    }
+                    return Some(socket);
+                }
+                Err(e) => {
+                    delay = delay
+                        .saturating_mul(2)
+                        .min(self.options.max_reconnect_delay);
+                    log::warn!("Cannot reconnect ({e}). Retrying in {delay:?}.");
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::tests::example_configuration_enterprise;
+    use rstest::rstest;
+
+    fn monitor(
+        configuration: crate::models::Configuration,
+        options: ClientOptions,
+    ) -> ConfigurationMonitor {
+        let snapshot = ConfigurationSnapshot::new("dev", None, configuration).unwrap();
+        ConfigurationMonitor {
+            latest_config_snapshot: Arc::new(RwLock::new(snapshot)),
+            status: Arc::new(Mutex::new(ClientStatus {
+                connection: ConnectionState::Disconnected,
+                last_update: None,
+            })),
+            update_callbacks: UpdateCallbacks::default(),
+            environment_change: Arc::new(Mutex::new(None)),
+            credentials: Credentials::ApiKey("apikey".to_string()),
+            access_token: http::AccessToken::default(),
+            configuration_id: ConfigurationId {
+                region: "region".to_string(),
+                guid: "guid".to_string(),
+                environment_id: "dev".to_string(),
+                collection_id: "collection_id".to_string(),
+            },
+            options,
+        }
+    }
+
+    #[rstest]
+    fn test_reconnect_stops_when_client_is_dropped(
+        example_configuration_enterprise: crate::models::Configuration,
+    ) {
+        let mut monitor = monitor(example_configuration_enterprise, ClientOptions::default());
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        drop(sender);
+        assert!(monitor.reconnect(&receiver).is_none());
+    }
+
+    #[rstest]
+    fn test_access_token_renewal_failure(
+        example_configuration_enterprise: crate::models::Configuration,
+    ) {
+        let mut monitor = monitor(example_configuration_enterprise, ClientOptions::default());
+        // Expired right away
+        monitor.access_token = http::AccessToken::from(http::AccessTokenResponse {
+            access_token: "token".to_string(),
+            expires_in: Some(0),
+        });
+        assert!(monitor.access_token.needs_renewal());
+
+        // The token is kept for a while
+        monitor.access_token_renewed(Err(Error::Other("IAM unavailable".to_string())));
+        assert_eq!(monitor.access_token.token, "token");
+        assert!(!monitor.access_token.needs_renewal());
+    }
+
+    #[rstest]
+    #[case(Message::Text("test message".to_string()), false)]
+    #[case(Message::Text("configuration updated".to_string()), true)]
+    #[case(Message::Binary(Vec::new()), false)]
+    #[case(Message::Binary(b"test message".to_vec()), false)]
+    #[case(Message::Binary(b"configuration updated".to_vec()), true)]
+    #[case(Message::Ping(Vec::new()), false)]
+    #[case(Message::Pong(Vec::new()), false)]
+    fn test_is_configuration_change(#[case] message: Message, #[case] expected: bool) {
+        assert_eq!(
+            ConfigurationMonitor::is_configuration_change(message).unwrap(),
+            expected
         );
+    }
+
+    #[test]
+    fn test_close_is_not_configuration_change() {
+        let result = ConfigurationMonitor::is_configuration_change(Message::Close(None));
+        assert!(matches!(
+            result,
+            Err(Error::TungsteniteError(
+                tungstenite::Error::ConnectionClosed
+            ))
+        ));
+    }
 
-        Ok(sender)
+    #[rstest]
+    fn test_ping_pong(example_configuration_enterprise: crate::models::Configuration) {
+        // Mock server pinging the client, then waiting for the client to ping
+        // before closing the connection
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut socket = tungstenite::accept(stream).unwrap();
+            socket.send(Message::Ping(b"server".to_vec())).unwrap();
+            let mut received = Vec::new();
+            while received.len() < 2 {
+                received.push(socket.read().unwrap());
+            }
+            socket.close(None).unwrap();
+            while socket.read().is_ok() {}
+            received
+        });
+
+        let options = ClientOptions {
+            ping_interval: Duration::from_millis(200),
+            ..ClientOptions::default()
+        };
+        let mut monitor = monitor(example_configuration_enterprise, options);
+        let stream = TcpStream::connect(address).unwrap();
+        let (mut socket, _) =
+            tungstenite::client(format!("ws://{address}/"), MaybeTlsStream::Plain(stream)).unwrap();
+        http::set_read_timeout(&socket, Some(Duration::from_millis(50))).unwrap();
+
+        let mut last_activity = Instant::now();
+        // Until the server closes the connection
+        while monitor
+            .wait_for_configuration_update(&mut socket, &mut last_activity)
+            .is_ok()
+        {}
+        drop(socket);
+        assert_eq!(
+            server.join().unwrap(),
+            vec![Message::Pong(b"server".to_vec()), Message::Ping(Vec::new())]
+        );
+    }
+
+    #[test]
+    fn test_monitor_takes_environment_change() {
+        let mut path = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("data/data-dump-enterprise-plan-sdk-testing.json");
+        let client = AppConfigurationClient::from_file(&path, "dev", "car-rentals").unwrap();
+        let mut monitor = client.configuration_monitor(
+            &Credentials::ApiKey("apikey".to_string()),
+            ClientOptions::default(),
+        );
+        assert!(!monitor.take_environment_change());
+
+        client.set_environment("prod").unwrap();
+        assert!(monitor.take_environment_change());
+        assert_eq!(monitor.configuration_id.environment_id, "prod");
+        assert!(!monitor.take_environment_change());
+    }
+
+    #[test]
+    fn test_configuration_of_previous_environment_dropped() {
+        let snapshot =
+            ConfigurationSnapshot::new("dev", None, example_configuration_enterprise()).unwrap();
+        let client = AppConfigurationClient {
+            configuration_id: ConfigurationId {
+                environment_id: "dev".to_string(),
+                ..ConfigurationId::default()
+            },
+            ..AppConfigurationClient::new_offline(snapshot)
+        };
+        let mut monitor = client.configuration_monitor(
+            &Credentials::ApiKey("apikey".to_string()),
+            ClientOptions::default(),
+        );
+
+        // Requested for "dev", received after switching to "prod"
+        client.set_environment("prod").unwrap();
+        monitor
+            .apply_configuration(example_configuration_enterprise())
+            .unwrap();
+        assert_eq!(client.environment_id(), "prod");
+        assert!(client.status().unwrap().last_update.is_none());
+
+        // Also if the monitor already took the switch
+        assert!(monitor.take_environment_change());
+        monitor.configuration_id.environment_id = "dev".to_string();
+        monitor
+            .apply_configuration(example_configuration_enterprise())
+            .unwrap();
+        assert_eq!(client.environment_id(), "prod");
+        assert!(client.status().unwrap().last_update.is_none());
+
+        monitor.configuration_id.environment_id = "prod".to_string();
+        monitor
+            .apply_configuration(example_configuration_enterprise())
+            .unwrap();
+        assert_eq!(client.environment_id(), "prod");
+        assert!(client.status().unwrap().last_update.is_some());
+    }
+
+    #[rstest]
+    fn test_offline_client_status(example_configuration_enterprise: crate::models::Configuration) {
+        let snapshot =
+            ConfigurationSnapshot::new("dev", None, example_configuration_enterprise).unwrap();
+        let client = AppConfigurationClient::new_offline(snapshot);
+
+        assert!(!client.is_live());
+        assert_eq!(
+            client.status().unwrap(),
+            ClientStatus {
+                connection: ConnectionState::Offline,
+                last_update: None,
+            }
+        );
+    }
+
+    #[rstest]
+    fn test_monitoring_failed(example_configuration_enterprise: crate::models::Configuration) {
+        let snapshot =
+            ConfigurationSnapshot::new("dev", None, example_configuration_enterprise).unwrap();
+        let client = AppConfigurationClient::new_offline(snapshot);
+
+        let result = client.monitoring_failed(Error::Other("no websocket".to_string()), false);
+        assert!(matches!(result, Err(Error::Other(_))));
+
+        client
+            .monitoring_failed(Error::Other("no websocket".to_string()), true)
+            .unwrap();
+        assert!(!client.is_live());
+        assert_eq!(client.status().unwrap().connection, ConnectionState::Failed);
+    }
+
+    // A configuration lacking the environment of the client does not
+    // replace the last good one in the persistent cache
+    #[rstest]
+    fn test_invalid_configuration_not_cached(
+        example_configuration_enterprise: crate::models::Configuration,
+    ) {
+        let path = std::env::temp_dir().join(format!(
+            "appconfiguration-rust-sdk-test-invalid-{}.json",
+            std::process::id()
+        ));
+        let options = ClientOptions {
+            persistent_cache: Some(path.clone()),
+            ..ClientOptions::default()
+        };
+        let configuration_id = |environment_id: &str| ConfigurationId {
+            environment_id: environment_id.to_string(),
+            collection_id: "car-rentals".to_string(),
+            ..ConfigurationId::default()
+        };
+        let content = cache::serialize_configuration(&example_configuration_enterprise).unwrap();
+        AppConfigurationClient::snapshot_from_configuration(
+            example_configuration_enterprise,
+            &configuration_id("dev"),
+            &options,
+        )
+        .unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), content);
+
+        let result = AppConfigurationClient::snapshot_from_configuration(
+            crate::models::Configuration {
+                environments: Vec::new(),
+                segments: Vec::new(),
+            },
+            &configuration_id("dev"),
+            &options,
+        );
+        let cached = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(result.is_err());
+        assert_eq!(cached, content);
+    }
+
+    #[rstest]
+    fn test_last_updated(example_configuration_enterprise: crate::models::Configuration) {
+        let before = SystemTime::now();
+        let snapshot =
+            ConfigurationSnapshot::new("dev", None, example_configuration_enterprise).unwrap();
+        let client = AppConfigurationClient::new_offline(snapshot);
+
+        let last_updated = client.last_updated().unwrap();
+        assert!(before <= last_updated && last_updated <= SystemTime::now());
+    }
+
+    #[rstest]
+    fn test_feature_proxy_records_evaluations(
+        example_configuration_enterprise: crate::models::Configuration,
+    ) {
+        #[derive(Default)]
+        struct Recorder(Mutex<usize>);
+        impl crate::client::MetricsRecorder for Arc<Recorder> {
+            fn record_evaluation(
+                &self,
+                _feature_id: &str,
+                _reason: crate::client::feature::EvaluationReason,
+            ) {
+                *self.0.lock().unwrap() += 1;
+            }
+        }
+
+        let snapshot =
+            ConfigurationSnapshot::new("dev", None, example_configuration_enterprise).unwrap();
+        let mut client = AppConfigurationClient::new_offline(snapshot);
+        let recorder = Arc::new(Recorder::default());
+        client.metrics = Metrics::new(recorder.clone());
+
+        let proxy = client.get_feature_proxy("f1").unwrap();
+        proxy
+            .get_current_value(&crate::tests::TrivialEntity {})
+            .unwrap();
+        proxy
+            .with_snapshot(|feature| feature.get_value(&crate::tests::TrivialEntity {}))
+            .unwrap()
+            .unwrap();
+        assert_eq!(*recorder.0.lock().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_configuration_id_of_client_from_file() {
+        let mut path = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("data/data-dump-enterprise-plan-sdk-testing.json");
+        let client = AppConfigurationClient::from_file(&path, "dev", "car-rentals").unwrap();
+
+        assert_eq!(client.environment_id(), "dev");
+        assert_eq!(client.collection_id(), "car-rentals");
+        assert_eq!(client.region(), "");
+        assert_eq!(client.guid(), "");
+    }
+
+    #[rstest]
+    fn test_clones_share_configuration(
+        example_configuration_enterprise: crate::models::Configuration,
+    ) {
+        let snapshot =
+            ConfigurationSnapshot::new("dev", None, example_configuration_enterprise).unwrap();
+        let client = AppConfigurationClient::new_offline(snapshot);
+        let clone = client.clone();
+
+        client
+            .latest_config_snapshot
+            .write()
+            .unwrap()
+            .features
+            .remove("f1");
+        assert!(clone.get_feature("f1").is_err());
+    }
+
+    #[rstest]
+    fn test_recovers_from_poisoned_snapshot_lock(
+        example_configuration_enterprise: crate::models::Configuration,
+    ) {
+        let snapshot =
+            ConfigurationSnapshot::new("dev", None, example_configuration_enterprise).unwrap();
+        let client = AppConfigurationClient::new_offline(snapshot);
+        let latest_config_snapshot = client.latest_config_snapshot.clone();
+        let result = thread::spawn(move || {
+            let _guard = latest_config_snapshot.write().unwrap();
+            panic!("panicking while holding the lock");
+        })
+        .join();
+        assert!(result.is_err());
+        assert!(client.latest_config_snapshot.is_poisoned());
+
+        assert!(client.get_feature("f1").is_ok());
+        assert!(client.get_property("p1").is_ok());
+        assert!(!client.get_feature_ids().unwrap().is_empty());
+        assert!(!client.get_property_ids().unwrap().is_empty());
+        assert!(!client.latest_config_snapshot.is_poisoned());
+    }
+
+    #[rstest]
+    fn test_close_stops_background_thread(
+        example_configuration_enterprise: crate::models::Configuration,
+    ) {
+        let snapshot =
+            ConfigurationSnapshot::new("dev", None, example_configuration_enterprise).unwrap();
+        let client = AppConfigurationClient::new_offline(snapshot);
+        let monitor = client.configuration_monitor(
+            &Credentials::ApiKey("apikey".to_string()),
+            ClientOptions::default(),
+        );
+        let (terminator, background_thread) =
+            AppConfigurationClient::poll_configuration(monitor, Duration::from_secs(3600));
+        let client = AppConfigurationClient {
+            thread_terminator: terminator,
+            background_thread: Arc::new(Mutex::new(Some(background_thread))),
+            ..client
+        };
+        let clone = client.clone();
+
+        client.close().unwrap();
+        assert_eq!(clone.status().unwrap().connection, ConnectionState::Closed);
+        assert!(clone.get_feature("f1").is_ok());
+        // Closing again has nothing to wait for
+        clone.close().unwrap();
+    }
+
+    #[test]
+    fn test_thread_terminated_when_last_clone_dropped() {
+        let (sender, receiver) = std::sync::mpsc::channel::<()>();
+        let client = AppConfigurationClient {
+            thread_terminator: sender,
+            ..AppConfigurationClient::new_offline(ConfigurationSnapshot {
+                features: HashMap::new(),
+                properties: HashMap::new(),
+                segments: HashMap::new(),
+                last_updated: SystemTime::now(),
+                environment_id: "dev".to_string(),
+                environment_name: "Dev".to_string(),
+                other_environments: Vec::new(),
+            })
+        };
+        let clone = client.clone();
+
+        drop(client);
+        assert_eq!(
+            receiver.try_recv(),
+            Err(std::sync::mpsc::TryRecvError::Empty)
+        );
+        drop(clone);
+        assert_eq!(
+            receiver.try_recv(),
+            Err(std::sync::mpsc::TryRecvError::Disconnected)
+        );
     }
 }
@@ -0,0 +1,387 @@
+// (C) Copyright IBM Corp. 2024.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use futures_util::{SinkExt, StreamExt};
+use tokio::time::{Instant, Interval};
+
+use crate::client::app_configuration_client::{
+    AppConfigurationClient, ClientOptions, ConfigurationId, ConfigurationMonitor, UpdateMode,
+};
+use crate::client::builder::AppConfigurationClientBuilder;
+use crate::client::http::{AccessToken, Credentials};
+use crate::client::http_async::{self, MonitoringWebSocket};
+use crate::client::status::ConnectionState;
+use crate::errors::{Error, Result};
+
+/// How often the background task checks whether its client was dropped.
+const TERMINATION_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+impl AppConfigurationClient {
+    /// Like [`AppConfigurationClient::new`], for async applications: the
+    /// initial configuration is retrieved without blocking, and changes are
+    /// monitored by a task on the current `tokio` runtime instead of a
+    /// thread. The task stops when the last clone of the client is dropped.
+    ///
+    /// Features and properties are evaluated the same as with any other
+    /// client, synchronously.
+    ///
+    /// # Panics
+    ///
+    /// If not called from within a `tokio` runtime.
+    pub async fn new_async(
+        apikey: &str,
+        region: &str,
+        guid: &str,
+        environment_id: &str,
+        collection_id: &str,
+    ) -> Result<Self> {
+        AppConfigurationClientBuilder::default()
+            .apikey(apikey)
+            .region(region)
+            .guid(guid)
+            .environment_id(environment_id)
+            .collection_id(collection_id)
+            .build_async()
+            .await
+    }
+
+    pub(crate) async fn create_async(
+        credentials: Credentials,
+        configuration_id: ConfigurationId,
+        options: ClientOptions,
+    ) -> Result<Self> {
+        // Populate initial configuration
+        let result = async {
+            let mut access_token = credentials.initial_access_token();
+            if access_token.needs_renewal() {
+                access_token =
+                    http_async::get_access_token(&credentials, &options.http_options).await?;
+            }
+            let configuration = http_async::get_configuration(
+                &access_token.token,
+                &configuration_id.region,
+                &configuration_id.guid,
+                &configuration_id.collection_id,
+                &configuration_id.environment_id,
+                &options.http_options,
+            )
+            .await?;
+            Self::snapshot_from_configuration(configuration, &configuration_id, &options)
+        }
+        .await;
+        let (mut client, from_persistent_cache) =
+            Self::with_initial_configuration(result, &credentials, configuration_id, &options)?;
+        let updates_optional = from_persistent_cache || !options.require_live_updates;
+
+        // start monitoring configuration
+        let monitor = client.configuration_monitor(&credentials, options);
+        let (sender, terminator) = mpsc::channel();
+        match MonitoringTask::start(monitor, terminator).await {
+            Ok(()) => client.thread_terminator = sender,
+            Err(e) => client.monitoring_failed(e, updates_optional)?,
+        }
+        Ok(client)
+    }
+}
+
+impl ConfigurationMonitor {
+    /// Like [`ConfigurationMonitor::connect`], without blocking.
+    async fn connect_async(&mut self) -> Result<MonitoringWebSocket> {
+        if self.access_token.needs_renewal() {
+            self.access_token =
+                http_async::get_access_token(&self.credentials, &self.options.http_options).await?;
+        }
+        let result = http_async::get_configuration_monitoring_websocket(
+            &self.access_token.token,
+            &self.configuration_id.region,
+            &self.configuration_id.guid,
+            &self.configuration_id.collection_id,
+            &self.configuration_id.environment_id,
+            &self.options.http_options,
+        )
+        .await;
+        let socket = result.inspect_err(|_| {
+            // The access token might have expired
+            self.access_token = AccessToken::default();
+        })?;
+        self.set_connection_state(ConnectionState::Connected);
+        Ok(socket)
+    }
+
+    /// Like [`ConfigurationMonitor::update_configuration`], without blocking.
+    async fn update_configuration_async(&self) -> Result<()> {
+        let configuration = http_async::get_configuration(
+            &self.access_token.token,
+            &self.configuration_id.region,
+            &self.configuration_id.guid,
+            &self.configuration_id.collection_id,
+            &self.configuration_id.environment_id,
+            &self.options.http_options,
+        )
+        .await?;
+        self.apply_configuration(configuration)
+    }
+
+    /// Like [`ConfigurationMonitor::update_configuration_with_valid_token`],
+    /// without blocking.
+    async fn update_configuration_with_valid_token_async(&mut self) -> Result<()> {
+        match self.update_configuration_async().await {
+            Err(Error::AuthenticationError { status, .. }) => {
+                log::warn!("Access token rejected ({status}). Renewing it.");
+                self.access_token =
+                    http_async::get_access_token(&self.credentials, &self.options.http_options)
+                        .await?;
+                self.update_configuration_async().await
+            }
+            result => result,
+        }
+    }
+
+    /// Like [`ConfigurationMonitor::poll`], without blocking.
+    async fn poll_async(&mut self) -> Result<()> {
+        if self.access_token.needs_renewal() {
+            self.access_token =
+                http_async::get_access_token(&self.credentials, &self.options.http_options).await?;
+        }
+        if let Err(e) = self.update_configuration_async().await {
+            // The access token might have expired
+            self.access_token = AccessToken::default();
+            return Err(e);
+        }
+        self.set_connection_state(ConnectionState::Connected);
+        Ok(())
+    }
+}
+
+/// The task keeping the configuration of a client up to date, until the
+/// client is dropped.
+struct MonitoringTask {
+    monitor: ConfigurationMonitor,
+    // The client is closed once a message is received, or dropped once all
+    // clones of the sender are gone
+    terminator: mpsc::Receiver<()>,
+    termination_check: Interval,
+}
+
+impl MonitoringTask {
+    /// Connects `monitor` to the server and spawns the task on the current
+    /// runtime. Fails if the connection cannot be established.
+    async fn start(
+        mut monitor: ConfigurationMonitor,
+        terminator: mpsc::Receiver<()>,
+    ) -> Result<()> {
+        #[cfg(feature = "tracing")]
+        let span = monitor.span(match monitor.options.update_mode {
+            UpdateMode::WebSocket => "configuration_monitoring",
+            UpdateMode::Poll { .. } => "configuration_polling",
+        });
+        let task: Pin<Box<dyn Future<Output = ()> + Send>> = match monitor.options.update_mode {
+            UpdateMode::WebSocket => {
+                let socket = monitor.connect_async().await?;
+                Box::pin(Self::new(monitor, terminator).update_configuration_on_change(socket))
+            }
+            UpdateMode::Poll { interval } => {
+                if monitor.access_token.needs_renewal() {
+                    monitor.access_token = http_async::get_access_token(
+                        &monitor.credentials,
+                        &monitor.options.http_options,
+                    )
+                    .await?;
+                }
+                monitor.set_connection_state(ConnectionState::Connected);
+                Box::pin(Self::new(monitor, terminator).poll_configuration(interval))
+            }
+        };
+        #[cfg(feature = "tracing")]
+        let task = tracing::Instrument::instrument(task, span);
+        tokio::spawn(task);
+        Ok(())
+    }
+
+    fn new(monitor: ConfigurationMonitor, terminator: mpsc::Receiver<()>) -> Self {
+        Self {
+            monitor,
+            terminator,
+            termination_check: tokio::time::interval(TERMINATION_CHECK_INTERVAL),
+        }
+    }
+
+    fn should_stop(&self) -> bool {
+        self.terminator.try_recv() != Err(mpsc::TryRecvError::Empty)
+    }
+
+    /// Waits for `delay`. Returns `false` if the client is closed or dropped
+    /// meanwhile.
+    async fn sleep(&mut self, delay: Duration) -> bool {
+        let deadline = Instant::now() + delay;
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep_until(deadline) => return !self.should_stop(),
+                _ = self.termination_check.tick() => {
+                    if self.should_stop() {
+                        return false;
+                    }
+                }
+            }
+        }
+    }
+
+    async fn update_configuration_on_change(mut self, mut socket: MonitoringWebSocket) {
+        let mut last_activity = Instant::now();
+        loop {
+            let message = tokio::select! {
+                message = socket.next() => message,
+                _ = self.termination_check.tick() => {
+                    if self.should_stop() {
+                        break;
+                    }
+                    if self.monitor.access_token.needs_renewal() {
+                        let result = http_async::get_access_token(
+                            &self.monitor.credentials,
+                            &self.monitor.options.http_options,
+                        )
+                        .await;
+                        self.monitor.access_token_renewed(result);
+                    }
+                    if self.monitor.take_environment_change() {
+                        match self.reconnect().await {
+                            Some(new_socket) => {
+                                socket = new_socket;
+                                last_activity = Instant::now();
+                                continue;
+                            }
+                            None => break,
+                        }
+                    }
+                    if last_activity.elapsed() < self.monitor.options.ping_interval {
+                        continue;
+                    }
+                    log::debug!("Connection idle, sending a ping.");
+                    last_activity = Instant::now();
+                    match socket.send(tungstenite::Message::Ping(Vec::new())).await {
+                        Ok(()) => continue,
+                        Err(e) => Some(Err(e)),
+                    }
+                }
+            };
+            last_activity = Instant::now();
+            let result = match message {
+                // The pong to a ping is sent when the socket is next polled
+                Some(message) => message
+                    .map_err(Error::from)
+                    .and_then(ConfigurationMonitor::is_configuration_change),
+                None => Err(tungstenite::Error::ConnectionClosed.into()),
+            };
+            match result {
+                Ok(true) => {
+                    if let Err(e) = self
+                        .monitor
+                        .update_configuration_with_valid_token_async()
+                        .await
+                    {
+                        log::error!("Error getting config snapshot: {}", e);
+                    }
+                }
+                Ok(false) => {}
+                Err(e) => {
+                    log::warn!(
+                        "Lost connection to monitor configuration changes ({e}). Reconnecting."
+                    );
+                    self.monitor
+                        .set_connection_state(ConnectionState::Disconnected);
+                    match self.reconnect().await {
+                        Some(new_socket) => {
+                            socket = new_socket;
+                            last_activity = Instant::now();
+                        }
+                        None => break,
+                    }
+                }
+            }
+        }
+    }
+
+    /// Like [`ConfigurationMonitor::reconnect`], without blocking.
+    async fn reconnect(&mut self) -> Option<MonitoringWebSocket> {
+        let mut delay = self.monitor.options.http_options.retry_base_delay;
+        loop {
+            if !self.sleep(delay).await {
+                return None;
+            }
+            match self.monitor.connect_async().await {
+                Ok(socket) => {
+                    if let Err(e) = self
+                        .monitor
+                        .update_configuration_with_valid_token_async()
+                        .await
+                    {
+                        log::error!("Error getting config snapshot: {}", e);
+                    }
+                    return Some(socket);
+                }
+                Err(e) => {
+                    delay = delay
+                        .saturating_mul(2)
+                        .min(self.monitor.options.max_reconnect_delay);
+                    log::warn!("Cannot reconnect ({e}). Retrying in {delay:?}.");
+                }
+            }
+        }
+    }
+
+    async fn poll_configuration(mut self, interval: Duration) {
+        while self.sleep(interval).await {
+            self.monitor.take_environment_change();
+            if let Err(e) = self.monitor.poll_async().await {
+                log::warn!("Cannot poll configuration ({e}). Retrying in {interval:?}.");
+                self.monitor
+                    .set_connection_state(ConnectionState::Disconnected);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::cache::ConfigurationSnapshot;
+    use crate::models::tests::example_configuration_enterprise;
+    use rstest::rstest;
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_task_stops_when_client_is_dropped(
+        example_configuration_enterprise: crate::models::Configuration,
+    ) {
+        let snapshot =
+            ConfigurationSnapshot::new("dev", None, example_configuration_enterprise).unwrap();
+        let client = AppConfigurationClient::new_offline(snapshot);
+        let monitor = client.configuration_monitor(
+            &Credentials::ApiKey("apikey".to_string()),
+            ClientOptions::default(),
+        );
+        let (sender, terminator) = mpsc::channel();
+        let mut task = MonitoringTask::new(monitor, terminator);
+
+        assert!(task.sleep(Duration::from_millis(1)).await);
+        drop(sender);
+        assert!(!task.sleep(Duration::from_secs(60)).await);
+        assert!(task.reconnect().await.is_none());
+    }
+}
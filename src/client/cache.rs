@@ -13,19 +13,144 @@
 // limitations under the License.
 
 use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::SystemTime;
 
-use crate::errors::{ConfigurationAccessError, Result};
-use crate::models::{Configuration, Feature, Property, Segment};
+use crate::errors::{ConfigurationAccessError, DeserializationError, Error, Result};
+use crate::models::{in_collection, Configuration, Environment, Feature, Property, Segment};
+use crate::segment_evaluation::SUPPORTED_OPERATORS;
 
-#[derive(Debug, Default)]
+/// Reads a configuration stored as JSON in `path`, as returned by the
+/// App Configuration `/config` endpoint.
+pub(crate) fn read_configuration_file(path: &Path) -> Result<Configuration> {
+    let content = std::fs::read_to_string(path)?;
+    parse_configuration(&content)
+}
+
+/// Top-level keys of a configuration this SDK cannot do without.
+const REQUIRED_CONFIGURATION_KEYS: [&str; 2] = ["environments", "segments"];
+
+/// Parses a configuration given as JSON, as returned by the App
+/// Configuration `/config` endpoint.
+///
+/// Fails with [`Error::UnsupportedConfigurationSchema`] if a JSON object
+/// lacks any of the required top-level keys, as configurations of a newer
+/// schema might.
+pub(crate) fn parse_configuration(content: &str) -> Result<Configuration> {
+    serde_json::from_str(content).map_err(|e| {
+        let missing_keys = missing_configuration_keys(content);
+        if !missing_keys.is_empty() {
+            return Error::UnsupportedConfigurationSchema { missing_keys };
+        }
+        DeserializationError {
+            string: content.to_string(),
+            source: e.into(),
+        }
+        .into()
+    })
+}
+
+/// The required top-level keys `content` lacks, if it is a JSON object.
+fn missing_configuration_keys(content: &str) -> Vec<String> {
+    match serde_json::from_str::<serde_json::Value>(content) {
+        Ok(serde_json::Value::Object(configuration)) => REQUIRED_CONFIGURATION_KEYS
+            .iter()
+            .filter(|key| !configuration.contains_key(**key))
+            .map(|key| key.to_string())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Serializes `configuration` as JSON, see [`write_configuration_file`].
+pub(crate) fn serialize_configuration(configuration: &Configuration) -> Result<String> {
+    serde_json::to_string(configuration)
+        .map_err(|e| Error::Other(format!("Cannot serialize configuration: {e}")))
+}
+
+/// Writes `content`, a configuration serialized with
+/// [`serialize_configuration`], to `path`. The file is replaced atomically,
+/// so readers never see a partially written configuration.
+pub(crate) fn write_configuration_file(path: &Path, content: &str) -> Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    std::fs::write(&tmp_path, content)?;
+    std::fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/// A problem of a configuration which only shows when evaluating some
+/// entities, see [`AppConfigurationClient::validate_configuration`](crate::client::AppConfigurationClient::validate_configuration).
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[non_exhaustive]
+pub enum ConfigurationIssue {
+    /// A rule of segment `segment_id` uses an operator this SDK does not
+    /// support. Evaluating the rule fails.
+    UnknownOperator {
+        segment_id: String,
+        attribute_name: String,
+        operator: String,
+    },
+    /// The weights of the weighted values of the targeting rule `order` of
+    /// feature `feature_id` add up to `total` instead of 100. Evaluating the
+    /// feature for entities matching the rule fails.
+    InvalidWeightedValues {
+        feature_id: String,
+        order: u32,
+        total: u64,
+    },
+}
+
+impl std::fmt::Display for ConfigurationIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownOperator {
+                segment_id,
+                attribute_name,
+                operator,
+            } => write!(
+                f,
+                "Segment '{segment_id}' checks attribute '{attribute_name}' with unknown operator '{operator}'"
+            ),
+            Self::InvalidWeightedValues {
+                feature_id,
+                order,
+                total,
+            } => write!(
+                f,
+                "Weights of targeting rule {order} of feature '{feature_id}' add up to {total} instead of 100"
+            ),
+        }
+    }
+}
+
+#[derive(Debug)]
 pub(crate) struct ConfigurationSnapshot {
-    pub(crate) features: HashMap<String, Feature>,
-    pub(crate) properties: HashMap<String, Property>,
-    pub(crate) segments: HashMap<String, Segment>,
+    pub(crate) features: HashMap<String, Arc<Feature>>,
+    pub(crate) properties: HashMap<String, Arc<Property>>,
+    pub(crate) segments: HashMap<String, Arc<Segment>>,
+    /// When the configuration was received from the server (or read from a file).
+    pub(crate) last_updated: SystemTime,
+    /// The environment the features and properties are taken from.
+    pub(crate) environment_id: String,
+    pub(crate) environment_name: String,
+    /// The remaining environments of the configuration, to switch to them
+    /// without fetching the configuration again.
+    pub(crate) other_environments: Vec<Environment>,
+}
+
+/// Snapshots are equal if their content is, regardless of when they were
+/// received.
+impl PartialEq for ConfigurationSnapshot {
+    fn eq(&self, other: &Self) -> bool {
+        self.features == other.features
+            && self.properties == other.properties
+            && self.segments == other.segments
+    }
 }
 
 impl ConfigurationSnapshot {
-    pub fn get_feature(&self, feature_id: &str) -> Result<&Feature> {
+    pub fn get_feature(&self, feature_id: &str) -> Result<&Arc<Feature>> {
         self.features.get(feature_id).ok_or_else(|| {
             ConfigurationAccessError::FeatureNotFound {
                 feature_id: feature_id.to_string(),
@@ -34,7 +159,7 @@ impl ConfigurationSnapshot {
         })
     }
 
-    pub fn get_property(&self, property_id: &str) -> Result<&Property> {
+    pub fn get_property(&self, property_id: &str) -> Result<&Arc<Property>> {
         self.properties.get(property_id).ok_or_else(|| {
             ConfigurationAccessError::PropertyNotFound {
                 property_id: property_id.to_string(),
@@ -43,50 +168,246 @@ impl ConfigurationSnapshot {
         })
     }
 
-    pub fn new(environment_id: &str, configuration: Configuration) -> Result<Self> {
-        let environment = configuration
-            .environments
-            .into_iter()
-            .find(|e| e.environment_id == environment_id)
-            .ok_or(ConfigurationAccessError::EnvironmentNotFound {
-                environment_id: environment_id.to_string(),
-            })?;
-        // FIXME: why not filtering for collection here?
-
-        let mut features = HashMap::new();
-        for feature in environment.features {
-            features.insert(feature.feature_id.clone(), feature);
-        }
+    pub fn get_segment(&self, segment_id: &str) -> Result<&Arc<Segment>> {
+        self.segments.get(segment_id).ok_or_else(|| {
+            ConfigurationAccessError::SegmentNotFound {
+                segment_id: segment_id.to_string(),
+            }
+            .into()
+        })
+    }
 
-        let mut properties = HashMap::new();
-        for property in environment.properties {
-            properties.insert(property.property_id.clone(), property);
+    /// Makes a snapshot of the environment `environment_id` of
+    /// `configuration`. If `collection_id` is given, only features and
+    /// properties of that collection are kept.
+    pub fn new(
+        environment_id: &str,
+        collection_id: Option<&str>,
+        configuration: Configuration,
+    ) -> Result<Self> {
+        let mut other_environments = configuration.environments;
+        if let Some(collection_id) = collection_id {
+            for environment in &mut other_environments {
+                environment
+                    .features
+                    .retain(|feature| in_collection(&feature.collections, collection_id));
+                environment
+                    .properties
+                    .retain(|property| in_collection(&property.collections, collection_id));
+            }
         }
+        let environment = take_environment(&mut other_environments, environment_id)?;
 
         let mut segments = HashMap::new();
         for segment in configuration.segments {
-            segments.insert(segment.segment_id.clone(), segment.clone());
+            segments.insert(segment.segment_id.clone(), Arc::new(segment));
         }
-        Ok(ConfigurationSnapshot {
-            features,
-            properties,
+        let snapshot = ConfigurationSnapshot {
+            features: features_by_id(environment.features),
+            properties: properties_by_id(environment.properties),
             segments,
-        })
+            last_updated: SystemTime::now(),
+            environment_id: environment.environment_id,
+            environment_name: environment.name,
+            other_environments,
+        };
+        for issue in snapshot.validate() {
+            log::warn!("{issue}.");
+        }
+        Ok(snapshot)
+    }
+
+    /// Returns the problems of the configuration which would only show when
+    /// evaluating some entity, sorted by segment and feature.
+    pub fn validate(&self) -> Vec<ConfigurationIssue> {
+        let mut issues = self
+            .segments
+            .values()
+            .flat_map(|segment| {
+                segment
+                    .rules
+                    .iter()
+                    .filter(|rule| !SUPPORTED_OPERATORS.contains(&rule.operator.as_str()))
+                    .map(|rule| ConfigurationIssue::UnknownOperator {
+                        segment_id: segment.segment_id.clone(),
+                        attribute_name: rule.attribute_name.clone(),
+                        operator: rule.operator.clone(),
+                    })
+            })
+            .collect::<Vec<_>>();
+        issues.extend(self.features.values().flat_map(|feature| {
+            feature.segment_rules.iter().filter_map(|segment_rule| {
+                match segment_rule.check_weighted_values(&feature.feature_id) {
+                    Err(Error::InvalidWeightedValues { order, total, .. }) => {
+                        Some(ConfigurationIssue::InvalidWeightedValues {
+                            feature_id: feature.feature_id.clone(),
+                            order,
+                            total,
+                        })
+                    }
+                    _ => None,
+                }
+            })
+        }));
+        issues.sort();
+        issues
+    }
+
+    /// Takes features and properties from `environment_id`, one of the
+    /// other environments of the configuration, instead of the current one.
+    pub fn switch_environment(&mut self, environment_id: &str) -> Result<()> {
+        if environment_id == self.environment_id {
+            return Ok(());
+        }
+        let environment = take_environment(&mut self.other_environments, environment_id)?;
+        let previous = Environment {
+            name: std::mem::replace(&mut self.environment_name, environment.name),
+            environment_id: std::mem::replace(&mut self.environment_id, environment.environment_id),
+            features: std::mem::replace(&mut self.features, features_by_id(environment.features))
+                .into_values()
+                .map(Arc::unwrap_or_clone)
+                .collect(),
+            properties: std::mem::replace(
+                &mut self.properties,
+                properties_by_id(environment.properties),
+            )
+            .into_values()
+            .map(Arc::unwrap_or_clone)
+            .collect(),
+        };
+        self.other_environments.push(previous);
+        Ok(())
     }
 }
 
+/// Removes the environment `environment_id` from `environments`.
+fn take_environment(
+    environments: &mut Vec<Environment>,
+    environment_id: &str,
+) -> Result<Environment> {
+    let index = environments
+        .iter()
+        .position(|e| e.environment_id == environment_id)
+        .ok_or_else(|| ConfigurationAccessError::EnvironmentNotFound {
+            environment_id: environment_id.to_string(),
+        })?;
+    Ok(environments.swap_remove(index))
+}
+
+fn features_by_id(features: Vec<Feature>) -> HashMap<String, Arc<Feature>> {
+    features
+        .into_iter()
+        .map(|feature| (feature.feature_id.clone(), Arc::new(feature)))
+        .collect()
+}
+
+fn properties_by_id(properties: Vec<Property>) -> HashMap<String, Arc<Property>> {
+    properties
+        .into_iter()
+        .map(|property| (property.property_id.clone(), Arc::new(property)))
+        .collect()
+}
+
+/// Locks `snapshot` for reading. If a thread panicked while holding the
+/// lock, the snapshot is used anyway: a stale configuration is better than
+/// a client failing on every call.
+pub(crate) fn read_snapshot(
+    snapshot: &RwLock<ConfigurationSnapshot>,
+) -> RwLockReadGuard<'_, ConfigurationSnapshot> {
+    snapshot.read().unwrap_or_else(|e| {
+        log::warn!("Recovering configuration snapshot from a panicked thread.");
+        snapshot.clear_poison();
+        e.into_inner()
+    })
+}
+
+/// Locks `snapshot` for writing, recovering it like [`read_snapshot`].
+pub(crate) fn write_snapshot(
+    snapshot: &RwLock<ConfigurationSnapshot>,
+) -> RwLockWriteGuard<'_, ConfigurationSnapshot> {
+    snapshot.write().unwrap_or_else(|e| {
+        log::warn!("Recovering configuration snapshot from a panicked thread.");
+        snapshot.clear_poison();
+        e.into_inner()
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::errors::Error;
     use crate::models::tests::example_configuration_enterprise;
-    use crate::models::Configuration;
+    use crate::models::{ConfigValue, Configuration, WeightedValue};
+    use crate::segment_evaluation::{EvaluationOptions, SegmentMemo};
+    use crate::AttrValue;
     use rstest::*;
 
+    #[rstest]
+    fn test_write_and_read_configuration_file(example_configuration_enterprise: Configuration) {
+        let path = std::env::temp_dir().join(format!(
+            "appconfiguration-rust-sdk-test-{}.json",
+            std::process::id()
+        ));
+        let content = serialize_configuration(&example_configuration_enterprise).unwrap();
+        write_configuration_file(&path, &content).unwrap();
+        let configuration = read_configuration_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            serde_json::to_value(&configuration).unwrap(),
+            serde_json::to_value(&example_configuration_enterprise).unwrap()
+        );
+        let snapshot = ConfigurationSnapshot::new("dev", None, configuration).unwrap();
+        let mut features = snapshot.features.keys().cloned().collect::<Vec<_>>();
+        features.sort();
+        assert_eq!(features, vec!["f1", "f2", "f3", "f4", "f5", "f6"]);
+        assert_eq!(
+            snapshot.get_feature("f1").unwrap().enabled_value.as_i64(),
+            Some(5)
+        );
+    }
+
+    #[rstest]
+    #[case(r#"{"environments": []}"#, vec!["segments"])]
+    #[case(r#"{"segments": []}"#, vec!["environments"])]
+    #[case(r#"{}"#, vec!["environments", "segments"])]
+    fn test_parse_configuration_of_unsupported_schema(
+        #[case] content: &str,
+        #[case] expected: Vec<&str>,
+    ) {
+        let result = parse_configuration(content);
+        assert!(matches!(
+            result,
+            Err(Error::UnsupportedConfigurationSchema { missing_keys }) if missing_keys == expected
+        ));
+    }
+
+    #[test]
+    fn test_parse_truncated_configuration() {
+        let content = r#"{"environments": [], "segments": [{"name": "#;
+        let result = parse_configuration(content);
+        assert!(matches!(result, Err(Error::DeserializationError(_))));
+    }
+
+    #[rstest]
+    fn test_snapshots_differ_on_content(example_configuration_enterprise: Configuration) {
+        let json = serde_json::to_string(&example_configuration_enterprise).unwrap();
+        let snapshot =
+            ConfigurationSnapshot::new("dev", None, example_configuration_enterprise).unwrap();
+        let mut other =
+            ConfigurationSnapshot::new("dev", None, serde_json::from_str(&json).unwrap()).unwrap();
+        assert_eq!(snapshot, other);
+
+        Arc::make_mut(other.features.get_mut("f1").unwrap()).enabled = false;
+        assert_ne!(snapshot, other);
+    }
+
     #[rstest]
     fn test_filter_configurations(example_configuration_enterprise: Configuration) {
-        let result =
-            ConfigurationSnapshot::new("does_for_sure_not_exist", example_configuration_enterprise);
+        let result = ConfigurationSnapshot::new(
+            "does_for_sure_not_exist",
+            None,
+            example_configuration_enterprise,
+        );
         assert!(result.is_err());
 
         assert!(matches!(
@@ -94,4 +415,72 @@ mod tests {
                 Error::ConfigurationAccessError(ref e)
                 if matches!(e, ConfigurationAccessError::EnvironmentNotFound { ref environment_id} if environment_id == "does_for_sure_not_exist")));
     }
+
+    // Weights are checked in all environments, not only the one of the snapshot
+    #[rstest]
+    fn test_invalid_weighted_values(mut example_configuration_enterprise: Configuration) {
+        let feature = &mut example_configuration_enterprise.environments[0].features[0];
+        assert_eq!(feature.feature_id, "f1");
+        feature.segment_rules[0].weighted_values = vec![
+            WeightedValue {
+                value: ConfigValue(serde_json::json!(1)),
+                weight: 30,
+            },
+            WeightedValue {
+                value: ConfigValue(serde_json::json!(2)),
+                weight: 60,
+            },
+        ];
+        let snapshot =
+            ConfigurationSnapshot::new("dev", None, example_configuration_enterprise).unwrap();
+        assert_eq!(
+            snapshot.validate(),
+            vec![ConfigurationIssue::InvalidWeightedValues {
+                feature_id: "f1".to_string(),
+                order: 1,
+                total: 90,
+            }]
+        );
+
+        // Only evaluations for entities matching the rule fail
+        let evaluate = |attributes: HashMap<String, AttrValue>| {
+            crate::client::feature::evaluate(
+                snapshot.get_feature("f1").unwrap(),
+                &snapshot.segments,
+                &crate::tests::GenericEntity {
+                    id: "a1".to_string(),
+                    attributes,
+                },
+                &EvaluationOptions::default(),
+                &SegmentMemo::default(),
+            )
+        };
+        assert!(evaluate(HashMap::new()).is_ok());
+        assert!(matches!(
+            evaluate(HashMap::from([("code".to_string(), AttrValue::from("CHG1"))])),
+            Err(Error::InvalidWeightedValues { ref resource_id, order: 1, total: 90 })
+                if resource_id == "f1"
+        ));
+    }
+
+    #[rstest]
+    fn test_validate(example_configuration_enterprise: Configuration) {
+        let mut snapshot =
+            ConfigurationSnapshot::new("dev", None, example_configuration_enterprise).unwrap();
+        assert_eq!(snapshot.validate(), vec![]);
+
+        let (segment_id, segment) = snapshot.segments.iter_mut().next().unwrap();
+        let segment_id = segment_id.clone();
+        let rule = &mut Arc::make_mut(segment).rules[0];
+        rule.operator = "equals".to_string();
+        let attribute_name = rule.attribute_name.clone();
+        assert_eq!(
+            snapshot.validate(),
+            vec![ConfigurationIssue::UnknownOperator {
+                segment_id,
+                attribute_name,
+                operator: "equals".to_string(),
+            }]
+        );
+    }
 }
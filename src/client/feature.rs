@@ -12,112 +12,606 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::client::value::{NumericValue, Value};
+use crate::client::app_configuration_client::AppConfigurationClient;
+use crate::client::segment::Segment;
+use crate::client::value::Value;
 use crate::entity::Entity;
 use std::collections::HashMap;
+use std::sync::Arc;
 
-use super::feature_proxy::random_value;
-use crate::segment_evaluation::find_applicable_segment_rule_for_entity;
+use super::feature_proxy::{rollout_bucket_with_seed, variant_bucket_with_seed};
+use super::metrics::Metrics;
+use super::usage::UsageRecorder;
+use crate::models::{check_rollout_percentage, ConfigValueOrDefault, ValueKind};
+use crate::segment_evaluation::{
+    find_applicable_segment_rule_for_entity_memoized, EvaluationOptions, SegmentMemo,
+};
 
-use crate::errors::{Error, Result};
+use crate::errors::{ConfigurationAccessError, Error, ResourceKind, Result};
 
+/// Why a feature evaluated to a value for an entity, see
+/// [`Feature::get_value_with_details`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvaluationReason {
+    /// The feature is disabled: the value is its disabled value.
+    FeatureDisabled,
+    /// The entity belongs to the segments of the targeting rule with the
+    /// given order, and is part of the rule's rollout.
+    TargetingMatch { order: u32 },
+    /// The entity is not part of the rollout of the matching targeting rule
+    /// (or of the feature, if none matches): the value is the disabled value.
+    RolloutExcluded,
+    /// No targeting rule matches, and the entity is part of the feature's
+    /// rollout: the value is the enabled value.
+    Default,
+}
+
+/// The rollout applied when evaluating a feature for an entity, see
+/// [`Feature::get_value_with_rollout_details`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RolloutDetails {
+    /// Rollout percentage of the matching targeting rule (the feature's one
+    /// if the rule uses `$default`), or of the feature if no rule matches.
+    pub rollout_percentage: u32,
+    /// Rollout bucket of the entity, see
+    /// [`rollout_bucket`](crate::client::rollout_bucket).
+    pub bucket: u32,
+}
+
+impl RolloutDetails {
+    /// Whether the entity is part of the rollout: entities in buckets below
+    /// the rollout percentage are, and all of them for 100 percent.
+    fn includes_entity(&self) -> bool {
+        self.rollout_percentage == 100 || self.bucket < self.rollout_percentage
+    }
+}
+
+/// The value of a feature for an entity, why, and the rollout applied if
+/// the feature is enabled.
+pub(crate) type Evaluation = (
+    crate::models::ConfigValue,
+    EvaluationReason,
+    Option<RolloutDetails>,
+);
+
+/// A targeting rule of a feature, as returned by
+/// [`Feature::get_segment_overrides`], or with an optional `value` as part
+/// of a [`FeaturePreview`].
+#[derive(Debug, PartialEq)]
+pub struct SegmentOverride<V = Value> {
+    /// Rules are evaluated by increasing order, the first one matching an
+    /// entity applies.
+    pub order: u32,
+    /// The rule matches entities belonging to any of these segments.
+    pub segment_ids: Vec<String>,
+    /// Value for matching entities which are part of the rollout. The
+    /// enabled value of the feature if the rule does not override it, or
+    /// `None` in a [`FeaturePreview`].
+    pub value: V,
+    /// Percentage of the matching entities which get `value`, the others get
+    /// the disabled value.
+    pub rollout_percentage: u32,
+}
+
+/// A targeting rule of a feature with the segments it references, as
+/// returned by [`Feature::get_targeting_rules`].
+#[derive(Debug)]
+pub struct TargetingRuleView {
+    /// Rules are evaluated by increasing order, the first one matching an
+    /// entity applies.
+    pub order: u32,
+    /// The rule matches entities belonging to any of these segments.
+    pub segments: Vec<Segment>,
+    /// Value for matching entities which are part of the rollout, with
+    /// `$default` resolved.
+    pub value: Value,
+    /// Percentage of the matching entities which get `value`, with
+    /// `$default` resolved.
+    pub rollout_percentage: u32,
+}
+
+/// The configured values of a feature, as returned by [`Feature::preview`].
+#[derive(Debug, PartialEq)]
+pub struct FeaturePreview {
+    /// Value for entities the feature is enabled for, unless a segment
+    /// override applies.
+    pub enabled_value: Value,
+    /// Value if the feature is disabled, or for entities excluded from the
+    /// rollout.
+    pub disabled_value: Value,
+    /// Percentage of entities the feature is enabled for, when no segment
+    /// override applies.
+    pub rollout_percentage: u32,
+    /// Segment overrides sorted by their order. The value of those
+    /// inheriting the enabled value is `None`.
+    pub segment_overrides: Vec<SegmentOverride<Option<Value>>>,
+}
+
+/// A feature as it was when retrieved with
+/// [`AppConfigurationClient::get_feature`]: later configuration updates are
+/// not visible, until the feature is retrieved again or
+/// [`Feature::refresh`]ed. Use [`AppConfigurationClient::get_feature_proxy`]
+/// to always evaluate against the latest configuration.
 #[derive(Debug)]
 pub struct Feature {
-    feature: crate::models::Feature,
-    segments: HashMap<String, crate::models::Segment>,
+    feature: Arc<crate::models::Feature>,
+    segments: HashMap<String, Arc<crate::models::Segment>>,
+    evaluation_options: EvaluationOptions,
+    metrics: Metrics,
+    usage: UsageRecorder,
 }
 
 impl Feature {
     pub(crate) fn new(
-        feature: crate::models::Feature,
-        segments: HashMap<String, crate::models::Segment>,
+        feature: Arc<crate::models::Feature>,
+        segments: HashMap<String, Arc<crate::models::Segment>>,
     ) -> Self {
-        Self { feature, segments }
+        Self {
+            feature,
+            segments,
+            evaluation_options: EvaluationOptions::default(),
+            metrics: Metrics::default(),
+            usage: UsageRecorder::default(),
+        }
+    }
+
+    pub(crate) fn with_evaluation_options(mut self, evaluation_options: EvaluationOptions) -> Self {
+        self.evaluation_options = evaluation_options;
+        self
+    }
+
+    pub(crate) fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    pub(crate) fn with_usage(mut self, usage: UsageRecorder) -> Self {
+        self.usage = usage;
+        self
+    }
+
+    /// Replaces this feature by its current state in the configuration of
+    /// `client`, as if retrieved again with
+    /// [`AppConfigurationClient::get_feature`]. Fails (leaving the feature
+    /// unchanged) if it no longer exists.
+    pub fn refresh(&mut self, client: &AppConfigurationClient) -> Result<()> {
+        *self = client.get_feature(&self.feature.feature_id)?;
+        Ok(())
+    }
+
+    /// Returns the tags of the feature, as a comma separated list.
+    pub fn get_tags(&self) -> Option<String> {
+        self.feature.tags.clone()
+    }
+
+    /// Returns the data type of the values of the feature.
+    pub fn get_data_type(&self) -> crate::models::ValueKind {
+        self.feature.kind
+    }
+
+    /// Returns the value of the feature for entities it is enabled for,
+    /// unless a segment override applies.
+    pub fn get_enabled_value(&self) -> Result<Value> {
+        Value::from_config_value(
+            &self.feature.kind,
+            self.feature.enabled_value.clone(),
+            &self.feature.feature_id,
+        )
     }
 
+    /// Returns the value of the feature if it is disabled, or for entities
+    /// excluded from its rollout.
+    pub fn get_disabled_value(&self) -> Result<Value> {
+        Value::from_config_value(
+            &self.feature.kind,
+            self.feature.disabled_value.clone(),
+            &self.feature.feature_id,
+        )
+    }
+
+    /// Returns the percentage of entities the feature is enabled for, when
+    /// no segment override applies.
+    pub fn get_rollout_percentage(&self) -> u32 {
+        self.feature.rollout_percentage
+    }
+
+    /// Returns the segment overrides (targeting rules) of the feature,
+    /// sorted by their order, with default values resolved.
+    pub fn get_segment_overrides(&self) -> Result<Vec<SegmentOverride>> {
+        self.segment_overrides()?
+            .into_iter()
+            .map(|segment_override| {
+                Ok(SegmentOverride {
+                    order: segment_override.order,
+                    segment_ids: segment_override.segment_ids,
+                    value: match segment_override.value {
+                        Some(value) => value,
+                        None => self.get_enabled_value()?,
+                    },
+                    rollout_percentage: segment_override.rollout_percentage,
+                })
+            })
+            .collect()
+    }
+
+    /// Like [`Feature::get_segment_overrides`], with no value for the
+    /// overrides inheriting the enabled value.
+    fn segment_overrides(&self) -> Result<Vec<SegmentOverride<Option<Value>>>> {
+        let mut overrides = self
+            .feature
+            .segment_rules
+            .iter()
+            .map(|segment_rule| {
+                let value = match segment_rule.value_or_default() {
+                    ConfigValueOrDefault::Default => None,
+                    ConfigValueOrDefault::Value(value) => Some(Value::from_config_value(
+                        &self.feature.kind,
+                        value.clone(),
+                        &self.feature.feature_id,
+                    )?),
+                };
+                Ok(SegmentOverride {
+                    order: segment_rule.order,
+                    segment_ids: segment_rule
+                        .rules
+                        .iter()
+                        .flat_map(|segments| segments.segments.iter().cloned())
+                        .collect(),
+                    value,
+                    rollout_percentage: segment_rule
+                        .resolve_rollout_percentage(self.feature.rollout_percentage)?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        overrides.sort_by_key(|segment_override| segment_override.order);
+        Ok(overrides)
+    }
+
+    /// Returns the enabled and disabled values, the rollout percentage and
+    /// the segment overrides of the feature, without evaluating it for an
+    /// entity. Unlike [`Feature::get_segment_overrides`], overrides using
+    /// `$default` are told apart from those overriding the enabled value.
+    pub fn preview(&self) -> Result<FeaturePreview> {
+        Ok(FeaturePreview {
+            enabled_value: self.get_enabled_value()?,
+            disabled_value: self.get_disabled_value()?,
+            rollout_percentage: self.feature.rollout_percentage,
+            segment_overrides: self.segment_overrides()?,
+        })
+    }
+
+    /// Like [`Feature::get_segment_overrides`], with the segments of each
+    /// rule (and their rules) instead of just their ids.
+    pub fn get_targeting_rules(&self) -> Result<Vec<TargetingRuleView>> {
+        self.get_segment_overrides()?
+            .into_iter()
+            .map(|segment_override| {
+                let segments = segment_override
+                    .segment_ids
+                    .into_iter()
+                    .map(|segment_id| match self.segments.get(&segment_id) {
+                        Some(segment) => Ok(Segment::new(segment.clone())),
+                        None => {
+                            Err(ConfigurationAccessError::SegmentNotFound { segment_id }.into())
+                        }
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(TargetingRuleView {
+                    order: segment_override.order,
+                    segments,
+                    value: segment_override.value,
+                    rollout_percentage: segment_override.rollout_percentage,
+                })
+            })
+            .collect()
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            skip_all,
+            fields(feature_id = %self.feature.feature_id, entity_id = %entity.get_id())
+        )
+    )]
     pub fn get_value(&self, entity: &impl Entity) -> Result<Value> {
-        let model_value = self.evaluate_feature_for_entity(entity)?;
+        Ok(self.get_value_with_details(entity)?.0)
+    }
 
-        let value = match self.feature.kind {
-            crate::models::ValueKind::Numeric => {
-                Value::Numeric(NumericValue(model_value.0.clone()))
-            }
-            crate::models::ValueKind::Boolean => {
-                Value::Boolean(model_value.0.as_bool().ok_or(Error::ProtocolError)?)
+    /// Like [`Feature::get_value`] for each of `entities`, returned in the
+    /// same order. The feature and its segments are taken from this
+    /// snapshot, so evaluating many entities needs no further lookups or
+    /// locks.
+    pub fn get_values(&self, entities: &[&impl Entity]) -> Vec<Result<Value>> {
+        entities
+            .iter()
+            .map(|entity| self.get_value(*entity))
+            .collect()
+    }
+
+    /// Fails with [`ConfigurationAccessError::FeatureTypeMismatch`] if the
+    /// feature is not of type `expected`.
+    fn check_data_type(&self, expected: ValueKind) -> Result<()> {
+        if self.feature.kind != expected {
+            return Err(ConfigurationAccessError::FeatureTypeMismatch {
+                feature_id: self.feature.feature_id.clone(),
+                expected,
+                actual: self.feature.kind,
             }
-            crate::models::ValueKind::String => Value::String(
-                model_value
-                    .0
-                    .as_str()
-                    .ok_or(Error::ProtocolError)?
-                    .to_string(),
-            ),
-        };
-        Ok(value)
+            .into());
+        }
+        Ok(())
     }
 
-    fn evaluate_feature_for_entity(
-        &self,
-        entity: &impl Entity,
-    ) -> Result<crate::models::ConfigValue> {
-        if !self.feature.enabled {
-            return Ok(self.feature.disabled_value.clone());
+    fn value_type_mismatch(&self) -> Error {
+        Error::ValueTypeMismatch {
+            expected: self.feature.kind,
+            resource_id: self.feature.feature_id.clone(),
         }
+    }
 
-        if self.feature.segment_rules.is_empty() || entity.get_attributes().is_empty() {
-            // No match possible. Do not consider segment rules:
-            return self.use_rollout_percentage_to_get_value_from_feature_directly(entity);
+    /// Like [`Feature::get_value`], for boolean features. Fails with
+    /// [`ConfigurationAccessError::FeatureTypeMismatch`] if the feature is
+    /// not of boolean type.
+    pub fn get_value_as_bool(&self, entity: &impl Entity) -> Result<bool> {
+        self.check_data_type(ValueKind::Boolean)?;
+        match self.get_value(entity)? {
+            Value::Boolean(value) => Ok(value),
+            _ => Err(self.value_type_mismatch()),
         }
+    }
 
-        match find_applicable_segment_rule_for_entity(
-            &self.segments,
-            self.feature.segment_rules.clone().into_iter(),
-            entity,
-        ) {
-            Some(segment_rule) => {
-                // Get rollout percentage
-                let rollout_percentage = match segment_rule.rollout_percentage {
-                    Some(value) => {
-                        if value.is_default() {
-                            self.feature.rollout_percentage
-                        } else {
-                            u32::try_from(value.as_u64().expect("Rollout value is not u64."))
-                                .expect("Invalid rollout value. Could not convert to u32.")
-                        }
-                    }
-                    None => panic!("Rollout value is missing."),
-                };
+    /// Like [`Feature::get_value`], for string features. Fails with
+    /// [`ConfigurationAccessError::FeatureTypeMismatch`] if the feature is
+    /// not of string type.
+    pub fn get_value_as_string(&self, entity: &impl Entity) -> Result<String> {
+        self.check_data_type(ValueKind::String)?;
+        match self.get_value(entity)? {
+            Value::String(value) => Ok(value),
+            _ => Err(self.value_type_mismatch()),
+        }
+    }
+
+    /// Like [`Feature::get_value`], for string features of `JSON` or `YAML`
+    /// format: returns the value parsed as structured data. Fails with
+    /// [`ConfigurationAccessError::FeatureTypeMismatch`] if the feature is of
+    /// another type, with [`Error::ValueFormatMismatch`] if it is of another
+    /// format (like `TEXT`), or with [`Error::DeserializationError`] if the
+    /// value cannot be parsed.
+    pub fn get_value_as_json(&self, entity: &impl Entity) -> Result<serde_json::Value> {
+        self.check_data_type(ValueKind::String)?;
+        let (value, _, _) = self.evaluate_feature_for_entity(entity, &SegmentMemo::default())?;
+        value.parse_structured(&self.feature.feature_id, self.feature.format.as_deref())
+    }
+
+    /// Like [`Feature::get_value`], for numeric features. Fails with
+    /// [`ConfigurationAccessError::FeatureTypeMismatch`] if the feature is
+    /// not of numeric type, or with [`Error::NumericValueOutOfRange`] if its
+    /// value is not an `i64`.
+    pub fn get_value_as_i64(&self, entity: &impl Entity) -> Result<i64> {
+        self.check_data_type(ValueKind::Numeric)?;
+        match self.get_value(entity)? {
+            Value::Numeric(value) => value.as_i64().ok_or_else(|| Error::NumericValueOutOfRange {
+                target: "i64",
+                resource_id: self.feature.feature_id.clone(),
+            }),
+            _ => Err(self.value_type_mismatch()),
+        }
+    }
 
-                // Should rollout?
-                if Self::should_rollout(rollout_percentage, entity, &self.feature.feature_id) {
-                    if segment_rule.value.is_default() {
-                        Ok(self.feature.enabled_value.clone())
-                    } else {
-                        Ok(segment_rule.value)
-                    }
-                } else {
-                    Ok(self.feature.disabled_value.clone())
-                }
+    /// Like [`Feature::get_value`], but returns `default` if the feature
+    /// cannot be evaluated for `entity` (the error is logged).
+    pub fn get_value_or(&self, entity: &impl Entity, default: Value) -> Value {
+        self.get_value(entity).unwrap_or_else(|e| {
+            log::warn!(
+                "Cannot evaluate feature '{}' ({e}). Using fallback value.",
+                self.feature.feature_id
+            );
+            default
+        })
+    }
+
+    /// Like [`Feature::get_value_or`], with the disabled value of the feature
+    /// as fallback. Only fails if the disabled value is not of the type of
+    /// the feature.
+    pub fn get_value_with_default(&self, entity: &impl Entity) -> Result<Value> {
+        match self.get_value(entity) {
+            Ok(value) => Ok(value),
+            Err(e) => {
+                log::warn!(
+                    "Cannot evaluate feature '{}' ({e}). Using its disabled value.",
+                    self.feature.feature_id
+                );
+                Value::from_config_value(
+                    &self.feature.kind,
+                    self.feature.disabled_value.clone(),
+                    &self.feature.feature_id,
+                )
             }
-            None => self.use_rollout_percentage_to_get_value_from_feature_directly(entity),
         }
     }
 
-    fn should_rollout(rollout_percentage: u32, entity: &impl Entity, feature_id: &str) -> bool {
-        let tag = format!("{}:{}", entity.get_id(), feature_id);
-        rollout_percentage == 100 || random_value(&tag) < rollout_percentage
+    /// Like [`Feature::get_value`], also returning why the feature
+    /// evaluated to that value.
+    pub fn get_value_with_details(
+        &self,
+        entity: &impl Entity,
+    ) -> Result<(Value, EvaluationReason)> {
+        let (value, reason, _) = self.get_value_with_rollout_details(entity)?;
+        Ok((value, reason))
+    }
+
+    /// Like [`Feature::get_value_with_details`], also returning the rollout
+    /// percentage applied and the rollout bucket of `entity`. There is no
+    /// rollout if the feature is disabled.
+    pub fn get_value_with_rollout_details(
+        &self,
+        entity: &impl Entity,
+    ) -> Result<(Value, EvaluationReason, Option<RolloutDetails>)> {
+        let (model_value, reason, rollout) =
+            self.evaluate_feature_for_entity(entity, &SegmentMemo::default())?;
+
+        let value =
+            Value::from_config_value(&self.feature.kind, model_value, &self.feature.feature_id)?;
+        Ok((value, reason, rollout))
+    }
+
+    /// Like [`Feature::get_value`], reusing the segment evaluations of
+    /// `memo` for `entity`.
+    pub(crate) fn get_value_memoized(
+        &self,
+        entity: &impl Entity,
+        memo: &SegmentMemo,
+    ) -> Result<Value> {
+        let (model_value, _, _) = self.evaluate_feature_for_entity(entity, memo)?;
+        Value::from_config_value(&self.feature.kind, model_value, &self.feature.feature_id)
     }
 
-    fn use_rollout_percentage_to_get_value_from_feature_directly(
+    fn evaluate_feature_for_entity(
         &self,
         entity: &impl Entity,
-    ) -> Result<crate::models::ConfigValue> {
-        let rollout_percentage = self.feature.rollout_percentage;
-        if Self::should_rollout(rollout_percentage, entity, &self.feature.feature_id) {
-            Ok(self.feature.enabled_value.clone())
-        } else {
-            Ok(self.feature.disabled_value.clone())
+        memo: &SegmentMemo,
+    ) -> Result<Evaluation> {
+        let (value, reason, rollout) = self.resolve_value_for_entity(entity, memo)?;
+        self.metrics
+            .record_evaluation(&self.feature.feature_id, reason);
+        self.usage.record(&self.feature.feature_id, entity);
+        Ok((value, reason, rollout))
+    }
+
+    fn resolve_value_for_entity(
+        &self,
+        entity: &impl Entity,
+        memo: &SegmentMemo,
+    ) -> Result<Evaluation> {
+        evaluate(
+            &self.feature,
+            &self.segments,
+            entity,
+            &self.evaluation_options,
+            memo,
+        )
+    }
+}
+
+/// Evaluates `feature` for `entity`, with `segments` containing (at least)
+/// the segments its targeting rules refer to. This is the evaluation of
+/// both [`Feature`] and
+/// [`FeatureProxy`](crate::client::feature_proxy::FeatureProxy), without
+/// side effects: it neither records metrics nor usage. Segments already
+/// evaluated for `entity` are looked up in `memo`.
+pub(crate) fn evaluate(
+    feature: &crate::models::Feature,
+    segments: &HashMap<String, Arc<crate::models::Segment>>,
+    entity: &impl Entity,
+    evaluation_options: &EvaluationOptions,
+    memo: &SegmentMemo,
+) -> Result<Evaluation> {
+    if !feature.enabled {
+        return Ok((
+            feature.disabled_value.clone(),
+            EvaluationReason::FeatureDisabled,
+            None,
+        ));
+    }
+
+    // Entities without attributes are still evaluated against the segment
+    // rules: a rule can match on missing attributes (like `notExists`).
+    if feature.segment_rules.is_empty() {
+        // No match possible. Do not consider segment rules:
+        return use_rollout_percentage_to_get_value_from_feature_directly(
+            feature,
+            entity,
+            evaluation_options,
+        );
+    }
+
+    match find_applicable_segment_rule_for_entity_memoized(
+        ResourceKind::Feature,
+        &feature.feature_id,
+        segments,
+        &feature.segment_rules,
+        entity,
+        evaluation_options,
+        memo,
+    )? {
+        Some(segment_rule) => {
+            // Get rollout percentage
+            let rollout = rollout(
+                segment_rule.resolve_rollout_percentage(feature.rollout_percentage)?,
+                entity,
+                &feature.feature_id,
+                evaluation_options.rollout_seed,
+            );
+
+            // Should rollout?
+            if rollout.includes_entity() {
+                let reason = EvaluationReason::TargetingMatch {
+                    order: segment_rule.order,
+                };
+                let variant_bucket = variant_bucket_with_seed(
+                    &entity.get_id(),
+                    &feature.feature_id,
+                    evaluation_options.rollout_seed,
+                );
+                let value = segment_rule
+                    .weighted_value_or_default(&feature.feature_id, variant_bucket)?
+                    .unwrap_or(&feature.enabled_value);
+                Ok((value.clone(), reason, Some(rollout)))
+            } else {
+                Ok((
+                    feature.disabled_value.clone(),
+                    EvaluationReason::RolloutExcluded,
+                    Some(rollout),
+                ))
+            }
         }
+        None => use_rollout_percentage_to_get_value_from_feature_directly(
+            feature,
+            entity,
+            evaluation_options,
+        ),
+    }
+}
+
+/// A rollout of `rollout_percentage` percent (from 0 to 100) for
+/// `entity`: entities are assigned a bucket based on their id,
+/// `feature_id` and `seed`, and the ones in buckets below
+/// `rollout_percentage` are part of it.
+fn rollout(
+    rollout_percentage: u32,
+    entity: &impl Entity,
+    feature_id: &str,
+    seed: u32,
+) -> RolloutDetails {
+    RolloutDetails {
+        rollout_percentage,
+        bucket: rollout_bucket_with_seed(&entity.get_id(), feature_id, seed),
+    }
+}
+
+fn use_rollout_percentage_to_get_value_from_feature_directly(
+    feature: &crate::models::Feature,
+    entity: &impl Entity,
+    evaluation_options: &EvaluationOptions,
+) -> Result<Evaluation> {
+    let rollout = rollout(
+        check_rollout_percentage(feature.rollout_percentage)?,
+        entity,
+        &feature.feature_id,
+        evaluation_options.rollout_seed,
+    );
+    if rollout.includes_entity() {
+        Ok((
+            feature.enabled_value.clone(),
+            EvaluationReason::Default,
+            Some(rollout),
+        ))
+    } else {
+        Ok((
+            feature.disabled_value.clone(),
+            EvaluationReason::RolloutExcluded,
+            Some(rollout),
+        ))
     }
 }
 
@@ -125,13 +619,83 @@ impl Feature {
 pub mod tests {
 
     use super::*;
+    use crate::client::rollout_bucket;
     use crate::{
         entity,
-        models::{ConfigValue, Segment, SegmentRule, Segments, TargetingRule, ValueKind},
+        models::{
+            ConfigValue, Segment, SegmentRule, Segments, TargetingRule, ValueKind, WeightedValue,
+        },
         AttrValue,
     };
     use rstest::rstest;
 
+    #[rstest]
+    #[case("a1", "f1", 0, 68)]
+    #[case("a2", "f1", 0, 29)]
+    #[case("a1", "f4", 0, 35)]
+    #[case("user@example.com", "discount-banner", 0, 85)]
+    #[case("a1", "f1", 1, 28)]
+    #[case("a2", "f1", 42, 55)]
+    fn test_rollout_bucket(
+        #[case] entity_id: &str,
+        #[case] feature_id: &str,
+        #[case] seed: u32,
+        #[case] expected: u32,
+    ) {
+        assert_eq!(
+            crate::client::rollout_bucket_with_seed(entity_id, feature_id, seed),
+            expected
+        );
+        if seed == 0 {
+            assert_eq!(rollout_bucket(entity_id, feature_id), expected);
+            assert_eq!(
+                crate::client::rollout_percentage_for(entity_id, feature_id),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_rollout_bucket_hash() {
+        // The hash documented for rollout_bucket("a1", "f1")
+        let hash = murmur3::murmur3_32(&mut std::io::Cursor::new("a1:f1"), 0).unwrap();
+        assert_eq!(hash, 2957761103);
+        assert_eq!(rollout_bucket("a1", "f1"), 68);
+    }
+
+    #[test]
+    fn test_get_value_with_rollout_seed() {
+        let inner_feature = crate::models::Feature {
+            name: "F1".to_string(),
+            feature_id: "f1".to_string(),
+            kind: ValueKind::Numeric,
+            tags: None,
+            format: None,
+            enabled_value: ConfigValue(serde_json::Value::Number((-42).into())),
+            disabled_value: ConfigValue(serde_json::Value::Number((2).into())),
+            segment_rules: Vec::new(),
+            enabled: true,
+            rollout_percentage: 50,
+            collections: Vec::new(),
+        };
+        let entity = crate::tests::GenericEntity {
+            id: "a1".into(),
+            attributes: HashMap::new(),
+        };
+
+        // Bucket 68 with the default seed, 28 with seed 1
+        let feature = Feature::new(inner_feature.into(), HashMap::new());
+        let (_, reason) = feature.get_value_with_details(&entity).unwrap();
+        assert_eq!(reason, EvaluationReason::RolloutExcluded);
+
+        let feature = feature.with_evaluation_options(EvaluationOptions {
+            rollout_seed: 1,
+            ..Default::default()
+        });
+        let (_, reason) = feature.get_value_with_details(&entity).unwrap();
+        assert_eq!(reason, EvaluationReason::Default);
+    }
+
     #[rstest]
     #[case("a1", false)]
     #[case("a2", true)]
@@ -140,16 +704,16 @@ pub mod tests {
             id: entity_id.into(),
             attributes: HashMap::new(),
         };
-        let result = Feature::should_rollout(100, &entity, "f1");
+        let result = rollout(100, &entity, "f1", 0).includes_entity();
         assert!(result);
 
-        let result = Feature::should_rollout(0, &entity, "f1");
+        let result = rollout(0, &entity, "f1", 0).includes_entity();
         assert!(!result);
 
-        let result = Feature::should_rollout(50, &entity, "f1");
+        let result = rollout(50, &entity, "f1", 0).includes_entity();
         assert_eq!(result, partial_rollout_expectation);
 
-        let result = Feature::should_rollout(50, &entity, "f4");
+        let result = rollout(50, &entity, "f4", 0).includes_entity();
         // We chose feature ID here so that we rollout exactly inverted to "f1"
         assert_eq!(result, !partial_rollout_expectation);
     }
@@ -162,7 +726,7 @@ pub mod tests {
     // attrs but no segment rules
     #[case([].into(), [("key".into(), AttrValue::String("value".into()))].into())]
     // no attrs but segment rules
-    #[case([TargetingRule{rules: Vec::new(), value: ConfigValue(serde_json::json!("")), order: 0, rollout_percentage: None}].into(), [].into())]
+    #[case([TargetingRule{rules: Vec::new(), value: ConfigValue(serde_json::json!("")), order: 0, rollout_percentage: None, weighted_values: Vec::new()}].into(), [].into())]
     fn test_get_value_no_match_50_50_rollout(
         #[case] segment_rules: Vec<TargetingRule>,
         #[case] entity_attributes: HashMap<String, AttrValue>,
@@ -171,24 +735,23 @@ pub mod tests {
             name: "F1".to_string(),
             feature_id: "f1".to_string(),
             kind: ValueKind::Numeric,
+            tags: None,
             format: None,
             enabled_value: ConfigValue(serde_json::Value::Number((-42).into())),
             disabled_value: ConfigValue(serde_json::Value::Number((2).into())),
             segment_rules,
             enabled: true,
             rollout_percentage: 50,
+            collections: Vec::new(),
         };
-        let feature = Feature::new(inner_feature, HashMap::new());
+        let feature = Feature::new(inner_feature.into(), HashMap::new());
 
         // One entity and feature combination which leads to no rollout:
         let entity = crate::tests::GenericEntity {
             id: "a1".into(),
             attributes: entity_attributes.clone(),
         };
-        assert_eq!(
-            random_value(format!("{}:{}", entity.id, feature.feature.feature_id).as_str()),
-            68
-        );
+        assert_eq!(rollout_bucket(&entity.id, &feature.feature.feature_id), 68);
         let value = feature.get_value(&entity).unwrap();
         assert!(matches!(value, Value::Numeric(ref v) if v.as_i64().unwrap() == 2));
 
@@ -197,10 +760,7 @@ pub mod tests {
             id: "a2".into(),
             attributes: entity_attributes,
         };
-        assert_eq!(
-            random_value(format!("{}:{}", entity.id, feature.feature.feature_id).as_str()),
-            29
-        );
+        assert_eq!(rollout_bucket(&entity.id, &feature.feature.feature_id), 29);
         let value = feature.get_value(&entity).unwrap();
         assert!(matches!(value, Value::Numeric(ref v) if v.as_i64().unwrap() == -42));
     }
@@ -212,20 +772,253 @@ pub mod tests {
             name: "F1".to_string(),
             feature_id: "f1".to_string(),
             kind: ValueKind::Numeric,
+            tags: None,
             format: None,
             enabled_value: ConfigValue(serde_json::Value::Number((-42).into())),
             disabled_value: ConfigValue(serde_json::Value::Number((2).into())),
             segment_rules: Vec::new(),
             enabled: false,
             rollout_percentage: 100,
+            collections: Vec::new(),
         };
-        let feature = Feature::new(inner_feature, HashMap::new());
+        let feature = Feature::new(inner_feature.into(), HashMap::new());
 
         let entity = crate::tests::TrivialEntity {};
         let value = feature.get_value(&entity).unwrap();
         assert!(matches!(value, Value::Numeric(ref v) if v.as_f64().unwrap() == 2.0));
         assert!(matches!(value, Value::Numeric(ref v) if v.as_i64().unwrap() == 2));
         assert!(matches!(value, Value::Numeric(ref v) if v.as_u64().unwrap() == 2));
+
+        let (_, reason) = feature.get_value_with_details(&entity).unwrap();
+        assert_eq!(reason, EvaluationReason::FeatureDisabled);
+    }
+
+    #[test]
+    fn test_get_value_records_evaluation() {
+        #[derive(Default)]
+        struct Recorder(std::sync::Mutex<Vec<(String, EvaluationReason)>>);
+        impl crate::client::MetricsRecorder for Arc<Recorder> {
+            fn record_evaluation(&self, feature_id: &str, reason: EvaluationReason) {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push((feature_id.to_string(), reason));
+            }
+        }
+
+        let inner_feature = crate::models::Feature {
+            name: "F1".to_string(),
+            feature_id: "f1".to_string(),
+            kind: ValueKind::Numeric,
+            tags: None,
+            format: None,
+            enabled_value: ConfigValue(serde_json::Value::Number((-42).into())),
+            disabled_value: ConfigValue(serde_json::Value::Number((2).into())),
+            segment_rules: Vec::new(),
+            enabled: false,
+            rollout_percentage: 100,
+            collections: Vec::new(),
+        };
+        let recorder = Arc::new(Recorder::default());
+        let feature = Feature::new(inner_feature.into(), HashMap::new())
+            .with_metrics(Metrics::new(recorder.clone()));
+
+        feature.get_value(&crate::tests::TrivialEntity {}).unwrap();
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            vec![("f1".to_string(), EvaluationReason::FeatureDisabled)]
+        );
+    }
+
+    #[test]
+    fn test_configured_values() {
+        let inner_feature = crate::models::Feature {
+            name: "F1".to_string(),
+            feature_id: "f1".to_string(),
+            kind: ValueKind::Numeric,
+            tags: None,
+            format: None,
+            enabled_value: ConfigValue(serde_json::json!(-42)),
+            disabled_value: ConfigValue(serde_json::json!(2)),
+            segment_rules: vec![
+                TargetingRule {
+                    rules: vec![
+                        Segments {
+                            segments: vec!["s2".into()],
+                        },
+                        Segments {
+                            segments: vec!["s3".into()],
+                        },
+                    ],
+                    value: ConfigValue(serde_json::json!("$default")),
+                    order: 2,
+                    rollout_percentage: Some(ConfigValue(serde_json::json!("$default"))),
+                    weighted_values: Vec::new(),
+                },
+                TargetingRule {
+                    rules: vec![Segments {
+                        segments: vec!["s1".into()],
+                    }],
+                    value: ConfigValue(serde_json::json!(-48)),
+                    order: 1,
+                    rollout_percentage: Some(ConfigValue(serde_json::json!(20))),
+                    weighted_values: Vec::new(),
+                },
+            ],
+            enabled: true,
+            rollout_percentage: 50,
+            collections: Vec::new(),
+        };
+        let feature = Feature::new(inner_feature.into(), HashMap::new());
+
+        assert_eq!(feature.get_enabled_value().unwrap(), Value::from(-42));
+        assert_eq!(feature.get_disabled_value().unwrap(), Value::from(2));
+        assert_eq!(feature.get_rollout_percentage(), 50);
+        assert_eq!(
+            feature.get_segment_overrides().unwrap(),
+            vec![
+                SegmentOverride {
+                    order: 1,
+                    segment_ids: vec!["s1".into()],
+                    value: Value::from(-48),
+                    rollout_percentage: 20,
+                },
+                SegmentOverride {
+                    order: 2,
+                    segment_ids: vec!["s2".into(), "s3".into()],
+                    value: Value::from(-42),
+                    rollout_percentage: 50,
+                },
+            ]
+        );
+    }
+
+    // `$default` values are previewed as inheriting the enabled value,
+    // `$default` rollout percentages are resolved.
+    #[test]
+    fn test_preview() {
+        let targeting_rule = |order, value, rollout_percentage| TargetingRule {
+            rules: vec![Segments {
+                segments: vec![format!("s{order}")],
+            }],
+            value: ConfigValue(value),
+            order,
+            rollout_percentage: Some(ConfigValue(rollout_percentage)),
+            weighted_values: Vec::new(),
+        };
+        let inner_feature = crate::models::Feature {
+            name: "F1".to_string(),
+            feature_id: "f1".to_string(),
+            kind: ValueKind::Numeric,
+            tags: None,
+            format: None,
+            enabled_value: ConfigValue(serde_json::json!(-42)),
+            disabled_value: ConfigValue(serde_json::json!(2)),
+            segment_rules: vec![
+                targeting_rule(3, serde_json::json!(-49), serde_json::json!("$default")),
+                targeting_rule(1, serde_json::json!(-48), serde_json::json!(20)),
+                targeting_rule(2, serde_json::json!("$default"), serde_json::json!(30)),
+            ],
+            enabled: true,
+            rollout_percentage: 50,
+            collections: Vec::new(),
+        };
+        let feature = Feature::new(inner_feature.into(), HashMap::new());
+
+        assert_eq!(
+            feature.preview().unwrap(),
+            FeaturePreview {
+                enabled_value: Value::from(-42),
+                disabled_value: Value::from(2),
+                rollout_percentage: 50,
+                segment_overrides: vec![
+                    SegmentOverride {
+                        order: 1,
+                        segment_ids: vec!["s1".into()],
+                        value: Some(Value::from(-48)),
+                        rollout_percentage: 20,
+                    },
+                    SegmentOverride {
+                        order: 2,
+                        segment_ids: vec!["s2".into()],
+                        value: None,
+                        rollout_percentage: 30,
+                    },
+                    SegmentOverride {
+                        order: 3,
+                        segment_ids: vec!["s3".into()],
+                        value: Some(Value::from(-49)),
+                        rollout_percentage: 50,
+                    },
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn test_get_targeting_rules() {
+        let inner_feature = crate::models::Feature {
+            name: "F1".to_string(),
+            feature_id: "f1".to_string(),
+            kind: ValueKind::Numeric,
+            tags: None,
+            format: None,
+            enabled_value: ConfigValue(serde_json::json!(-42)),
+            disabled_value: ConfigValue(serde_json::json!(2)),
+            segment_rules: vec![TargetingRule {
+                rules: vec![Segments {
+                    segments: vec!["s1".into()],
+                }],
+                value: ConfigValue(serde_json::json!("$default")),
+                order: 1,
+                rollout_percentage: Some(ConfigValue(serde_json::json!(20))),
+                weighted_values: Vec::new(),
+            }],
+            enabled: true,
+            rollout_percentage: 50,
+            collections: Vec::new(),
+        };
+        let segment = Segment {
+            name: "Heinz".into(),
+            segment_id: "s1".into(),
+            description: "".into(),
+            tags: None,
+            rules: vec![SegmentRule {
+                attribute_name: "name".into(),
+                operator: "is".into(),
+                values: vec!["heinz".into()],
+            }],
+        };
+        let feature = Feature::new(
+            inner_feature.into(),
+            HashMap::from([("s1".into(), Arc::new(segment))]),
+        );
+
+        let targeting_rules = feature.get_targeting_rules().unwrap();
+        assert_eq!(targeting_rules.len(), 1);
+        let targeting_rule = &targeting_rules[0];
+        assert_eq!(targeting_rule.order, 1);
+        assert_eq!(targeting_rule.value, Value::from(-42));
+        assert_eq!(targeting_rule.rollout_percentage, 20);
+        assert_eq!(targeting_rule.segments.len(), 1);
+        assert_eq!(targeting_rule.segments[0].get_name(), "Heinz");
+        assert_eq!(
+            targeting_rule.segments[0].get_rules(),
+            vec![crate::client::segment::SegmentRule {
+                attribute_name: "name".into(),
+                operator: "is".into(),
+                values: vec!["heinz".into()],
+            }]
+        );
+
+        // The segments are part of the feature
+        let feature = Feature::new(feature.feature.clone(), HashMap::new());
+        assert!(matches!(
+            feature.get_targeting_rules(),
+            Err(Error::ConfigurationAccessError(
+                ConfigurationAccessError::SegmentNotFound { ref segment_id }
+            )) if segment_id == "s1"
+        ));
     }
 
     // Get a feature value using different entities, matching or not matching a segment rule.
@@ -236,6 +1029,7 @@ pub mod tests {
             name: "F1".to_string(),
             feature_id: "f1".to_string(),
             kind: ValueKind::Numeric,
+            tags: None,
             format: None,
             enabled_value: ConfigValue(serde_json::Value::Number((-42).into())),
             disabled_value: ConfigValue(serde_json::Value::Number((2).into())),
@@ -246,15 +1040,17 @@ pub mod tests {
                 value: ConfigValue(serde_json::Value::Number((-48).into())),
                 order: 0,
                 rollout_percentage: Some(ConfigValue(serde_json::Value::Number((50).into()))),
+                weighted_values: Vec::new(),
             }],
             enabled: true,
             rollout_percentage: 50,
+            collections: Vec::new(),
         };
         let feature = Feature::new(
-            inner_feature,
+            inner_feature.into(),
             HashMap::from([(
                 "some_segment_id".into(),
-                Segment {
+                Arc::new(Segment {
                     name: "".into(),
                     segment_id: "".into(),
                     description: "".into(),
@@ -264,7 +1060,7 @@ pub mod tests {
                         operator: "is".into(),
                         values: vec!["heinz".into()],
                     }],
-                },
+                }),
             )]),
         );
 
@@ -274,8 +1070,9 @@ pub mod tests {
             attributes: HashMap::from([("name".into(), AttrValue::from("heinz".to_string()))]),
         };
 
-        let value = feature.get_value(&entity).unwrap();
+        let (value, reason) = feature.get_value_with_details(&entity).unwrap();
         assert!(matches!(value, Value::Numeric(ref v) if v.as_i64().unwrap() == -48));
+        assert_eq!(reason, EvaluationReason::TargetingMatch { order: 0 });
 
         // matching the segment + rollout disallowed
         let entity = crate::tests::GenericEntity {
@@ -283,8 +1080,9 @@ pub mod tests {
             attributes: HashMap::from([("name".into(), AttrValue::from("heinz".to_string()))]),
         };
 
-        let value = feature.get_value(&entity).unwrap();
+        let (value, reason) = feature.get_value_with_details(&entity).unwrap();
         assert!(matches!(value, Value::Numeric(ref v) if v.as_i64().unwrap() == 2));
+        assert_eq!(reason, EvaluationReason::RolloutExcluded);
 
         // not matching the segment + rollout allowed
         let entity = crate::tests::GenericEntity {
@@ -292,8 +1090,85 @@ pub mod tests {
             attributes: HashMap::from([("name".into(), AttrValue::from("heinzz".to_string()))]),
         };
 
-        let value = feature.get_value(&entity).unwrap();
+        let (value, reason) = feature.get_value_with_details(&entity).unwrap();
         assert!(matches!(value, Value::Numeric(ref v) if v.as_i64().unwrap() == -42));
+        assert_eq!(reason, EvaluationReason::Default);
+
+        // not matching the segment + rollout disallowed
+        let entity = crate::tests::GenericEntity {
+            id: "a1".into(),
+            attributes: HashMap::from([("name".into(), AttrValue::from("heinzz".to_string()))]),
+        };
+
+        let (value, reason) = feature.get_value_with_details(&entity).unwrap();
+        assert!(matches!(value, Value::Numeric(ref v) if v.as_i64().unwrap() == 2));
+        assert_eq!(reason, EvaluationReason::RolloutExcluded);
+
+        // the applied rollout and the bucket of the entity are reported
+        let (_, _, rollout) = feature.get_value_with_rollout_details(&entity).unwrap();
+        assert_eq!(
+            rollout,
+            Some(RolloutDetails {
+                rollout_percentage: 50,
+                bucket: rollout_bucket("a1", "f1"),
+            })
+        );
+    }
+
+    #[test]
+    fn test_get_value_matching_a_rule_without_attributes() {
+        let inner_feature = crate::models::Feature {
+            name: "F1".to_string(),
+            feature_id: "f1".to_string(),
+            kind: ValueKind::Numeric,
+            tags: None,
+            format: None,
+            enabled_value: ConfigValue(serde_json::Value::Number((-42).into())),
+            disabled_value: ConfigValue(serde_json::Value::Number((2).into())),
+            segment_rules: vec![TargetingRule {
+                rules: vec![Segments {
+                    segments: vec!["anonymous".into()],
+                }],
+                value: ConfigValue(serde_json::Value::Number((-48).into())),
+                order: 0,
+                rollout_percentage: Some(ConfigValue(serde_json::Value::Number((100).into()))),
+                weighted_values: Vec::new(),
+            }],
+            enabled: true,
+            rollout_percentage: 100,
+            collections: Vec::new(),
+        };
+        let feature = Feature::new(
+            inner_feature.into(),
+            HashMap::from([(
+                "anonymous".into(),
+                Arc::new(Segment {
+                    name: "".into(),
+                    segment_id: "".into(),
+                    description: "".into(),
+                    tags: None,
+                    rules: vec![SegmentRule {
+                        attribute_name: "email".into(),
+                        operator: "notExists".into(),
+                        values: vec![],
+                    }],
+                }),
+            )]),
+        );
+
+        let (value, reason) = feature
+            .get_value_with_details(&crate::tests::TrivialEntity)
+            .unwrap();
+        assert!(matches!(value, Value::Numeric(ref v) if v.as_i64().unwrap() == -48));
+        assert_eq!(reason, EvaluationReason::TargetingMatch { order: 0 });
+
+        let entity = crate::tests::GenericEntity {
+            id: "a1".into(),
+            attributes: HashMap::from([("email".into(), AttrValue::from("a@b.c".to_string()))]),
+        };
+        let (value, reason) = feature.get_value_with_details(&entity).unwrap();
+        assert!(matches!(value, Value::Numeric(ref v) if v.as_i64().unwrap() == -42));
+        assert_eq!(reason, EvaluationReason::Default);
     }
 
     // The matched segment rule's value has a "$default" value.
@@ -304,6 +1179,7 @@ pub mod tests {
             name: "F1".to_string(),
             feature_id: "f1".to_string(),
             kind: ValueKind::Numeric,
+            tags: None,
             format: None,
             enabled_value: ConfigValue(serde_json::Value::Number((-42).into())),
             disabled_value: ConfigValue(serde_json::Value::Number((2).into())),
@@ -314,15 +1190,17 @@ pub mod tests {
                 value: ConfigValue(serde_json::Value::String("$default".into())),
                 order: 0,
                 rollout_percentage: Some(ConfigValue(serde_json::Value::Number((50).into()))),
+                weighted_values: Vec::new(),
             }],
             enabled: true,
             rollout_percentage: 50,
+            collections: Vec::new(),
         };
         let feature = Feature::new(
-            inner_feature,
+            inner_feature.into(),
             HashMap::from([(
                 "some_segment_id".into(),
-                Segment {
+                Arc::new(Segment {
                     name: "".into(),
                     segment_id: "".into(),
                     description: "".into(),
@@ -332,7 +1210,7 @@ pub mod tests {
                         operator: "is".into(),
                         values: vec!["heinz".into()],
                     }],
-                },
+                }),
             )]),
         );
 
@@ -346,6 +1224,62 @@ pub mod tests {
         assert!(matches!(value, Value::Numeric(ref v) if v.as_i64().unwrap() == -42));
     }
 
+    // A string feature whose own value is "$default" is not mistaken for the
+    // marker, also when a rule refers to it with "$default".
+    #[test]
+    fn test_get_value_string_feature_with_literal_default() {
+        let inner_feature = crate::models::Feature {
+            name: "F1".to_string(),
+            feature_id: "f1".to_string(),
+            kind: ValueKind::String,
+            tags: None,
+            format: Some("TEXT".to_string()),
+            enabled_value: ConfigValue(serde_json::json!("$default")),
+            disabled_value: ConfigValue(serde_json::json!("off")),
+            segment_rules: vec![TargetingRule {
+                rules: vec![Segments {
+                    segments: vec!["some_segment_id".into()],
+                }],
+                value: ConfigValue(serde_json::json!("$default")),
+                order: 0,
+                rollout_percentage: Some(ConfigValue(serde_json::json!(100))),
+                weighted_values: Vec::new(),
+            }],
+            enabled: true,
+            rollout_percentage: 100,
+            collections: Vec::new(),
+        };
+        let feature = Feature::new(
+            inner_feature.into(),
+            HashMap::from([(
+                "some_segment_id".into(),
+                Arc::new(Segment {
+                    name: "".into(),
+                    segment_id: "".into(),
+                    description: "".into(),
+                    tags: None,
+                    rules: vec![SegmentRule {
+                        attribute_name: "name".into(),
+                        operator: "is".into(),
+                        values: vec!["heinz".into()],
+                    }],
+                }),
+            )]),
+        );
+
+        for name in ["heinz", "karl"] {
+            let entity = crate::tests::GenericEntity {
+                id: "a2".into(),
+                attributes: HashMap::from([("name".into(), AttrValue::from(name))]),
+            };
+            assert_eq!(feature.get_value(&entity).unwrap(), Value::from("$default"));
+        }
+        assert_eq!(
+            feature.get_segment_overrides().unwrap()[0].value,
+            Value::from("$default")
+        );
+    }
+
     // The matched segment rule's rollout percentage has a "$default" value.
     // In this case, the feature's rollout percentage should be used whenever the rule matches.
     #[test]
@@ -354,6 +1288,7 @@ pub mod tests {
             name: "F1".to_string(),
             feature_id: "f1".to_string(),
             kind: ValueKind::Numeric,
+            tags: None,
             format: None,
             enabled_value: ConfigValue(serde_json::Value::Number((-42).into())),
             disabled_value: ConfigValue(serde_json::Value::Number((2).into())),
@@ -364,15 +1299,17 @@ pub mod tests {
                 value: ConfigValue(serde_json::Value::Number((48).into())),
                 order: 0,
                 rollout_percentage: Some(ConfigValue(serde_json::Value::String("$default".into()))),
+                weighted_values: Vec::new(),
             }],
             enabled: true,
             rollout_percentage: 0,
+            collections: Vec::new(),
         };
         let feature = Feature::new(
-            inner_feature,
+            inner_feature.into(),
             HashMap::from([(
                 "some_segment_id".into(),
-                Segment {
+                Arc::new(Segment {
                     name: "".into(),
                     segment_id: "".into(),
                     description: "".into(),
@@ -382,7 +1319,7 @@ pub mod tests {
                         operator: "is".into(),
                         values: vec!["heinz".into()],
                     }],
-                },
+                }),
             )]),
         );
 
@@ -402,6 +1339,7 @@ pub mod tests {
             name: "F1".to_string(),
             feature_id: "f1".to_string(),
             kind: ValueKind::Numeric,
+            tags: None,
             format: None,
             enabled_value: ConfigValue(serde_json::Value::Number((-42).into())),
             disabled_value: ConfigValue(serde_json::Value::Number((2).into())),
@@ -413,6 +1351,7 @@ pub mod tests {
                     value: ConfigValue(serde_json::Value::Number((-48).into())),
                     order: 1,
                     rollout_percentage: Some(ConfigValue(serde_json::Value::Number((100).into()))),
+                    weighted_values: Vec::new(),
                 },
                 TargetingRule {
                     rules: vec![Segments {
@@ -421,17 +1360,19 @@ pub mod tests {
                     value: ConfigValue(serde_json::Value::Number((-49).into())),
                     order: 0,
                     rollout_percentage: Some(ConfigValue(serde_json::Value::Number((100).into()))),
+                    weighted_values: Vec::new(),
                 },
             ],
             enabled: true,
             rollout_percentage: 100,
+            collections: Vec::new(),
         };
         let feature = Feature::new(
-            inner_feature,
+            inner_feature.into(),
             HashMap::from([
                 (
                     "some_segment_id_1".into(),
-                    Segment {
+                    Arc::new(Segment {
                         name: "".into(),
                         segment_id: "".into(),
                         description: "".into(),
@@ -441,11 +1382,11 @@ pub mod tests {
                             operator: "is".into(),
                             values: vec!["heinz".into()],
                         }],
-                    },
+                    }),
                 ),
                 (
                     "some_segment_id_2".into(),
-                    Segment {
+                    Arc::new(Segment {
                         name: "".into(),
                         segment_id: "".into(),
                         description: "".into(),
@@ -455,7 +1396,7 @@ pub mod tests {
                             operator: "is".into(),
                             values: vec!["heinz".into()],
                         }],
-                    },
+                    }),
                 ),
             ]),
         );
@@ -468,4 +1409,283 @@ pub mod tests {
         let value = feature.get_value(&entity).unwrap();
         assert!(matches!(value, Value::Numeric(ref v) if v.as_i64().unwrap() == -49));
     }
+
+    // Entities matching a rule with weighted values get one of them, picked
+    // by their variant bucket, in the given shares.
+    #[test]
+    fn test_get_value_weighted_values() {
+        let inner_feature = crate::models::Feature {
+            name: "F1".to_string(),
+            feature_id: "f1".to_string(),
+            kind: ValueKind::Numeric,
+            tags: None,
+            format: None,
+            enabled_value: ConfigValue(serde_json::json!(-42)),
+            disabled_value: ConfigValue(serde_json::json!(2)),
+            segment_rules: vec![TargetingRule {
+                rules: vec![Segments {
+                    segments: vec!["some_segment_id".into()],
+                }],
+                value: ConfigValue(serde_json::json!(-48)),
+                order: 0,
+                rollout_percentage: Some(ConfigValue(serde_json::json!(100))),
+                weighted_values: vec![
+                    WeightedValue {
+                        value: ConfigValue(serde_json::json!(1)),
+                        weight: 30,
+                    },
+                    WeightedValue {
+                        value: ConfigValue(serde_json::json!(2)),
+                        weight: 70,
+                    },
+                ],
+            }],
+            enabled: true,
+            rollout_percentage: 100,
+            collections: Vec::new(),
+        };
+        let feature = Feature::new(
+            inner_feature.into(),
+            HashMap::from([(
+                "some_segment_id".into(),
+                Arc::new(Segment {
+                    name: "".into(),
+                    segment_id: "".into(),
+                    description: "".into(),
+                    tags: None,
+                    rules: vec![SegmentRule {
+                        attribute_name: "name".into(),
+                        operator: "is".into(),
+                        values: vec!["heinz".into()],
+                    }],
+                }),
+            )]),
+        );
+
+        let mut first_variant = 0;
+        for i in 0..1000 {
+            let entity = crate::tests::GenericEntity {
+                id: format!("e{i}"),
+                attributes: HashMap::from([("name".into(), AttrValue::from("heinz".to_string()))]),
+            };
+            let (value, reason) = feature.get_value_with_details(&entity).unwrap();
+            assert_eq!(reason, EvaluationReason::TargetingMatch { order: 0 });
+            let expected = if variant_bucket_with_seed(&entity.id, "f1", 0) < 30 {
+                first_variant += 1;
+                1i64
+            } else {
+                2
+            };
+            assert_eq!(value, Value::from(expected));
+        }
+        assert!((250..350).contains(&first_variant), "{first_variant}");
+    }
+
+    // A malformed rollout percentage in a matching segment rule is reported
+    // as an error instead of panicking.
+    #[rstest]
+    #[case(serde_json::json!("fifty"))]
+    #[case(serde_json::json!(50.5))]
+    #[case(serde_json::json!(-1))]
+    #[case(serde_json::json!(101))]
+    #[case(serde_json::json!(u64::from(u32::MAX) + 1))]
+    fn test_get_value_malformed_rollout_percentage(#[case] rollout_percentage: serde_json::Value) {
+        let inner_feature = crate::models::Feature {
+            name: "F1".to_string(),
+            feature_id: "f1".to_string(),
+            kind: ValueKind::Numeric,
+            tags: None,
+            format: None,
+            enabled_value: ConfigValue(serde_json::Value::Number((-42).into())),
+            disabled_value: ConfigValue(serde_json::Value::Number((2).into())),
+            segment_rules: vec![TargetingRule {
+                rules: vec![Segments {
+                    segments: vec!["some_segment_id".into()],
+                }],
+                value: ConfigValue(serde_json::Value::Number((-48).into())),
+                order: 0,
+                rollout_percentage: Some(ConfigValue(rollout_percentage)),
+                weighted_values: Vec::new(),
+            }],
+            enabled: true,
+            rollout_percentage: 50,
+            collections: Vec::new(),
+        };
+        let feature = Feature::new(
+            inner_feature.into(),
+            HashMap::from([(
+                "some_segment_id".into(),
+                Arc::new(Segment {
+                    name: "".into(),
+                    segment_id: "".into(),
+                    description: "".into(),
+                    tags: None,
+                    rules: vec![SegmentRule {
+                        attribute_name: "name".into(),
+                        operator: "is".into(),
+                        values: vec!["heinz".into()],
+                    }],
+                }),
+            )]),
+        );
+        let entity = crate::tests::GenericEntity {
+            id: "a2".into(),
+            attributes: HashMap::from([("name".into(), AttrValue::from("heinz".to_string()))]),
+        };
+
+        let result = feature.get_value(&entity);
+        assert!(matches!(result, Err(Error::ProtocolError)));
+    }
+
+    // Rollout percentages of features above 100 are reported as an error,
+    // whether used directly or through a `$default` segment rule rollout.
+    #[rstest]
+    #[case("heinz")]
+    #[case("not heinz")]
+    fn test_get_value_feature_rollout_percentage_out_of_range(#[case] name: &str) {
+        let inner_feature = crate::models::Feature {
+            name: "F1".to_string(),
+            feature_id: "f1".to_string(),
+            kind: ValueKind::Numeric,
+            tags: None,
+            format: None,
+            enabled_value: ConfigValue(serde_json::Value::Number((-42).into())),
+            disabled_value: ConfigValue(serde_json::Value::Number((2).into())),
+            segment_rules: vec![TargetingRule {
+                rules: vec![Segments {
+                    segments: vec!["some_segment_id".into()],
+                }],
+                value: ConfigValue(serde_json::Value::Number((-48).into())),
+                order: 0,
+                rollout_percentage: Some(ConfigValue(serde_json::json!("$default"))),
+                weighted_values: Vec::new(),
+            }],
+            enabled: true,
+            rollout_percentage: 150,
+            collections: Vec::new(),
+        };
+        let feature = Feature::new(
+            inner_feature.into(),
+            HashMap::from([(
+                "some_segment_id".into(),
+                Arc::new(Segment {
+                    name: "".into(),
+                    segment_id: "".into(),
+                    description: "".into(),
+                    tags: None,
+                    rules: vec![SegmentRule {
+                        attribute_name: "name".into(),
+                        operator: "is".into(),
+                        values: vec!["heinz".into()],
+                    }],
+                }),
+            )]),
+        );
+        let entity = crate::tests::GenericEntity {
+            id: "a2".into(),
+            attributes: HashMap::from([("name".into(), AttrValue::from(name.to_string()))]),
+        };
+
+        let result = feature.get_value(&entity);
+        assert!(matches!(result, Err(Error::ProtocolError)));
+    }
+
+    // Evaluation errors are replaced by fallback values.
+    #[test]
+    fn test_get_value_fallback() {
+        let inner_feature = crate::models::Feature {
+            name: "F1".to_string(),
+            feature_id: "f1".to_string(),
+            kind: ValueKind::Numeric,
+            tags: None,
+            format: None,
+            enabled_value: ConfigValue(serde_json::Value::Number((-42).into())),
+            disabled_value: ConfigValue(serde_json::Value::Number((2).into())),
+            segment_rules: Vec::new(),
+            enabled: true,
+            rollout_percentage: 150,
+            collections: Vec::new(),
+        };
+        let feature = Feature::new(inner_feature.into(), HashMap::new());
+        let entity = crate::tests::TrivialEntity {};
+        assert!(feature.get_value(&entity).is_err());
+
+        let value = feature.get_value_or(&entity, Value::Numeric(7i64.into()));
+        assert!(matches!(value, Value::Numeric(ref v) if v.as_i64().unwrap() == 7));
+
+        let value = feature.get_value_with_default(&entity).unwrap();
+        assert!(matches!(value, Value::Numeric(ref v) if v.as_i64().unwrap() == 2));
+    }
+
+    // String features of JSON or YAML format can be retrieved as
+    // structured data. JSON can be given as a string or directly.
+    #[rstest]
+    #[case("JSON", serde_json::json!(r#"{"key": [1, "two"]}"#), Ok(serde_json::json!({"key": [1, "two"]})))]
+    #[case("JSON", serde_json::json!({"key": [1, "two"]}), Ok(serde_json::json!({"key": [1, "two"]})))]
+    #[case("JSON", serde_json::json!("{not json"), Err("Cannot deserialize string '{not json'"))]
+    #[case("TEXT", serde_json::json!(r#"{"key": [1, "two"]}"#), Err("Format of 'f1' is TEXT, not JSON or YAML"))]
+    #[case("YAML", serde_json::json!("key:\n  - 1\n  - two\n"), Ok(serde_json::json!({"key": [1, "two"]})))]
+    #[case("YAML", serde_json::json!("key: [1, two"), Err("Cannot deserialize string 'key: [1, two'"))]
+    fn test_get_value_as_json(
+        #[case] format: &str,
+        #[case] value: serde_json::Value,
+        #[case] expected: std::result::Result<serde_json::Value, &str>,
+    ) {
+        let inner_feature = crate::models::Feature {
+            name: "F1".to_string(),
+            feature_id: "f1".to_string(),
+            kind: ValueKind::String,
+            tags: None,
+            format: Some(format.to_string()),
+            enabled_value: ConfigValue(value.clone()),
+            disabled_value: ConfigValue(value),
+            segment_rules: Vec::new(),
+            enabled: true,
+            rollout_percentage: 100,
+            collections: Vec::new(),
+        };
+        let feature = Feature::new(inner_feature.into(), HashMap::new());
+        let entity = crate::tests::TrivialEntity {};
+
+        let result = feature.get_value_as_json(&entity);
+        match expected {
+            Ok(expected) => assert_eq!(result.unwrap(), expected),
+            Err(message) => assert!(
+                result
+                    .as_ref()
+                    .unwrap_err()
+                    .to_string()
+                    .starts_with(message),
+                "{result:?}"
+            ),
+        }
+    }
+
+    // Structured data stored as YAML is retrieved unchanged.
+    #[test]
+    fn test_get_value_as_json_yaml_round_trip() {
+        let data = serde_json::json!({
+            "name": "car-rentals",
+            "limits": {"cars": 3, "ratio": 0.5, "enabled": true},
+            "regions": ["eu", "us"],
+        });
+        let inner_feature = crate::models::Feature {
+            name: "F1".to_string(),
+            feature_id: "f1".to_string(),
+            kind: ValueKind::String,
+            tags: None,
+            format: Some("YAML".to_string()),
+            enabled_value: ConfigValue(serde_yaml::to_string(&data).unwrap().into()),
+            disabled_value: ConfigValue("".into()),
+            segment_rules: Vec::new(),
+            enabled: true,
+            rollout_percentage: 100,
+            collections: Vec::new(),
+        };
+        let feature = Feature::new(inner_feature.into(), HashMap::new());
+        let entity = crate::tests::TrivialEntity {};
+
+        assert_eq!(feature.get_data_type(), ValueKind::String);
+        assert_eq!(feature.get_value_as_json(&entity).unwrap(), data);
+    }
 }
@@ -13,210 +13,206 @@
 // limitations under the License.
 
 use std::sync::Arc;
-use std::{io::Cursor, sync::Mutex};
+use std::{io::Cursor, sync::RwLock};
 
 use murmur3::murmur3_32;
 
 use crate::{
-    client::cache::ConfigurationSnapshot, models,
-    segment_evaluation::find_applicable_segment_rule_for_entity,
+    client::app_configuration_client::AppConfigurationClient,
+    client::cache::{self, ConfigurationSnapshot},
+    client::feature::{evaluate, Feature},
+    client::metrics::Metrics,
+    client::usage::UsageRecorder,
+    models,
+    segment_evaluation::{EvaluationOptions, SegmentMemo},
 };
 
 use crate::entity::Entity;
 
-use crate::errors::ConfigurationAccessError;
-
-const MISSING_FEATURE_ERROR_MSG: &str = "The feature should exist in the configuration_snapshot. It should have been validated in `AppConfigurationClient::get_feature()`.";
+use crate::errors::Result;
 
 /// A feature in a collection and environment. Use the `get_feature()`
 /// method of the `AppConfigurationClient` to create instances of features.
 #[derive(Debug)]
 pub struct FeatureProxy {
-    configuration_snapshot: Arc<Mutex<ConfigurationSnapshot>>,
+    configuration_snapshot: Arc<RwLock<ConfigurationSnapshot>>,
     feature_id: String,
+    evaluation_options: EvaluationOptions,
+    metrics: Metrics,
+    usage: UsageRecorder,
 }
 
 impl FeatureProxy {
     pub(crate) fn new(
-        configuration_snapshot: Arc<Mutex<ConfigurationSnapshot>>,
+        configuration_snapshot: Arc<RwLock<ConfigurationSnapshot>>,
         feature_id: String,
+        evaluation_options: EvaluationOptions,
     ) -> Self {
         FeatureProxy {
             configuration_snapshot,
             feature_id,
+            evaluation_options,
+            metrics: Metrics::default(),
+            usage: UsageRecorder::default(),
         }
     }
 
+    pub(crate) fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    pub(crate) fn with_usage(mut self, usage: UsageRecorder) -> Self {
+        self.usage = usage;
+        self
+    }
+
+    /// Calls `f` with the feature in the current configuration, failing with
+    /// `ConfigurationAccessError::FeatureNotFound` if it is not there
+    /// (anymore).
+    fn read<R>(&self, f: impl FnOnce(&models::Feature) -> R) -> Result<R> {
+        Ok(f(
+            cache::read_snapshot(&self.configuration_snapshot).get_feature(&self.feature_id)?
+        ))
+    }
+
     /// Returns the name of the feature.
-    pub fn get_name(&self) -> String {
-        self.configuration_snapshot
-            .lock()
-            .unwrap_or_else(|_| panic!("{}", ConfigurationAccessError::LockAcquisitionError))
-            .get_feature(&self.feature_id)
-            .expect(MISSING_FEATURE_ERROR_MSG)
-            .name
-            .clone()
+    pub fn get_name(&self) -> Result<String> {
+        self.read(|feature| feature.name.clone())
     }
 
     /// Returns the disable value as a `models::ConfigValue`.
-    pub fn get_disabled_value(&self) -> models::ConfigValue {
-        self.configuration_snapshot
-            .lock()
-            .unwrap_or_else(|_| panic!("{}", ConfigurationAccessError::LockAcquisitionError))
-            .get_feature(&self.feature_id)
-            .expect(MISSING_FEATURE_ERROR_MSG)
-            .disabled_value
-            .clone()
+    pub fn get_disabled_value(&self) -> Result<models::ConfigValue> {
+        self.read(|feature| feature.disabled_value.clone())
     }
 
     /// Returns the enabled value as a `models::ConfigValue`.
-    pub fn get_enabled_value(&self) -> models::ConfigValue {
-        self.configuration_snapshot
-            .lock()
-            .unwrap_or_else(|_| panic!("{}", ConfigurationAccessError::LockAcquisitionError))
-            .get_feature(&self.feature_id)
-            .expect(MISSING_FEATURE_ERROR_MSG)
-            .enabled_value
-            .clone()
+    pub fn get_enabled_value(&self) -> Result<models::ConfigValue> {
+        self.read(|feature| feature.enabled_value.clone())
     }
 
     /// Returns the id of the feature.
-    pub fn get_id(&self) -> String {
-        self.configuration_snapshot
-            .lock()
-            .unwrap_or_else(|_| panic!("{}", ConfigurationAccessError::LockAcquisitionError))
-            .get_feature(&self.feature_id)
-            .expect(MISSING_FEATURE_ERROR_MSG)
-            .feature_id
-            .clone()
+    pub fn get_id(&self) -> Result<String> {
+        self.read(|feature| feature.feature_id.clone())
     }
 
     /// Returns the data type as a member of the `models::ValueKind` enumeration.
-    pub fn get_data_type(&self) -> models::ValueKind {
-        self.configuration_snapshot
-            .lock()
-            .unwrap_or_else(|_| panic!("{}", ConfigurationAccessError::LockAcquisitionError))
-            .get_feature(&self.feature_id)
-            .expect(MISSING_FEATURE_ERROR_MSG)
-            .kind
+    pub fn get_data_type(&self) -> Result<models::ValueKind> {
+        self.read(|feature| feature.kind)
     }
 
     /// Gets the `Some(data_format)` if the feature data type is
     /// `models::ValueKind::STRING`, or `None` otherwise.
-    pub fn get_data_format(&self) -> Option<String> {
-        self.configuration_snapshot
-            .lock()
-            .unwrap_or_else(|_| panic!("{}", ConfigurationAccessError::LockAcquisitionError))
-            .get_feature(&self.feature_id)
-            .expect(MISSING_FEATURE_ERROR_MSG)
-            .format
-            .clone()
+    pub fn get_data_format(&self) -> Result<Option<String>> {
+        self.read(|feature| feature.format.clone())
     }
 
     /// Returns the rollout peArcentage as a positive integer.
-    pub fn get_rollout_percentage(&self) -> u32 {
-        self.configuration_snapshot
-            .lock()
-            .unwrap_or_else(|_| panic!("{}", ConfigurationAccessError::LockAcquisitionError))
-            .get_feature(&self.feature_id)
-            .expect(MISSING_FEATURE_ERROR_MSG)
-            .rollout_percentage
+    pub fn get_rollout_percentage(&self) -> Result<u32> {
+        self.read(|feature| feature.rollout_percentage)
     }
 
     /// Returns the targeting rules for the feature. I.e.: what value to
     /// associate with an entity, under what ciArcumnstances, and how frequent
     /// it applies.
-    pub fn get_targeting_rules(&self) -> Vec<models::TargetingRule> {
-        self.configuration_snapshot
-            .lock()
-            .unwrap_or_else(|_| panic!("{}", ConfigurationAccessError::LockAcquisitionError))
-            .get_feature(&self.feature_id)
-            .expect(MISSING_FEATURE_ERROR_MSG)
-            .segment_rules
-            .clone()
+    pub fn get_targeting_rules(&self) -> Result<Vec<models::TargetingRule>> {
+        self.read(|feature| feature.segment_rules.clone())
     }
 
     /// Returns if the feature is enabled or not.
-    pub fn is_enabled(&self) -> bool {
-        self.configuration_snapshot
-            .lock()
-            .unwrap_or_else(|_| panic!("{}", ConfigurationAccessError::LockAcquisitionError))
-            .get_feature(&self.feature_id)
-            .expect(MISSING_FEATURE_ERROR_MSG)
-            .enabled
+    pub fn is_enabled(&self) -> Result<bool> {
+        self.read(|feature| feature.enabled)
+    }
+
+    /// Calls `f` with a [`Feature`] taken from the current configuration,
+    /// acquiring the lock on the configuration only once for a batch of
+    /// reads, instead of once per method call of the proxy.
+    ///
+    /// The [`Feature`] is a snapshot: configuration updates received while
+    /// `f` runs are not reflected in it.
+    pub fn with_snapshot<R>(&self, f: impl FnOnce(&Feature) -> R) -> Result<R> {
+        let feature = {
+            let configuration_snapshot = cache::read_snapshot(&self.configuration_snapshot);
+            let feature = configuration_snapshot.get_feature(&self.feature_id)?;
+            let segments = AppConfigurationClient::referenced_segments(
+                &configuration_snapshot,
+                &self.feature_id,
+                &feature.segment_rules,
+            )?;
+            Feature::new(feature.clone(), segments)
+                .with_evaluation_options(self.evaluation_options)
+                .with_metrics(self.metrics.clone())
+                .with_usage(self.usage.clone())
+        };
+        Ok(f(&feature))
     }
 
     /// Evaluates the feature for `entity` and returns the evaluation as a
     /// `models::ConfigValue`.
-    pub fn get_current_value(&self, entity: &impl Entity) -> models::ConfigValue {
-        if !self.is_enabled() {
-            self.get_disabled_value()
-        } else {
-            self.evaluate_feature_for_entity(entity)
-        }
+    pub fn get_current_value(&self, entity: &impl Entity) -> Result<models::ConfigValue> {
+        // Evaluate under a single lock, so the feature and its segments are
+        // taken from the same configuration without cloning them.
+        let configuration_snapshot = cache::read_snapshot(&self.configuration_snapshot);
+        let feature = configuration_snapshot.get_feature(&self.feature_id)?;
+        let (value, reason, _) = evaluate(
+            feature,
+            &configuration_snapshot.segments,
+            entity,
+            &self.evaluation_options,
+            &SegmentMemo::default(),
+        )?;
+        self.metrics.record_evaluation(&self.feature_id, reason);
+        self.usage.record(&self.feature_id, entity);
+        Ok(value)
     }
+}
 
-    fn evaluate_feature_for_entity(&self, entity: &impl Entity) -> models::ConfigValue {
-        let tag = format!("{}:{}", entity.get_id(), self.get_id());
-
-        if self.get_targeting_rules().len() == 0 && entity.get_attributes().len() == 0 {
-            // TODO rollout percentage evaluation
-        }
+/// Returns the rollout bucket of the entity `entity_id` for the feature
+/// `feature_id`, from 0 to 100: the entity is part of a rollout of `n`
+/// percent if its bucket is below `n`. Buckets are the same as in other App
+/// Configuration SDKs.
+///
+/// The bucket is computed from the tag `"{entity_id}:{feature_id}"`, hashed
+/// as UTF-8 with 32 bit MurmurHash3 (x86 variant) and seed 0, as
+/// `floor(hash / (2^32 - 1) * 100)`. It is 100 only for the largest
+/// hash, so in practice buckets range from 0 to 99. For example the bucket of
+/// entity `a1` for feature `f1` is 68 (hash 2957761103), so the entity is
+/// part of rollouts of more than 68 percent.
+pub fn rollout_bucket(entity_id: &str, feature_id: &str) -> u32 {
+    rollout_bucket_with_seed(entity_id, feature_id, 0)
+}
 
-        let segment_rule = find_applicable_segment_rule_for_entity(
-            &self
-                .configuration_snapshot
-                .lock()
-                .unwrap_or_else(|e| panic!("Failed to acquire configuration snapshot lock: {e}"))
-                .segments,
-            self.get_targeting_rules().into_iter(),
-            entity,
-        );
-        if let Some(segment_rule) = segment_rule {
-            let rollout_percentage = self.resolve_rollout_percentage(&segment_rule);
-            if rollout_percentage == 100 || random_value(&tag) < rollout_percentage {
-                self.resolve_enabled_value(&segment_rule)
-            } else {
-                self.get_disabled_value()
-            }
-        } else {
-            let rollout_percentage = self.get_rollout_percentage();
-            if rollout_percentage == 100 || random_value(&tag) < rollout_percentage {
-                self.get_enabled_value()
-            } else {
-                self.get_disabled_value()
-            }
-        }
-    }
+/// Returns the rollout bucket of the entity `entity_id` for the feature
+/// `feature_id` as a percentage from 0 to 99, for reproducing the bucketing
+/// outside of the SDK: the tag `"{entity_id}:{feature_id}"` hashed with
+/// 32 bit MurmurHash3 and seed 0, as documented for [`rollout_bucket`].
+///
+/// It is the [`rollout_bucket`], except for the largest hash which is put
+/// into bucket 99 instead of 100.
+pub fn rollout_percentage_for(entity_id: &str, feature_id: &str) -> u32 {
+    rollout_bucket(entity_id, feature_id).min(99)
+}
 
-    fn resolve_rollout_percentage(&self, segment_rule: &models::TargetingRule) -> u32 {
-        let missing_rollout_msg = "Rollout velue is missing.";
-        let rollout_value = segment_rule
-            .rollout_percentage
-            .as_ref()
-            .expect(missing_rollout_msg);
-        if rollout_value.is_default() {
-            self.get_rollout_percentage()
-        } else {
-            u32::try_from(rollout_value.as_u64().expect(missing_rollout_msg))
-                .expect("Invalid rollout value.")
-        }
-    }
+/// Like [`rollout_bucket`], hashing with `seed` instead of the default 0,
+/// see
+/// [`EvaluationOptions::rollout_seed`](crate::EvaluationOptions::rollout_seed).
+pub fn rollout_bucket_with_seed(entity_id: &str, feature_id: &str, seed: u32) -> u32 {
+    random_value(&format!("{entity_id}:{feature_id}"), seed)
+}
 
-    fn resolve_enabled_value(&self, segment_rule: &models::TargetingRule) -> models::ConfigValue {
-        if segment_rule.value.is_default() {
-            self.get_enabled_value()
-        } else {
-            segment_rule.value.clone()
-        }
-    }
+/// Bucket of the entity `entity_id` picking its value among the weighted
+/// values of a targeting rule of the feature `feature_id`, from 0 to 100.
+/// It is hashed from another tag than the rollout bucket, so that values are
+/// shared out evenly among the entities part of a partial rollout.
+pub(crate) fn variant_bucket_with_seed(entity_id: &str, feature_id: &str, seed: u32) -> u32 {
+    random_value(&format!("{entity_id}:{feature_id}:variant"), seed)
 }
 
-pub fn random_value(v: &str) -> u32 {
+pub(crate) fn random_value(v: &str, seed: u32) -> u32 {
     let max_hash = u32::MAX;
-    (f64::from(hash(v)) / f64::from(max_hash) * 100.0) as u32
+    (f64::from(hash(v, seed)) / f64::from(max_hash) * 100.0) as u32
 }
 
-fn hash(v: &str) -> u32 {
-    murmur3_32(&mut Cursor::new(v), 0).expect("Cannot hash the value.")
+fn hash(v: &str, seed: u32) -> u32 {
+    murmur3_32(&mut Cursor::new(v), seed).expect("Cannot hash the value.")
 }
@@ -13,33 +13,357 @@
 // limitations under the License.
 
 use std::collections::HashMap;
+use std::fmt;
+use std::io::{Read, Write};
 use std::net::TcpStream;
+use std::sync::{Arc, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
 
-use reqwest::blocking::Client;
-use serde::Deserialize;
+use base64::prelude::{Engine, BASE64_STANDARD};
+use reqwest::blocking::{Client, RequestBuilder};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
 use tungstenite::client::IntoClientRequest;
-use tungstenite::handshake::client::Response;
+use tungstenite::handshake::client::{Request, Response};
 use tungstenite::stream::MaybeTlsStream;
-use tungstenite::{connect, WebSocket};
+use tungstenite::{client_tls, connect, HandshakeError, WebSocket};
 use url::Url;
 
-use crate::errors::{Error, Result};
+use crate::client::cache;
+use crate::errors::{DeserializationError, Error, Result};
 use crate::models;
 
+/// Default timeout for HTTP requests.
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Default number of attempts for HTTP requests failing with transient errors.
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+
+/// Default delay before retrying a failed HTTP request. It is doubled for
+/// every further retry.
+pub const DEFAULT_RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+
+/// `User-Agent` of all requests, followed by the suffix set with
+/// [`AppConfigurationClientBuilder::user_agent_suffix`](crate::client::AppConfigurationClientBuilder::user_agent_suffix)
+/// if any.
+pub const USER_AGENT: &str = concat!("appconfiguration-rust-sdk/", env!("CARGO_PKG_VERSION"));
+
+/// Settings applied to the HTTP requests sent to IAM and App Configuration.
+#[derive(Debug, Clone)]
+pub(crate) struct HttpOptions {
+    pub request_timeout: Duration,
+    pub max_attempts: u32,
+    pub retry_base_delay: Duration,
+    pub proxy: Option<Url>,
+    pub user_agent: String,
+    /// Client sending the requests, built on first use and shared by the
+    /// clones of these options, so that connections and TLS sessions are
+    /// reused across requests.
+    client: Arc<OnceLock<Client>>,
+    /// Like `client`, for the requests of async clients.
+    #[cfg(feature = "async")]
+    async_client: Arc<OnceLock<reqwest::Client>>,
+}
+
+impl Default for HttpOptions {
+    fn default() -> Self {
+        Self {
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            retry_base_delay: DEFAULT_RETRY_BASE_DELAY,
+            proxy: None,
+            user_agent: USER_AGENT.to_string(),
+            client: Arc::default(),
+            #[cfg(feature = "async")]
+            async_client: Arc::default(),
+        }
+    }
+}
+
+impl HttpOptions {
+    /// Returns the client for these options, building it on first use.
+    fn client(&self) -> Result<&Client> {
+        if let Some(client) = self.client.get() {
+            return Ok(client);
+        }
+        let client = build_client(self)?;
+        Ok(self.client.get_or_init(|| client))
+    }
+
+    /// Like [`HttpOptions::client`], for async requests.
+    #[cfg(feature = "async")]
+    pub fn async_client(&self) -> Result<&reqwest::Client> {
+        if let Some(client) = self.async_client.get() {
+            return Ok(client);
+        }
+        let client = client_builder(self)?
+            .timeout(self.request_timeout)
+            .build()
+            .map_err(Error::ReqwestError)?;
+        Ok(self.async_client.get_or_init(|| client))
+    }
+
+    /// Drops the client (if any) built for the previous settings, and stops
+    /// sharing it with clones: it is rebuilt with the current settings.
+    pub fn reset_client(&mut self) {
+        self.client = Arc::default();
+        #[cfg(feature = "async")]
+        {
+            self.async_client = Arc::default();
+        }
+    }
+}
+
+/// Builder with the settings of `options` shared by the blocking and the
+/// async client. The request timeout is set on each of them, as the
+/// blocking client keeps its own.
+fn client_builder(options: &HttpOptions) -> Result<reqwest::ClientBuilder> {
+    let mut builder = reqwest::Client::builder().user_agent(&options.user_agent);
+    if let Some(proxy) = &options.proxy {
+        builder = builder.proxy(reqwest::Proxy::all(proxy.clone())?);
+    }
+    Ok(builder)
+}
+
+fn build_client(options: &HttpOptions) -> Result<Client> {
+    reqwest::blocking::ClientBuilder::from(client_builder(options)?)
+        .timeout(options.request_timeout)
+        .build()
+        .map_err(Error::ReqwestError)
+}
+
+pub(crate) fn reqwest_error(error: reqwest::Error) -> Error {
+    if error.is_timeout() {
+        Error::RequestTimeout(error)
+    } else {
+        Error::ReqwestError(error)
+    }
+}
+
+/// Sends `request`, failing if the server answers with an error status.
+/// Authentication failures are [`Error::AuthenticationError`]s.
+fn send(request: RequestBuilder) -> Result<reqwest::blocking::Response> {
+    let response = request.send().map_err(reqwest_error)?;
+    if is_authentication_failure(response.status()) {
+        return Err(Error::AuthenticationError {
+            status: response.status().as_u16(),
+            body: response.text().unwrap_or_default(),
+        });
+    }
+    response.error_for_status().map_err(reqwest_error)
+}
+
+/// Whether the server answered with `status` because the API key (or the
+/// access token obtained with it) is invalid or lacks permissions.
+pub(crate) fn is_authentication_failure(status: reqwest::StatusCode) -> bool {
+    matches!(
+        status,
+        reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN
+    )
+}
+
+/// Reads the body of `response` and deserializes it from JSON. A body which
+/// cannot be deserialized is returned in the error.
+fn json<T: DeserializeOwned>(response: reqwest::blocking::Response) -> Result<T> {
+    deserialize(&response.text().map_err(reqwest_error)?)
+}
+
+pub(crate) fn deserialize<T: DeserializeOwned>(body: &str) -> Result<T> {
+    serde_json::from_str(body).map_err(|e| {
+        DeserializationError {
+            string: body.to_string(),
+            source: e.into(),
+        }
+        .into()
+    })
+}
+
+/// Whether a request failing with `error` may succeed if sent again.
+/// Client errors (like a 401 for an invalid apikey) are not.
+pub(crate) fn is_transient(error: &Error) -> bool {
+    match error {
+        Error::RequestTimeout(_) => true,
+        Error::ReqwestError(e) => {
+            e.is_connect() || e.status().is_some_and(|status| status.is_server_error())
+        }
+        _ => false,
+    }
+}
+
+/// Calls `request` until it succeeds, fails with a non-transient error, or
+/// `options.max_attempts` is reached. The delay between attempts grows
+/// exponentially starting at `options.retry_base_delay`.
+fn with_retries<T>(options: &HttpOptions, mut request: impl FnMut() -> Result<T>) -> Result<T> {
+    let mut attempt = 1;
+    loop {
+        match request() {
+            Err(e) if attempt < options.max_attempts && is_transient(&e) => {
+                let delay = retry_delay(options, attempt);
+                log::warn!("Request failed ({e}). Retrying in {delay:?}.");
+                thread::sleep(delay);
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Delay before sending a request again after its `attempt`-th failure.
+pub(crate) fn retry_delay(options: &HttpOptions, attempt: u32) -> Duration {
+    options
+        .retry_base_delay
+        .saturating_mul(2u32.saturating_pow(attempt - 1))
+}
+
 #[derive(Deserialize)]
-struct AccessTokenResponse {
-    access_token: String,
+pub(crate) struct AccessTokenResponse {
+    pub access_token: String,
+    /// Lifetime of the token in seconds
+    #[serde(default)]
+    pub expires_in: Option<u64>,
+}
+
+/// Stands for a secret in `Debug` output, so that credentials don't end up
+/// in logs.
+pub(crate) struct Redacted;
+
+impl fmt::Debug for Redacted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("***")
+    }
+}
+
+/// An IAM access token, and when to renew it.
+#[derive(Clone, Default)]
+pub(crate) struct AccessToken {
+    pub token: String,
+    renew_at: Option<Instant>,
 }
 
+impl AccessToken {
+    /// A token obtained by the application. Its lifetime is unknown, so it
+    /// is used until the server rejects it.
+    pub fn new(token: String) -> Self {
+        AccessToken {
+            token,
+            renew_at: None,
+        }
+    }
+
+    /// Whether the token is missing, or so close to expiring that it should
+    /// be renewed.
+    pub fn needs_renewal(&self) -> bool {
+        self.token.is_empty()
+            || self
+                .renew_at
+                .is_some_and(|renew_at| Instant::now() >= renew_at)
+    }
+
+    /// Keeps using the token for `delay`, after failing to renew it.
+    pub fn postpone_renewal(&mut self, delay: Duration) {
+        self.renew_at = Some(Instant::now() + delay);
+    }
+}
+
+impl fmt::Debug for AccessToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AccessToken")
+            .field("token", &Redacted)
+            .field("renew_at", &self.renew_at)
+            .finish()
+    }
+}
+
+impl From<AccessTokenResponse> for AccessToken {
+    /// Tokens are renewed once 80% of their lifetime passed, as in other
+    /// IBM Cloud SDKs. Tokens without a lifetime are never renewed.
+    fn from(response: AccessTokenResponse) -> Self {
+        AccessToken {
+            token: response.access_token,
+            renew_at: response
+                .expires_in
+                .map(|expires_in| Instant::now() + Duration::from_secs(expires_in).mul_f64(0.8)),
+        }
+    }
+}
+
+/// Closure returning a new access token, for applications obtaining access
+/// tokens themselves.
+#[derive(Clone)]
+pub(crate) struct AccessTokenRefresh(pub Arc<dyn Fn() -> Result<String> + Send + Sync>);
+
+impl fmt::Debug for AccessTokenRefresh {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("AccessTokenRefresh")
+    }
+}
+
+/// How a client authenticates to App Configuration.
+#[derive(Clone)]
+pub(crate) enum Credentials {
+    /// An API key, exchanged at IAM for access tokens
+    ApiKey(String),
+    /// An access token obtained by the application, replaced with the one
+    /// returned by `refresh` (if any) whenever a new one is needed
+    AccessToken {
+        token: String,
+        refresh: Option<AccessTokenRefresh>,
+    },
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Credentials::ApiKey(_) => f.debug_tuple("ApiKey").field(&Redacted).finish(),
+            Credentials::AccessToken { refresh, .. } => f
+                .debug_struct("AccessToken")
+                .field("token", &Redacted)
+                .field("refresh", refresh)
+                .finish(),
+        }
+    }
+}
+
+impl Credentials {
+    /// The access token to start with: the one obtained by the application,
+    /// if any. Otherwise it has to be requested with [`get_access_token`].
+    pub fn initial_access_token(&self) -> AccessToken {
+        match self {
+            Credentials::ApiKey(_) => AccessToken::default(),
+            Credentials::AccessToken { token, .. } => AccessToken::new(token.clone()),
+        }
+    }
+}
+
+/// A new access token obtained by the application: the one returned by
+/// `refresh`, or `token` again if there's no way to refresh it.
+pub(crate) fn refresh_access_token(
+    token: &str,
+    refresh: Option<&AccessTokenRefresh>,
+) -> Result<AccessToken> {
+    match refresh {
+        Some(refresh) => (refresh.0)().map(AccessToken::new),
+        None => Ok(AccessToken::new(token.to_string())),
+    }
+}
+
+pub(crate) const IAM_TOKEN_URL: &str = "https://iam.cloud.ibm.com/identity/token";
+
 pub fn get_base_url(region: &str, guid: &str) -> String {
     format!("https://{region}.apprapp.cloud.ibm.com/apprapp/feature/v1/instances/{guid}/config")
 }
 
+pub fn get_usage_url(region: &str, guid: &str) -> String {
+    format!("https://{region}.apprapp.cloud.ibm.com/apprapp/events/v1/instances/{guid}/usage")
+}
+
 pub fn get_ws_url(region: &str) -> String {
     format!("wss://{region}.apprapp.cloud.ibm.com/apprapp/wsfeature")
 }
 
-pub fn get_access_token(apikey: &str) -> Result<String> {
+/// Form to exchange `apikey` for an access token at [`IAM_TOKEN_URL`].
+pub(crate) fn access_token_form(apikey: &str) -> HashMap<String, String> {
     let mut form_data = HashMap::new();
     form_data.insert("reponse_type".to_string(), "cloud_iam".to_string());
     form_data.insert(
@@ -47,51 +371,129 @@ pub fn get_access_token(apikey: &str) -> Result<String> {
         "urn:ibm:params:oauth:grant-type:apikey".to_string(),
     );
     form_data.insert("apikey".to_string(), apikey.to_string());
+    form_data
+}
 
-    let client = Client::new();
-    Ok(client
-        .post("https://iam.cloud.ibm.com/identity/token")
-        .header("Accept", "application/json")
-        .form(&form_data)
-        .send()
-        .map_err(Error::ReqwestError)?
-        .json::<AccessTokenResponse>()
-        .map_err(Error::ReqwestError)? // FIXME: This is a deserialization error (extract it from Reqwest)
-        .access_token)
+/// Returns a new access token: the API key is exchanged for one at IAM, tokens
+/// obtained by the application are refreshed if possible.
+pub(crate) fn get_access_token(
+    credentials: &Credentials,
+    options: &HttpOptions,
+) -> Result<AccessToken> {
+    let apikey = match credentials {
+        Credentials::ApiKey(apikey) => apikey,
+        Credentials::AccessToken { token, refresh } => {
+            return refresh_access_token(token, refresh.as_ref())
+        }
+    };
+    let form_data = access_token_form(apikey);
+    let client = options.client()?;
+    with_retries(options, || {
+        let response = send(
+            client
+                .post(IAM_TOKEN_URL)
+                .header("Accept", "application/json")
+                .form(&form_data),
+        )?;
+        Ok(json::<AccessTokenResponse>(response)?.into())
+    })
 }
 
+/// Downloads the configuration of the collection `collection_id` in the
+/// environment `environment_id`. With the `compression` feature, it is
+/// requested gzip or deflate compressed.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(skip(access_token, options), err(Display))
+)]
 pub fn get_configuration(
     access_token: &str,
     region: &str,
     guid: &str,
     collection_id: &str,
     environment_id: &str,
+    options: &HttpOptions,
 ) -> Result<models::Configuration> {
-    let client = Client::new();
+    let client = options.client()?;
     let url = get_base_url(region, guid);
-    client
-        .get(&url)
-        .query(&[
-            ("action", "sdkConfig"),
-            ("collection_id", collection_id),
-            ("environment_id", environment_id),
-        ])
-        .header("Accept", "application/json")
-        .header("User-Agent", "appconfiguration-rust-sdk/0.0.1")
-        .bearer_auth(access_token)
-        .send()
-        .map_err(Error::ReqwestError)?
-        .json()
-        .map_err(Error::ReqwestError) // FIXME: This is a deserialization error (extract it from Reqwest)
+    with_retries(options, || {
+        let response = send(
+            client
+                .get(&url)
+                .query(&[
+                    ("action", "sdkConfig"),
+                    ("collection_id", collection_id),
+                    ("environment_id", environment_id),
+                ])
+                .header("Accept", "application/json")
+                .bearer_auth(access_token),
+        )?;
+        cache::parse_configuration(&response.text().map_err(reqwest_error)?)
+    })
+}
+
+/// Sends the usage (feature evaluations) collected by the client to the
+/// metering endpoint.
+pub fn post_usage(
+    access_token: &str,
+    region: &str,
+    guid: &str,
+    usage: &impl Serialize,
+    options: &HttpOptions,
+) -> Result<()> {
+    let client = options.client()?;
+    let url = get_usage_url(region, guid);
+    with_retries(options, || {
+        send(client.post(&url).json(usage).bearer_auth(access_token))?;
+        Ok(())
+    })
 }
 
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(skip(access_token, options), err(Display))
+)]
 pub fn get_configuration_monitoring_websocket(
     access_token: &str,
     region: &str,
     guid: &str,
     collection_id: &str,
     environment_id: &str,
+    options: &HttpOptions,
 ) -> Result<(WebSocket<MaybeTlsStream<TcpStream>>, Response)> {
+    let (request, url) = configuration_monitoring_request(
+        access_token,
+        region,
+        guid,
+        collection_id,
+        environment_id,
+        &options.user_agent,
+    )?;
+    match &options.proxy {
+        None => Ok(connect(request)?),
+        Some(proxy) => {
+            let (host, port) = host_and_port(&url)?;
+            let stream = connect_via_proxy(proxy, host, port)?;
+            client_tls(request, stream).map_err(|e| match e {
+                HandshakeError::Failure(e) => Error::TungsteniteError(e),
+                HandshakeError::Interrupted(_) => {
+                    Error::Other("WebSocket handshake interrupted".to_string())
+                }
+            })
+        }
+    }
+}
+
+/// The request opening the WebSocket on which the server notifies
+/// configuration changes, and the URL it is sent to.
+pub(crate) fn configuration_monitoring_request(
+    access_token: &str,
+    region: &str,
+    guid: &str,
+    collection_id: &str,
+    environment_id: &str,
+    user_agent: &str,
+) -> Result<(Request, Url)> {
     let url = get_ws_url(region);
     let mut url = Url::parse(&url)
         .map_err(|e| Error::Other(format!("Cannot parse '{}' as URL: {}", url, e)))?;
@@ -108,7 +510,7 @@ pub fn get_configuration_monitoring_websocket(
     let headers = request.headers_mut();
     headers.insert(
         "User-Agent",
-        "appconfiguration-rust-sdk/0.0.1"
+        user_agent
             .parse()
             .map_err(|_| Error::Other("Invalid header value for 'User-Agent'".to_string()))?,
     );
@@ -118,6 +520,360 @@ pub fn get_configuration_monitoring_websocket(
             .parse()
             .map_err(|_| Error::Other("Invalid header value for 'Authorization'".to_string()))?,
     );
+    Ok((request, url))
+}
+
+/// Sets the timeout for reading from `socket`: reads fail with
+/// `WouldBlock` or `TimedOut` if nothing is received in time.
+pub(crate) fn set_read_timeout(
+    socket: &WebSocket<MaybeTlsStream<TcpStream>>,
+    timeout: Option<Duration>,
+) -> Result<()> {
+    match socket.get_ref() {
+        MaybeTlsStream::Plain(stream) => stream.set_read_timeout(timeout)?,
+        MaybeTlsStream::NativeTls(stream) => stream.get_ref().set_read_timeout(timeout)?,
+        _ => {}
+    }
+    Ok(())
+}
+
+pub(crate) fn host_and_port(url: &Url) -> Result<(&str, u16)> {
+    let host = url
+        .host_str()
+        .ok_or_else(|| Error::Other(format!("URL '{}' has no host", url)))?;
+    Ok((host, url.port_or_known_default().unwrap_or(443)))
+}
+
+/// Opens a TCP connection to `host`:`port` tunneled through the HTTP proxy
+/// at `proxy` (using the `CONNECT` method).
+pub(crate) fn connect_via_proxy(proxy: &Url, host: &str, port: u16) -> Result<TcpStream> {
+    if proxy.scheme() != "http" {
+        return Err(Error::Other(format!(
+            "Unsupported proxy '{}': only 'http' proxies can be used for WebSockets",
+            proxy
+        )));
+    }
+    let mut stream = TcpStream::connect(&*proxy.socket_addrs(|| Some(80))?)?;
+
+    let mut request = format!("CONNECT {host}:{port} HTTP/1.1\r\nHost: {host}:{port}\r\n");
+    if !proxy.username().is_empty() {
+        let decode = |s| percent_encoding::percent_decode_str(s).decode_utf8_lossy();
+        let credentials = format!(
+            "{}:{}",
+            decode(proxy.username()),
+            decode(proxy.password().unwrap_or_default())
+        );
+        request.push_str(&format!(
+            "Proxy-Authorization: Basic {}\r\n",
+            BASE64_STANDARD.encode(credentials)
+        ));
+    }
+    request.push_str("\r\n");
+    stream.write_all(request.as_bytes())?;
+
+    // Read the response head byte by byte: anything after it already belongs
+    // to the tunneled connection.
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        let mut byte = [0];
+        if stream.read(&mut byte)? == 0 {
+            return Err(Error::Other(format!(
+                "Proxy '{}' closed the connection",
+                proxy
+            )));
+        }
+        head.push(byte[0]);
+    }
+    let head = String::from_utf8_lossy(&head);
+    let status_line = head.lines().next().unwrap_or_default();
+    if status_line.split_whitespace().nth(1) != Some("200") {
+        return Err(Error::Other(format!(
+            "Proxy '{}' refused to connect to '{host}:{port}': {status_line}",
+            proxy
+        )));
+    }
+    Ok(stream)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Serves one canned response per incoming request, with the given
+    /// status codes. Returns the URL to send requests to and the number of
+    /// requests received so far.
+    fn mock_server(statuses: Vec<u16>) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        thread::spawn(move || {
+            for status in statuses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut buffer = [0; 4096];
+                let _ = stream.read(&mut buffer).unwrap();
+                counter.fetch_add(1, Ordering::SeqCst);
+                let body = "{}";
+                let response = format!(
+                    "HTTP/1.1 {status} Mock\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        (url, requests)
+    }
+
+    fn retry_options(max_attempts: u32) -> HttpOptions {
+        HttpOptions {
+            max_attempts,
+            retry_base_delay: Duration::from_millis(1),
+            ..HttpOptions::default()
+        }
+    }
+
+    #[test]
+    fn test_client_is_shared_by_clones() {
+        let options = HttpOptions::default();
+        let mut clone = options.clone();
+        assert!(std::ptr::eq(
+            options.client().unwrap(),
+            clone.client().unwrap()
+        ));
+
+        clone.reset_client();
+        assert!(!std::ptr::eq(
+            options.client().unwrap(),
+            clone.client().unwrap()
+        ));
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_async_client_is_shared_by_clones() {
+        let options = HttpOptions::default();
+        let mut clone = options.clone();
+        assert!(std::ptr::eq(
+            options.async_client().unwrap(),
+            clone.async_client().unwrap()
+        ));
+
+        clone.reset_client();
+        assert!(!std::ptr::eq(
+            options.async_client().unwrap(),
+            clone.async_client().unwrap()
+        ));
+    }
+
+    #[test]
+    fn test_retry_transient_errors() {
+        let (url, requests) = mock_server(vec![503, 500, 200]);
+        let options = retry_options(3);
+        let client = build_client(&options).unwrap();
+
+        let result = with_retries(&options, || send(client.get(&url)));
+        assert_eq!(result.unwrap().status(), 200);
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_retry_gives_up_after_max_attempts() {
+        let (url, requests) = mock_server(vec![503, 503, 200]);
+        let options = retry_options(2);
+        let client = build_client(&options).unwrap();
+
+        let result = with_retries(&options, || send(client.get(&url)));
+        let error = result.unwrap_err();
+        assert!(
+            matches!(&error, Error::ReqwestError(e) if e.status() == Some(503.try_into().unwrap()))
+        );
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_no_retry_on_client_errors() {
+        let (url, requests) = mock_server(vec![404, 200]);
+        let options = retry_options(3);
+        let client = build_client(&options).unwrap();
+
+        let result = with_retries(&options, || send(client.get(&url)));
+        let error = result.unwrap_err();
+        assert!(
+            matches!(&error, Error::ReqwestError(e) if e.status() == Some(404.try_into().unwrap()))
+        );
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[rstest]
+    #[case(401)]
+    #[case(403)]
+    fn test_authentication_error(#[case] status: u16) {
+        let (url, requests) = mock_server(vec![status, 200]);
+        let options = retry_options(3);
+        let client = build_client(&options).unwrap();
+
+        let result = with_retries(&options, || send(client.get(&url)));
+        assert!(matches!(
+            result.unwrap_err(),
+            Error::AuthenticationError { status: s, ref body } if s == status && body == "{}"
+        ));
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_access_token_renewal() {
+        let response: AccessTokenResponse =
+            serde_json::from_str(r#"{"access_token": "token", "expires_in": 3600}"#).unwrap();
+        let mut access_token = AccessToken::from(response);
+        assert_eq!(access_token.token, "token");
+        assert!(!access_token.needs_renewal());
+
+        access_token.postpone_renewal(Duration::ZERO);
+        assert!(access_token.needs_renewal());
+
+        // Without a lifetime, only missing tokens are renewed
+        let response: AccessTokenResponse =
+            serde_json::from_str(r#"{"access_token": "token"}"#).unwrap();
+        assert!(!AccessToken::from(response).needs_renewal());
+        assert!(AccessToken::default().needs_renewal());
+    }
+
+    #[test]
+    fn test_supplied_access_token() {
+        let credentials = Credentials::AccessToken {
+            token: "token".to_string(),
+            refresh: None,
+        };
+        let access_token = credentials.initial_access_token();
+        assert_eq!(access_token.token, "token");
+        assert!(!access_token.needs_renewal());
+
+        // Without a way to refresh it, the token is used again
+        let access_token = get_access_token(&credentials, &HttpOptions::default()).unwrap();
+        assert_eq!(access_token.token, "token");
+
+        let credentials = Credentials::AccessToken {
+            token: "token".to_string(),
+            refresh: Some(AccessTokenRefresh(Arc::new(|| {
+                Err(Error::Other("sidecar unavailable".to_string()))
+            }))),
+        };
+        let result = get_access_token(&credentials, &HttpOptions::default());
+        assert!(matches!(result, Err(Error::Other(_))));
+    }
+
+    #[test]
+    fn test_unexpected_body_is_deserialization_error() {
+        let (url, _) = mock_server(vec![200]);
+        let client = build_client(&HttpOptions::default()).unwrap();
+
+        let response = send(client.get(&url)).unwrap();
+        let result = json::<AccessTokenResponse>(response);
+        assert!(matches!(result, Err(Error::DeserializationError(e)) if e.string == "{}"));
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn test_compressed_configuration() {
+        use flate2::{write::GzEncoder, Compression};
+
+        let mut path = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("data/data-dump-enterprise-plan-sdk-testing.json");
+        let content = std::fs::read(path).unwrap();
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&content).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buffer = [0; 4096];
+            let read = stream.read(&mut buffer).unwrap();
+            let request = String::from_utf8_lossy(&buffer[..read]).to_lowercase();
+            let header = format!(
+                "HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                compressed.len()
+            );
+            stream.write_all(header.as_bytes()).unwrap();
+            stream.write_all(&compressed).unwrap();
+            request
+        });
+
+        let client = build_client(&HttpOptions::default()).unwrap();
+        let response = send(client.get(&url)).unwrap();
+        let configuration: models::Configuration = json(response).unwrap();
+        let request = server.join().unwrap();
+        assert!(request.contains("accept-encoding: gzip"), "{request}");
+
+        let expected: models::Configuration = serde_json::from_slice(&content).unwrap();
+        assert_eq!(
+            serde_json::to_value(configuration).unwrap(),
+            serde_json::to_value(expected).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_request_timeout() {
+        // Connections are accepted by the OS, but nobody ever answers.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let options = HttpOptions {
+            request_timeout: Duration::from_millis(100),
+            ..HttpOptions::default()
+        };
+
+        let client = build_client(&options).unwrap();
+        let error = client.get(url).send().map_err(reqwest_error).unwrap_err();
+        assert!(matches!(error, Error::RequestTimeout(_)));
+    }
+
+    /// Accepts a single connection and answers its `CONNECT` request with
+    /// `response`. Returns the proxy URL and a handle yielding the request.
+    fn mock_proxy(response: &'static str) -> (Url, thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = Url::parse(&format!(
+            "http://user:p%40ss@{}",
+            listener.local_addr().unwrap()
+        ));
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buffer = [0; 4096];
+            let size = stream.read(&mut buffer).unwrap();
+            stream.write_all(response.as_bytes()).unwrap();
+            String::from_utf8_lossy(&buffer[..size]).to_string()
+        });
+        (url.unwrap(), handle)
+    }
+
+    #[test]
+    fn test_connect_via_proxy() {
+        let (proxy, handle) = mock_proxy("HTTP/1.1 200 Connection established\r\n\r\n");
+
+        connect_via_proxy(&proxy, "example.com", 443).unwrap();
+        let request = handle.join().unwrap();
+        assert_eq!(
+            request,
+            format!(
+                "CONNECT example.com:443 HTTP/1.1\r\nHost: example.com:443\r\nProxy-Authorization: Basic {}\r\n\r\n",
+                BASE64_STANDARD.encode("user:p@ss")
+            )
+        );
+    }
+
+    #[test]
+    fn test_connect_via_proxy_refused() {
+        let (proxy, handle) = mock_proxy("HTTP/1.1 407 Proxy Authentication Required\r\n\r\n");
 
-    Ok(connect(request)?)
+        let error = connect_via_proxy(&proxy, "example.com", 443).unwrap_err();
+        handle.join().unwrap();
+        assert!(matches!(error, Error::Other(_)));
+        assert!(error.to_string().ends_with(
+            "refused to connect to 'example.com:443': HTTP/1.1 407 Proxy Authentication Required"
+        ));
+    }
 }
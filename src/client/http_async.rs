@@ -0,0 +1,165 @@
+// (C) Copyright IBM Corp. 2024.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Async counterparts of the requests in [`crate::client::http`], used by
+//! clients created with `AppConfigurationClient::new_async`.
+
+use std::future::Future;
+
+use reqwest::RequestBuilder;
+use serde::de::DeserializeOwned;
+use tokio::net::TcpStream;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+use crate::client::cache;
+use crate::client::http::{self, AccessToken, AccessTokenResponse, Credentials, HttpOptions};
+use crate::errors::{Error, Result};
+use crate::models;
+
+pub(crate) type MonitoringWebSocket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Sends `request`, failing if the server answers with an error status,
+/// like `http::send`.
+async fn send(request: RequestBuilder) -> Result<reqwest::Response> {
+    let response = request.send().await.map_err(http::reqwest_error)?;
+    if http::is_authentication_failure(response.status()) {
+        return Err(Error::AuthenticationError {
+            status: response.status().as_u16(),
+            body: response.text().await.unwrap_or_default(),
+        });
+    }
+    response.error_for_status().map_err(http::reqwest_error)
+}
+
+/// Reads the body of `response` and deserializes it from JSON, like
+/// `http::json`.
+async fn json<T: DeserializeOwned>(response: reqwest::Response) -> Result<T> {
+    http::deserialize(&response.text().await.map_err(http::reqwest_error)?)
+}
+
+/// Like `http::with_retries`, waiting on the runtime between attempts.
+async fn with_retries<T, F, Fut>(options: &HttpOptions, mut request: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut attempt = 1;
+    loop {
+        match request().await {
+            Err(e) if attempt < options.max_attempts && http::is_transient(&e) => {
+                let delay = http::retry_delay(options, attempt);
+                log::warn!("Request failed ({e}). Retrying in {delay:?}.");
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+pub async fn get_access_token(
+    credentials: &Credentials,
+    options: &HttpOptions,
+) -> Result<AccessToken> {
+    let apikey = match credentials {
+        Credentials::ApiKey(apikey) => apikey,
+        Credentials::AccessToken { token, refresh } => {
+            return http::refresh_access_token(token, refresh.as_ref())
+        }
+    };
+    let form_data = &http::access_token_form(apikey);
+    let client = options.async_client()?;
+    with_retries(options, || async move {
+        let response = send(
+            client
+                .post(http::IAM_TOKEN_URL)
+                .header("Accept", "application/json")
+                .form(form_data),
+        )
+        .await?;
+        Ok(json::<AccessTokenResponse>(response).await?.into())
+    })
+    .await
+}
+
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(skip(access_token, options), err(Display))
+)]
+pub async fn get_configuration(
+    access_token: &str,
+    region: &str,
+    guid: &str,
+    collection_id: &str,
+    environment_id: &str,
+    options: &HttpOptions,
+) -> Result<models::Configuration> {
+    let client = options.async_client()?;
+    let url = &http::get_base_url(region, guid);
+    with_retries(options, || async move {
+        let response = send(
+            client
+                .get(url)
+                .query(&[
+                    ("action", "sdkConfig"),
+                    ("collection_id", collection_id),
+                    ("environment_id", environment_id),
+                ])
+                .header("Accept", "application/json")
+                .bearer_auth(access_token),
+        )
+        .await?;
+        cache::parse_configuration(&response.text().await.map_err(http::reqwest_error)?)
+    })
+    .await
+}
+
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(skip(access_token, options), err(Display))
+)]
+pub async fn get_configuration_monitoring_websocket(
+    access_token: &str,
+    region: &str,
+    guid: &str,
+    collection_id: &str,
+    environment_id: &str,
+    options: &HttpOptions,
+) -> Result<MonitoringWebSocket> {
+    let (request, url) = http::configuration_monitoring_request(
+        access_token,
+        region,
+        guid,
+        collection_id,
+        environment_id,
+        &options.user_agent,
+    )?;
+    let (socket, _response) = match &options.proxy {
+        None => tokio_tungstenite::connect_async(request).await?,
+        Some(proxy) => {
+            let (host, port) = http::host_and_port(&url)?;
+            let (proxy, host) = (proxy.clone(), host.to_string());
+            // Only the tunnel is set up with blocking IO, on a blocking thread
+            let stream = tokio::task::spawn_blocking(move || {
+                let stream = http::connect_via_proxy(&proxy, &host, port)?;
+                stream.set_nonblocking(true)?;
+                Ok::<_, Error>(stream)
+            })
+            .await
+            .map_err(|e| Error::Other(format!("Cannot connect via proxy: {e}")))??;
+            tokio_tungstenite::client_async_tls(request, TcpStream::from_std(stream)?).await?
+        }
+    };
+    Ok(socket)
+}
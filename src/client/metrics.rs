@@ -0,0 +1,70 @@
+// (C) Copyright IBM Corp. 2024.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt;
+use std::sync::Arc;
+
+use crate::client::feature::EvaluationReason;
+
+/// Receives events to be counted, e.g. to feed `prometheus` or `metrics`.
+/// Set it with
+/// [`AppConfigurationClientBuilder::metrics_recorder`](crate::client::AppConfigurationClientBuilder::metrics_recorder).
+///
+/// All methods do nothing by default. They are called on the thread
+/// evaluating features, or on the background thread updating the
+/// configuration, and should return quickly.
+pub trait MetricsRecorder: Send + Sync {
+    /// Called each time the feature `feature_id` is evaluated for an entity.
+    fn record_evaluation(&self, feature_id: &str, reason: EvaluationReason) {
+        let _ = (feature_id, reason);
+    }
+
+    /// Called each time the configuration is fetched from the server, whether
+    /// it changed or not.
+    fn record_config_refresh(&self) {}
+}
+
+struct NoopMetricsRecorder;
+
+impl MetricsRecorder for NoopMetricsRecorder {}
+
+/// The recorder of a client, shared with its features and background thread.
+#[derive(Clone)]
+pub(crate) struct Metrics(Arc<dyn MetricsRecorder>);
+
+impl Metrics {
+    pub fn new(recorder: impl MetricsRecorder + 'static) -> Self {
+        Self(Arc::new(recorder))
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new(NoopMetricsRecorder)
+    }
+}
+
+impl std::ops::Deref for Metrics {
+    type Target = dyn MetricsRecorder;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref()
+    }
+}
+
+impl fmt::Debug for Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Metrics")
+    }
+}
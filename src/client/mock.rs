@@ -0,0 +1,187 @@
+// (C) Copyright IBM Corp. 2024.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use crate::client::app_configuration_client::AppConfigurationClient;
+use crate::client::cache::ConfigurationSnapshot;
+use crate::client::feature::Feature;
+use crate::client::property::Property;
+use crate::client::provider::FeatureProvider;
+use crate::client::value::Value;
+use crate::errors::Result;
+use crate::models;
+
+/// A [`FeatureProvider`] with features and properties set up front, for
+/// testing code that depends on them without network access.
+///
+/// ```
+/// # use appconfiguration_rust_sdk::client::{FeatureProvider, MockAppConfigurationClient};
+/// # use appconfiguration_rust_sdk::SimpleEntity;
+/// fn discount(provider: &impl FeatureProvider) -> i64 {
+///     let entity = SimpleEntity::new("user1").build();
+///     let feature = provider.get_feature("discount").unwrap();
+///     feature.get_value_as_i64(&entity).unwrap()
+/// }
+///
+/// let client = MockAppConfigurationClient::builder()
+///     .feature("discount", 10)
+///     .property("currency", "EUR")
+///     .build();
+/// assert_eq!(discount(&client), 10);
+/// ```
+#[derive(Debug, Clone)]
+pub struct MockAppConfigurationClient {
+    client: AppConfigurationClient,
+}
+
+impl MockAppConfigurationClient {
+    /// Returns a builder to set up the features and properties of the mock.
+    pub fn builder() -> MockAppConfigurationClientBuilder {
+        MockAppConfigurationClientBuilder::default()
+    }
+}
+
+impl FeatureProvider for MockAppConfigurationClient {
+    fn get_feature_ids(&self) -> Result<Vec<String>> {
+        self.client.get_feature_ids()
+    }
+
+    fn get_feature(&self, feature_id: &str) -> Result<Feature> {
+        self.client.get_feature(feature_id)
+    }
+
+    fn get_property_ids(&self) -> Result<Vec<String>> {
+        self.client.get_property_ids()
+    }
+
+    fn get_property(&self, property_id: &str) -> Result<Property> {
+        self.client.get_property(property_id)
+    }
+}
+
+/// Builder of [`MockAppConfigurationClient`]s. Features and properties
+/// have no targeting rules: they evaluate to the same value for all
+/// entities.
+#[derive(Debug, Default)]
+pub struct MockAppConfigurationClientBuilder {
+    features: HashMap<String, Arc<models::Feature>>,
+    properties: HashMap<String, Arc<models::Property>>,
+}
+
+impl MockAppConfigurationClientBuilder {
+    /// Adds the enabled feature `feature_id`, evaluating to `value`.
+    pub fn feature(self, feature_id: &str, value: impl Into<Value>) -> Self {
+        self.with_feature(feature_id, value.into(), true)
+    }
+
+    /// Adds the disabled feature `feature_id`, evaluating to
+    /// `disabled_value`.
+    pub fn disabled_feature(self, feature_id: &str, disabled_value: impl Into<Value>) -> Self {
+        self.with_feature(feature_id, disabled_value.into(), false)
+    }
+
+    fn with_feature(mut self, feature_id: &str, value: Value, enabled: bool) -> Self {
+        let (kind, value) = value.into_config_value();
+        let feature = models::Feature {
+            name: feature_id.to_string(),
+            feature_id: feature_id.to_string(),
+            kind,
+            tags: None,
+            format: None,
+            enabled_value: value.clone(),
+            disabled_value: value,
+            segment_rules: Vec::new(),
+            enabled,
+            rollout_percentage: 100,
+            collections: Vec::new(),
+        };
+        self.features
+            .insert(feature_id.to_string(), Arc::new(feature));
+        self
+    }
+
+    /// Adds the property `property_id`, evaluating to `value`.
+    pub fn property(mut self, property_id: &str, value: impl Into<Value>) -> Self {
+        let (kind, value) = value.into().into_config_value();
+        let property = models::Property {
+            name: property_id.to_string(),
+            property_id: property_id.to_string(),
+            kind,
+            tags: None,
+            format: None,
+            value,
+            segment_rules: Vec::new(),
+            collections: Vec::new(),
+        };
+        self.properties
+            .insert(property_id.to_string(), Arc::new(property));
+        self
+    }
+
+    /// Creates the mock client with the features and properties added.
+    pub fn build(self) -> MockAppConfigurationClient {
+        MockAppConfigurationClient {
+            client: AppConfigurationClient::new_offline(ConfigurationSnapshot {
+                features: self.features,
+                properties: self.properties,
+                segments: HashMap::new(),
+                last_updated: SystemTime::now(),
+                environment_id: String::new(),
+                environment_name: String::new(),
+                other_environments: Vec::new(),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::{ConfigurationAccessError, Error};
+    use crate::tests::TrivialEntity;
+
+    #[test]
+    fn test_mock_client() {
+        let client = MockAppConfigurationClient::builder()
+            .feature("f1", true)
+            .disabled_feature("f2", "off")
+            .property("p1", 42)
+            .build();
+
+        let mut feature_ids = client.get_feature_ids().unwrap();
+        feature_ids.sort();
+        assert_eq!(feature_ids, vec!["f1", "f2"]);
+        let feature = client.get_feature("f1").unwrap();
+        assert!(feature.get_value_as_bool(&TrivialEntity).unwrap());
+        let feature = client.get_feature("f2").unwrap();
+        assert_eq!(
+            feature.get_value(&TrivialEntity).unwrap(),
+            Value::from("off")
+        );
+
+        assert_eq!(client.get_property_ids().unwrap(), vec!["p1"]);
+        let property = client.get_property("p1").unwrap();
+        assert_eq!(property.get_value(&TrivialEntity).unwrap(), Value::from(42));
+
+        assert!(matches!(
+            client.get_feature("f3"),
+            Err(Error::ConfigurationAccessError(
+                ConfigurationAccessError::FeatureNotFound { .. }
+            ))
+        ));
+    }
+}
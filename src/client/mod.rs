@@ -13,15 +13,45 @@
 // limitations under the License.
 
 mod app_configuration_client;
+#[cfg(feature = "async")]
+mod async_client;
+mod builder;
 
 pub(crate) mod cache;
 pub mod feature;
 pub(crate) mod feature_proxy;
 pub(crate) mod http;
+#[cfg(feature = "async")]
+pub(crate) mod http_async;
+mod metrics;
+mod mock;
+mod multi_collection_client;
+mod offline;
 pub mod property;
 pub(crate) mod property_proxy;
+mod provider;
+pub mod segment;
+mod status;
+mod typed_feature;
+mod usage;
 pub mod value;
 
-pub use app_configuration_client::AppConfigurationClient;
+pub use app_configuration_client::{
+    AppConfigurationClient, UpdateMode, CLOSE_TIMEOUT, DEFAULT_MAX_RECONNECT_DELAY,
+    DEFAULT_PING_INTERVAL,
+};
+pub use builder::AppConfigurationClientBuilder;
+pub use cache::ConfigurationIssue;
+pub use feature_proxy::{rollout_bucket, rollout_bucket_with_seed, rollout_percentage_for};
+pub use http::{
+    DEFAULT_MAX_ATTEMPTS, DEFAULT_REQUEST_TIMEOUT, DEFAULT_RETRY_BASE_DELAY, USER_AGENT,
+};
+pub use metrics::MetricsRecorder;
+pub use mock::{MockAppConfigurationClient, MockAppConfigurationClientBuilder};
+pub use multi_collection_client::MultiCollectionClient;
+pub use offline::{evaluate_feature, evaluate_property};
+pub use provider::FeatureProvider;
+pub use status::{ClientStatus, ConfigurationUpdate, ConnectionState};
+pub use typed_feature::{BoolFeature, FeatureValue, NumericFeature, StringFeature, TypedFeature};
 
 pub const REGION_US_SOUTH: &str = "us-south";
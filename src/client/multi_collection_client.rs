@@ -0,0 +1,95 @@
+// (C) Copyright IBM Corp. 2024.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use crate::client::app_configuration_client::AppConfigurationClient;
+use crate::client::feature::Feature;
+use crate::client::property::Property;
+use crate::errors::{ConfigurationAccessError, Result};
+
+/// Client for several collections of the same App Configuration instance
+/// and environment. Use
+/// [`AppConfigurationClientBuilder::build_for_collections`](crate::client::AppConfigurationClientBuilder::build_for_collections)
+/// to create it.
+///
+/// Each collection has its own configuration, and can be browsed as a
+/// single collection client with [`MultiCollectionClient::collection`].
+#[derive(Debug, Clone)]
+pub struct MultiCollectionClient {
+    clients: HashMap<String, AppConfigurationClient>,
+}
+
+impl MultiCollectionClient {
+    pub(crate) fn new(clients: HashMap<String, AppConfigurationClient>) -> Self {
+        Self { clients }
+    }
+
+    /// Returns the ids of the collections of this client.
+    pub fn get_collection_ids(&self) -> Vec<String> {
+        self.clients.keys().cloned().collect()
+    }
+
+    /// Returns the client for the collection `collection_id`.
+    pub fn collection(&self, collection_id: &str) -> Result<&AppConfigurationClient> {
+        self.clients.get(collection_id).ok_or_else(|| {
+            ConfigurationAccessError::CollectionNotFound {
+                collection_id: collection_id.to_string(),
+            }
+            .into()
+        })
+    }
+
+    /// Like [`AppConfigurationClient::get_feature`], for the feature
+    /// `feature_id` of collection `collection_id`.
+    pub fn get_feature(&self, collection_id: &str, feature_id: &str) -> Result<Feature> {
+        self.collection(collection_id)?.get_feature(feature_id)
+    }
+
+    /// Like [`AppConfigurationClient::get_property`], for the property
+    /// `property_id` of collection `collection_id`.
+    pub fn get_property(&self, collection_id: &str, property_id: &str) -> Result<Property> {
+        self.collection(collection_id)?.get_property(property_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::cache::ConfigurationSnapshot;
+    use crate::errors::Error;
+    use crate::models::tests::example_configuration_enterprise;
+    use crate::models::Configuration;
+    use rstest::rstest;
+
+    #[rstest]
+    fn test_get_feature_by_collection(example_configuration_enterprise: Configuration) {
+        let snapshot =
+            ConfigurationSnapshot::new("dev", None, example_configuration_enterprise).unwrap();
+        let client = MultiCollectionClient::new(HashMap::from([(
+            "car-rentals".to_string(),
+            AppConfigurationClient::new_offline(snapshot),
+        )]));
+
+        assert_eq!(client.get_collection_ids(), vec!["car-rentals"]);
+        assert!(client.get_feature("car-rentals", "f1").is_ok());
+        assert!(client.get_property("car-rentals", "p1").is_ok());
+        assert!(matches!(
+            client.get_feature("other", "f1"),
+            Err(Error::ConfigurationAccessError(
+                ConfigurationAccessError::CollectionNotFound { ref collection_id }
+            )) if collection_id == "other"
+        ));
+    }
+}
@@ -0,0 +1,93 @@
+// (C) Copyright IBM Corp. 2024.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Evaluation of a configuration dump without a client: no threads, no
+//! network access.
+
+use crate::client::app_configuration_client::AppConfigurationClient;
+use crate::client::cache::{self, ConfigurationSnapshot};
+use crate::client::feature::Feature;
+use crate::client::property::Property;
+use crate::client::value::Value;
+use crate::entity::Entity;
+use crate::errors::Result;
+
+/// Evaluates the feature `feature_id` of the environment `environment_id`
+/// for `entity`, in `config_json`: a configuration as returned by the App
+/// Configuration `/config` endpoint. The configuration is parsed for every
+/// call, use [`AppConfigurationClient::from_configuration_json`] to evaluate
+/// it repeatedly.
+///
+/// ```
+/// # use appconfiguration_rust_sdk::client::evaluate_feature;
+/// # use appconfiguration_rust_sdk::client::value::Value;
+/// # use appconfiguration_rust_sdk::SimpleEntity;
+/// let json = r#"{
+///     "environments": [{
+///         "name": "Dev",
+///         "environment_id": "dev",
+///         "features": [{
+///             "name": "Discount",
+///             "feature_id": "discount",
+///             "type": "NUMERIC",
+///             "enabled_value": 10,
+///             "disabled_value": 0,
+///             "segment_rules": [],
+///             "enabled": true,
+///             "rollout_percentage": 100
+///         }],
+///         "properties": []
+///     }],
+///     "segments": []
+/// }"#;
+/// let entity = SimpleEntity::new("user1").build();
+/// let value = evaluate_feature(json, "dev", "discount", &entity)?;
+/// assert_eq!(value, Value::from(10));
+/// # Ok::<(), appconfiguration_rust_sdk::errors::Error>(())
+/// ```
+pub fn evaluate_feature(
+    config_json: &str,
+    environment_id: &str,
+    feature_id: &str,
+    entity: &impl Entity,
+) -> Result<Value> {
+    let snapshot = snapshot(config_json, environment_id)?;
+    let feature = snapshot.get_feature(feature_id)?;
+    let segments =
+        AppConfigurationClient::referenced_segments(&snapshot, feature_id, &feature.segment_rules)?;
+    Feature::new(feature.clone(), segments).get_value(entity)
+}
+
+/// Like [`evaluate_feature`], for the property `property_id`.
+pub fn evaluate_property(
+    config_json: &str,
+    environment_id: &str,
+    property_id: &str,
+    entity: &impl Entity,
+) -> Result<Value> {
+    let snapshot = snapshot(config_json, environment_id)?;
+    let property = snapshot.get_property(property_id)?;
+    let segments = AppConfigurationClient::referenced_segments(
+        &snapshot,
+        property_id,
+        &property.segment_rules,
+    )?;
+    Property::new(property.clone(), segments).get_value(entity)
+}
+
+/// The features and properties of all collections of the environment.
+fn snapshot(config_json: &str, environment_id: &str) -> Result<ConfigurationSnapshot> {
+    let configuration = cache::parse_configuration(config_json)?;
+    ConfigurationSnapshot::new(environment_id, None, configuration)
+}
@@ -12,71 +12,165 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::client::value::{NumericValue, Value};
+use crate::client::app_configuration_client::AppConfigurationClient;
+use crate::client::value::Value;
 use crate::entity::Entity;
 use std::collections::HashMap;
+use std::sync::Arc;
 
-use crate::errors::{Error, Result};
-use crate::segment_evaluation::find_applicable_segment_rule_for_entity;
+use crate::errors::{ConfigurationAccessError, Error, ResourceKind, Result};
+use crate::models::ValueKind;
+use crate::segment_evaluation::{find_applicable_segment_rule_for_entity, EvaluationOptions};
 
+/// A property as it was when retrieved with
+/// [`AppConfigurationClient::get_property`]: later configuration updates are
+/// not visible, until the property is retrieved again or
+/// [`Property::refresh`]ed. Use [`AppConfigurationClient::get_property_proxy`]
+/// to always evaluate against the latest configuration.
 #[derive(Debug)]
 pub struct Property {
-    property: crate::models::Property,
-    segments: HashMap<String, crate::models::Segment>,
+    property: Arc<crate::models::Property>,
+    segments: HashMap<String, Arc<crate::models::Segment>>,
+    evaluation_options: EvaluationOptions,
 }
 
 impl Property {
     pub(crate) fn new(
-        property: crate::models::Property,
-        segments: HashMap<String, crate::models::Segment>,
+        property: Arc<crate::models::Property>,
+        segments: HashMap<String, Arc<crate::models::Segment>>,
     ) -> Self {
-        Self { property, segments }
+        Self {
+            property,
+            segments,
+            evaluation_options: EvaluationOptions::default(),
+        }
+    }
+
+    pub(crate) fn with_evaluation_options(mut self, evaluation_options: EvaluationOptions) -> Self {
+        self.evaluation_options = evaluation_options;
+        self
+    }
+
+    /// Replaces this property by its current state in the configuration of
+    /// `client`, as if retrieved again with
+    /// [`AppConfigurationClient::get_property`]. Fails (leaving the property
+    /// unchanged) if it no longer exists.
+    pub fn refresh(&mut self, client: &AppConfigurationClient) -> Result<()> {
+        *self = client.get_property(&self.property.property_id)?;
+        Ok(())
+    }
+
+    /// Returns the tags of the property, as a comma separated list.
+    pub fn get_tags(&self) -> Option<String> {
+        self.property.tags.clone()
+    }
+
+    /// Returns the data type of the values of the property.
+    pub fn get_data_type(&self) -> crate::models::ValueKind {
+        self.property.kind
     }
 
     pub fn get_value(&self, entity: &impl Entity) -> Result<Value> {
         let model_value = self.evaluate_feature_for_entity(entity)?;
 
-        let value = match self.property.kind {
-            crate::models::ValueKind::Numeric => {
-                Value::Numeric(NumericValue(model_value.0.clone()))
-            }
-            crate::models::ValueKind::Boolean => {
-                Value::Boolean(model_value.0.as_bool().ok_or(Error::ProtocolError)?)
-            }
-            crate::models::ValueKind::String => Value::String(
-                model_value
-                    .0
-                    .as_str()
-                    .ok_or(Error::ProtocolError)?
-                    .to_string(),
-            ),
-        };
+        let value =
+            Value::from_config_value(&self.property.kind, model_value, &self.property.property_id)?;
         Ok(value)
     }
 
-    fn evaluate_feature_for_entity(
+    /// Fails with [`ConfigurationAccessError::PropertyTypeMismatch`] if the
+    /// property is not of type `expected`.
+    fn check_data_type(&self, expected: ValueKind) -> Result<()> {
+        if self.property.kind != expected {
+            return Err(ConfigurationAccessError::PropertyTypeMismatch {
+                property_id: self.property.property_id.clone(),
+                expected,
+                actual: self.property.kind,
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    fn value_type_mismatch(&self) -> Error {
+        Error::ValueTypeMismatch {
+            expected: self.property.kind,
+            resource_id: self.property.property_id.clone(),
+        }
+    }
+
+    /// Like [`Property::get_value`], for boolean properties. Fails with
+    /// [`ConfigurationAccessError::PropertyTypeMismatch`] if the property is
+    /// not of boolean type.
+    pub fn get_value_as_bool(&self, entity: &impl Entity) -> Result<bool> {
+        self.check_data_type(ValueKind::Boolean)?;
+        match self.get_value(entity)? {
+            Value::Boolean(value) => Ok(value),
+            _ => Err(self.value_type_mismatch()),
+        }
+    }
+
+    /// Like [`Property::get_value`], for string properties. Fails with
+    /// [`ConfigurationAccessError::PropertyTypeMismatch`] if the property is
+    /// not of string type.
+    pub fn get_value_as_string(&self, entity: &impl Entity) -> Result<String> {
+        self.check_data_type(ValueKind::String)?;
+        match self.get_value(entity)? {
+            Value::String(value) => Ok(value),
+            _ => Err(self.value_type_mismatch()),
+        }
+    }
+
+    /// Like [`Property::get_value`], for string properties of `JSON` or `YAML`
+    /// format: returns the value parsed as structured data. Fails with
+    /// [`ConfigurationAccessError::PropertyTypeMismatch`] if the property is of
+    /// another type, with [`Error::ValueFormatMismatch`] if it is of another
+    /// format (like `TEXT`), or with [`Error::DeserializationError`] if the
+    /// value cannot be parsed.
+    pub fn get_value_as_json(&self, entity: &impl Entity) -> Result<serde_json::Value> {
+        self.check_data_type(ValueKind::String)?;
+        let value = self.evaluate_feature_for_entity(entity)?;
+        value.parse_structured(&self.property.property_id, self.property.format.as_deref())
+    }
+
+    /// Like [`Property::get_value`], for numeric properties. Fails with
+    /// [`ConfigurationAccessError::PropertyTypeMismatch`] if the property is
+    /// not of numeric type, or with [`Error::NumericValueOutOfRange`] if its
+    /// value is not an `i64`.
+    pub fn get_value_as_i64(&self, entity: &impl Entity) -> Result<i64> {
+        self.check_data_type(ValueKind::Numeric)?;
+        match self.get_value(entity)? {
+            Value::Numeric(value) => value.as_i64().ok_or_else(|| Error::NumericValueOutOfRange {
+                target: "i64",
+                resource_id: self.property.property_id.clone(),
+            }),
+            _ => Err(self.value_type_mismatch()),
+        }
+    }
+
+    pub(crate) fn evaluate_feature_for_entity(
         &self,
         entity: &impl Entity,
     ) -> Result<crate::models::ConfigValue> {
-        if self.property.segment_rules.is_empty() || entity.get_attributes().is_empty() {
-            // TODO: this makes only sense if there can be a rule which matches
-            //       even on empty attributes
+        // Entities without attributes are still evaluated against the segment
+        // rules: a rule can match on missing attributes (like `notExists`).
+        if self.property.segment_rules.is_empty() {
             // No match possible. Do not consider segment rules:
             return Ok(self.property.value.clone());
         }
 
         match find_applicable_segment_rule_for_entity(
+            ResourceKind::Property,
+            &self.property.property_id,
             &self.segments,
-            self.property.segment_rules.clone().into_iter(),
+            &self.property.segment_rules,
             entity,
-        ) {
-            Some(segment_rule) => {
-                if segment_rule.value.is_default() {
-                    Ok(self.property.value.clone())
-                } else {
-                    Ok(segment_rule.value)
-                }
-            }
+            &self.evaluation_options,
+        )? {
+            Some(segment_rule) => Ok(segment_rule
+                .value_or_default()
+                .unwrap_or(&self.property.value)
+                .clone()),
             None => Ok(self.property.value.clone()),
         }
     }
@@ -89,6 +183,7 @@ pub mod tests {
         models::{ConfigValue, Segment, SegmentRule, Segments, TargetingRule, ValueKind},
         AttrValue,
     };
+    use rstest::rstest;
 
     #[test]
     fn test_get_value_segment_with_default_value() {
@@ -105,14 +200,16 @@ pub mod tests {
                 value: ConfigValue(serde_json::Value::String("$default".into())),
                 order: 1,
                 rollout_percentage: Some(ConfigValue(serde_json::Value::Number((100).into()))),
+                weighted_values: Vec::new(),
             }],
             tags: None,
+            collections: Vec::new(),
         };
         let property = Property::new(
-            inner_property,
+            inner_property.into(),
             HashMap::from([(
                 "some_segment_id_1".into(),
-                Segment {
+                Arc::new(Segment {
                     name: "".into(),
                     segment_id: "".into(),
                     description: "".into(),
@@ -122,7 +219,7 @@ pub mod tests {
                         operator: "is".into(),
                         values: vec!["heinz".into()],
                     }],
-                },
+                }),
             )]),
         );
 
@@ -135,6 +232,48 @@ pub mod tests {
         assert!(matches!(value, Value::Numeric(ref v) if v.as_i64().unwrap() == -42));
     }
 
+    #[test]
+    fn test_get_value_segment_matching_without_attributes() {
+        let inner_property = crate::models::Property {
+            name: "P1".to_string(),
+            property_id: "p1".to_string(),
+            kind: ValueKind::Numeric,
+            format: None,
+            value: ConfigValue(serde_json::Value::Number((-42).into())),
+            segment_rules: vec![TargetingRule {
+                rules: vec![Segments {
+                    segments: vec!["anonymous".into()],
+                }],
+                value: ConfigValue(serde_json::Value::Number((-48).into())),
+                order: 1,
+                rollout_percentage: Some(ConfigValue(serde_json::Value::Number((100).into()))),
+                weighted_values: Vec::new(),
+            }],
+            tags: None,
+            collections: Vec::new(),
+        };
+        let property = Property::new(
+            inner_property.into(),
+            HashMap::from([(
+                "anonymous".into(),
+                Arc::new(Segment {
+                    name: "".into(),
+                    segment_id: "".into(),
+                    description: "".into(),
+                    tags: None,
+                    rules: vec![SegmentRule {
+                        attribute_name: "email".into(),
+                        operator: "notExists".into(),
+                        values: vec![],
+                    }],
+                }),
+            )]),
+        );
+
+        let value = property.get_value(&crate::tests::TrivialEntity).unwrap();
+        assert!(matches!(value, Value::Numeric(ref v) if v.as_i64().unwrap() == -48));
+    }
+
     #[test]
     fn test_get_value_segment_rule_ordering() {
         let inner_property = crate::models::Property {
@@ -151,6 +290,7 @@ pub mod tests {
                     value: ConfigValue(serde_json::Value::Number((-48).into())),
                     order: 1,
                     rollout_percentage: Some(ConfigValue(serde_json::Value::Number((100).into()))),
+                    weighted_values: Vec::new(),
                 },
                 TargetingRule {
                     rules: vec![Segments {
@@ -159,16 +299,18 @@ pub mod tests {
                     value: ConfigValue(serde_json::Value::Number((-49).into())),
                     order: 0,
                     rollout_percentage: Some(ConfigValue(serde_json::Value::Number((100).into()))),
+                    weighted_values: Vec::new(),
                 },
             ],
             tags: None,
+            collections: Vec::new(),
         };
         let property = Property::new(
-            inner_property,
+            inner_property.into(),
             HashMap::from([
                 (
                     "some_segment_id_1".into(),
-                    Segment {
+                    Arc::new(Segment {
                         name: "".into(),
                         segment_id: "".into(),
                         description: "".into(),
@@ -178,11 +320,11 @@ pub mod tests {
                             operator: "is".into(),
                             values: vec!["heinz".into()],
                         }],
-                    },
+                    }),
                 ),
                 (
                     "some_segment_id_2".into(),
-                    Segment {
+                    Arc::new(Segment {
                         name: "".into(),
                         segment_id: "".into(),
                         description: "".into(),
@@ -192,7 +334,7 @@ pub mod tests {
                             operator: "is".into(),
                             values: vec!["heinz".into()],
                         }],
-                    },
+                    }),
                 ),
             ]),
         );
@@ -205,4 +347,47 @@ pub mod tests {
         let value = property.get_value(&entity).unwrap();
         assert!(matches!(value, Value::Numeric(ref v) if v.as_i64().unwrap() == -49));
     }
+
+    // String properties of JSON or YAML format can be retrieved as
+    // structured data. JSON can be given as a string or directly.
+    #[rstest]
+    #[case("JSON", serde_json::json!(r#"{"key": [1, "two"]}"#), Ok(serde_json::json!({"key": [1, "two"]})))]
+    #[case("JSON", serde_json::json!({"key": [1, "two"]}), Ok(serde_json::json!({"key": [1, "two"]})))]
+    #[case("JSON", serde_json::json!("{not json"), Err("Cannot deserialize string '{not json'"))]
+    #[case("TEXT", serde_json::json!(r#"{"key": [1, "two"]}"#), Err("Format of 'f1' is TEXT, not JSON or YAML"))]
+    #[case("YAML", serde_json::json!("key:\n  - 1\n  - two\n"), Ok(serde_json::json!({"key": [1, "two"]})))]
+    #[case("YAML", serde_json::json!("key: [1, two"), Err("Cannot deserialize string 'key: [1, two'"))]
+    fn test_get_value_as_json(
+        #[case] format: &str,
+        #[case] value: serde_json::Value,
+        #[case] expected: std::result::Result<serde_json::Value, &str>,
+    ) {
+        let inner_property = crate::models::Property {
+            name: "F1".to_string(),
+            property_id: "f1".to_string(),
+            kind: ValueKind::String,
+            tags: None,
+            format: Some(format.to_string()),
+            value: ConfigValue(value),
+            segment_rules: Vec::new(),
+            collections: Vec::new(),
+        };
+        let property = Property::new(inner_property.into(), HashMap::new());
+        let entity = crate::tests::TrivialEntity {};
+
+        assert_eq!(property.get_data_type(), ValueKind::String);
+
+        let result = property.get_value_as_json(&entity);
+        match expected {
+            Ok(expected) => assert_eq!(result.unwrap(), expected),
+            Err(message) => assert!(
+                result
+                    .as_ref()
+                    .unwrap_err()
+                    .to_string()
+                    .starts_with(message),
+                "{result:?}"
+            ),
+        }
+    }
 }
@@ -12,133 +12,125 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, RwLock};
 
 use crate::{
-    client::cache::ConfigurationSnapshot, models,
-    segment_evaluation::find_applicable_segment_rule_for_entity,
+    client::app_configuration_client::AppConfigurationClient,
+    client::cache::{self, ConfigurationSnapshot},
+    client::property::Property,
+    models,
+    segment_evaluation::{find_applicable_segment_rule_for_entity, EvaluationOptions},
 };
 
 use crate::entity::Entity;
 
-use crate::errors::ConfigurationAccessError;
-
-const MISSING_PROPERTY_ERROR_MSG: &str = "The property should exist in the index. It should have been validated in `AppConfigurationClient::get_property()`.";
+use crate::errors::{ResourceKind, Result};
 
 /// A property in a collection and environment. Use the `get_property()`
 /// method of the `AppConfigurationClient` to create instances of properties.
 #[derive(Debug)]
 pub struct PropertyProxy {
-    configuration_snapshot: Arc<Mutex<ConfigurationSnapshot>>,
+    configuration_snapshot: Arc<RwLock<ConfigurationSnapshot>>,
     property_id: String,
+    evaluation_options: EvaluationOptions,
 }
 
 impl PropertyProxy {
     pub(crate) fn new(
-        configuration_snapshot: Arc<Mutex<ConfigurationSnapshot>>,
+        configuration_snapshot: Arc<RwLock<ConfigurationSnapshot>>,
         property_id: String,
+        evaluation_options: EvaluationOptions,
     ) -> Self {
         PropertyProxy {
             configuration_snapshot,
             property_id,
+            evaluation_options,
         }
     }
 
+    /// Calls `f` with the property in the current configuration, failing
+    /// with `ConfigurationAccessError::PropertyNotFound` if it is not there
+    /// (anymore).
+    fn read<R>(&self, f: impl FnOnce(&models::Property) -> R) -> Result<R> {
+        Ok(f(
+            cache::read_snapshot(&self.configuration_snapshot).get_property(&self.property_id)?
+        ))
+    }
+
     /// Returns the name of the property.
-    pub fn get_name(&self) -> String {
-        self.configuration_snapshot
-            .lock()
-            .unwrap_or_else(|_| panic!("{}", ConfigurationAccessError::LockAcquisitionError))
-            .get_property(&self.property_id)
-            .expect(MISSING_PROPERTY_ERROR_MSG)
-            .name
-            .clone()
+    pub fn get_name(&self) -> Result<String> {
+        self.read(|property| property.name.clone())
     }
 
     /// Returns the value of the property as a `models::ConfigValue`.
-    pub fn get_value(&self) -> models::ConfigValue {
-        self.configuration_snapshot
-            .lock()
-            .unwrap_or_else(|_| panic!("{}", ConfigurationAccessError::LockAcquisitionError))
-            .get_property(&self.property_id)
-            .expect(MISSING_PROPERTY_ERROR_MSG)
-            .value
-            .clone()
+    pub fn get_value(&self) -> Result<models::ConfigValue> {
+        self.read(|property| property.value.clone())
     }
 
     /// Returns the id of the property.
-    pub fn get_id(&self) -> String {
-        self.configuration_snapshot
-            .lock()
-            .unwrap_or_else(|_| panic!("{}", ConfigurationAccessError::LockAcquisitionError))
-            .get_property(&self.property_id)
-            .expect(MISSING_PROPERTY_ERROR_MSG)
-            .property_id
-            .clone()
+    pub fn get_id(&self) -> Result<String> {
+        self.read(|property| property.property_id.clone())
     }
 
     /// Returns the data type as a member of the `models::ValueKind` enumeration.
-    pub fn get_data_type(&self) -> models::ValueKind {
-        self.configuration_snapshot
-            .lock()
-            .unwrap_or_else(|_| panic!("{}", ConfigurationAccessError::LockAcquisitionError))
-            .get_property(&self.property_id)
-            .expect(MISSING_PROPERTY_ERROR_MSG)
-            .kind
+    pub fn get_data_type(&self) -> Result<models::ValueKind> {
+        self.read(|property| property.kind)
     }
 
     /// Gets the `Some(data_format)` if the feature data type is
     /// `models::ValueKind::STRING`, or `None` otherwise.
-    pub fn get_data_format(&self) -> Option<String> {
-        self.configuration_snapshot
-            .lock()
-            .unwrap_or_else(|_| panic!("{}", ConfigurationAccessError::LockAcquisitionError))
-            .get_property(&self.property_id)
-            .expect(MISSING_PROPERTY_ERROR_MSG)
-            .format
-            .clone()
+    pub fn get_data_format(&self) -> Result<Option<String>> {
+        self.read(|property| property.format.clone())
     }
 
     /// Returns the targeting rules for the property. I.e.: what value to
     /// associate with an entity, and under what circumstances it applies.
-    pub fn get_targeting_rules(&self) -> Vec<models::TargetingRule> {
-        self.configuration_snapshot
-            .lock()
-            .unwrap_or_else(|_| panic!("{}", ConfigurationAccessError::LockAcquisitionError))
-            .get_property(&self.property_id)
-            .expect(MISSING_PROPERTY_ERROR_MSG)
-            .segment_rules
-            .clone()
+    pub fn get_targeting_rules(&self) -> Result<Vec<models::TargetingRule>> {
+        self.read(|property| property.segment_rules.clone())
     }
 
-    /// Evaluates the property for `entity` and returns the evaluation as a
-    /// `models::ConfigValue`.
-    pub fn get_current_value(&self, entity: &impl Entity) -> models::ConfigValue {
-        self.evaluate_feature_for_entity(entity)
+    /// Calls `f` with a [`Property`] taken from the current configuration,
+    /// acquiring the lock on the configuration only once for a batch of
+    /// reads, instead of once per method call of the proxy.
+    ///
+    /// The [`Property`] is a snapshot: configuration updates received while
+    /// `f` runs are not reflected in it.
+    pub fn with_snapshot<R>(&self, f: impl FnOnce(&Property) -> R) -> Result<R> {
+        let property = {
+            let configuration_snapshot = cache::read_snapshot(&self.configuration_snapshot);
+            let property = configuration_snapshot.get_property(&self.property_id)?;
+            let segments = AppConfigurationClient::referenced_segments(
+                &configuration_snapshot,
+                &self.property_id,
+                &property.segment_rules,
+            )?;
+            Property::new(property.clone(), segments)
+                .with_evaluation_options(self.evaluation_options)
+        };
+        Ok(f(&property))
     }
 
-    fn evaluate_feature_for_entity(&self, entity: &impl Entity) -> models::ConfigValue {
+    /// Evaluates the property for `entity` and returns the evaluation as a
+    /// `models::ConfigValue`.
+    pub fn get_current_value(&self, entity: &impl Entity) -> Result<models::ConfigValue> {
+        // Evaluate under a single lock, so the property and its segments are
+        // taken from the same configuration without cloning them.
+        let configuration_snapshot = cache::read_snapshot(&self.configuration_snapshot);
+        let property = configuration_snapshot.get_property(&self.property_id)?;
         let segment_rule = find_applicable_segment_rule_for_entity(
-            &self
-                .configuration_snapshot
-                .lock()
-                .unwrap_or_else(|e| panic!("Failed to acquire configuration snapshot lock: {e}"))
-                .segments,
-            self.get_targeting_rules().into_iter(),
+            ResourceKind::Property,
+            &property.property_id,
+            &configuration_snapshot.segments,
+            &property.segment_rules,
             entity,
-        );
-        if let Some(segment_rule) = segment_rule {
-            self.resolve_value(&segment_rule)
-        } else {
-            self.get_value()
-        }
-    }
-
-    fn resolve_value(&self, segment_rule: &models::TargetingRule) -> models::ConfigValue {
-        if segment_rule.value.is_default() {
-            self.get_value()
-        } else {
-            segment_rule.value.clone()
+            &self.evaluation_options,
+        )?;
+        match segment_rule {
+            Some(segment_rule) => Ok(segment_rule
+                .value_or_default()
+                .unwrap_or(&property.value)
+                .clone()),
+            None => Ok(property.value.clone()),
         }
     }
 }
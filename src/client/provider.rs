@@ -0,0 +1,57 @@
+// (C) Copyright IBM Corp. 2024.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::client::app_configuration_client::AppConfigurationClient;
+use crate::client::feature::Feature;
+use crate::client::property::Property;
+use crate::errors::Result;
+
+/// Source of the features and properties of a collection, implemented by
+/// [`AppConfigurationClient`] and by
+/// [`MockAppConfigurationClient`](crate::client::MockAppConfigurationClient).
+/// Code taking an `impl FeatureProvider` can be tested with fixed values,
+/// without network access.
+pub trait FeatureProvider {
+    /// Returns the ids of all features.
+    fn get_feature_ids(&self) -> Result<Vec<String>>;
+
+    /// Returns the feature `feature_id`, see
+    /// [`AppConfigurationClient::get_feature`].
+    fn get_feature(&self, feature_id: &str) -> Result<Feature>;
+
+    /// Returns the ids of all properties.
+    fn get_property_ids(&self) -> Result<Vec<String>>;
+
+    /// Returns the property `property_id`, see
+    /// [`AppConfigurationClient::get_property`].
+    fn get_property(&self, property_id: &str) -> Result<Property>;
+}
+
+impl FeatureProvider for AppConfigurationClient {
+    fn get_feature_ids(&self) -> Result<Vec<String>> {
+        AppConfigurationClient::get_feature_ids(self)
+    }
+
+    fn get_feature(&self, feature_id: &str) -> Result<Feature> {
+        AppConfigurationClient::get_feature(self, feature_id)
+    }
+
+    fn get_property_ids(&self) -> Result<Vec<String>> {
+        AppConfigurationClient::get_property_ids(self)
+    }
+
+    fn get_property(&self, property_id: &str) -> Result<Property> {
+        AppConfigurationClient::get_property(self, property_id)
+    }
+}
@@ -0,0 +1,73 @@
+// (C) Copyright IBM Corp. 2024.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+/// A segment of the configuration: the entities matching all of its rules.
+/// Use the `get_segment()` method of the `AppConfigurationClient` to get
+/// instances of segments.
+#[derive(Debug, Clone)]
+pub struct Segment {
+    segment: Arc<crate::models::Segment>,
+}
+
+impl Segment {
+    pub(crate) fn new(segment: Arc<crate::models::Segment>) -> Self {
+        Self { segment }
+    }
+
+    /// Returns the id of the segment.
+    pub fn get_id(&self) -> &str {
+        &self.segment.segment_id
+    }
+
+    /// Returns the name of the segment.
+    pub fn get_name(&self) -> &str {
+        &self.segment.name
+    }
+
+    /// Returns the description of the segment.
+    pub fn get_description(&self) -> &str {
+        &self.segment.description
+    }
+
+    /// Returns the tags of the segment, as a comma separated list.
+    pub fn get_tags(&self) -> Option<&str> {
+        self.segment.tags.as_deref()
+    }
+
+    /// Returns the rules an entity has to match to belong to the segment.
+    pub fn get_rules(&self) -> Vec<SegmentRule> {
+        self.segment
+            .rules
+            .iter()
+            .map(|rule| SegmentRule {
+                attribute_name: rule.attribute_name.clone(),
+                operator: rule.operator.clone(),
+                values: rule.values.clone(),
+            })
+            .collect()
+    }
+}
+
+/// A rule of a segment: the entity attribute `attribute_name` has to match
+/// one of `values` with `operator` (or, for `notEquals` and `notIn`, none
+/// of them). A value like `$attr:quota` stands for the value of the
+/// entity's `quota` attribute.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SegmentRule {
+    pub attribute_name: String,
+    pub operator: String,
+    pub values: Vec<String>,
+}
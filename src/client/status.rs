@@ -0,0 +1,191 @@
+// (C) Copyright IBM Corp. 2024.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt;
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::SystemTime;
+
+use crate::errors::Result;
+
+/// State of the connection on which the server notifies configuration
+/// changes to a client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// Configuration changes are received as they happen (or, with
+    /// [`UpdateMode::Poll`](crate::client::UpdateMode::Poll), at the next poll).
+    Connected,
+    /// The connection was lost (or the last poll failed) and the client is
+    /// trying to reconnect. Changes made meanwhile are fetched once
+    /// reconnected.
+    Disconnected,
+    /// Monitoring configuration changes could not be started: the
+    /// configuration will not be updated anymore.
+    Failed,
+    /// The client was created from a local configuration and never connects
+    /// to the server.
+    Offline,
+    /// The client was closed with
+    /// [`AppConfigurationClient::close`](crate::client::AppConfigurationClient::close):
+    /// the configuration will not be updated anymore.
+    Closed,
+}
+
+/// Reports whether the configuration of a client is up to date.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientStatus {
+    pub connection: ConnectionState,
+    /// When the current configuration was received from the server. `None`
+    /// if it was loaded from a file or the persistent cache instead.
+    pub last_update: Option<SystemTime>,
+}
+
+/// Passed to the callbacks registered with
+/// [`AppConfigurationClient::on_configuration_update`](crate::client::AppConfigurationClient::on_configuration_update)
+/// and sent to the receivers of
+/// [`AppConfigurationClient::updates`](crate::client::AppConfigurationClient::updates)
+/// whenever a new configuration is received.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigurationUpdate {
+    pub received_at: SystemTime,
+    /// Features available in the new configuration.
+    pub feature_ids: Vec<String>,
+    /// Properties available in the new configuration.
+    pub property_ids: Vec<String>,
+}
+
+type UpdateCallback = Arc<dyn Fn(&ConfigurationUpdate) + Send + Sync>;
+
+/// Callbacks and channels to notify about configuration updates, shared
+/// between a client and its background thread.
+#[derive(Clone, Default)]
+pub(crate) struct UpdateCallbacks {
+    callbacks: Arc<Mutex<Vec<UpdateCallback>>>,
+    subscribers: Arc<Mutex<Vec<mpsc::Sender<ConfigurationUpdate>>>>,
+}
+
+impl UpdateCallbacks {
+    pub fn register(
+        &self,
+        callback: impl Fn(&ConfigurationUpdate) + Send + Sync + 'static,
+    ) -> Result<()> {
+        self.callbacks.lock()?.push(Arc::new(callback));
+        Ok(())
+    }
+
+    /// Returns a receiver getting every further update. It is unsubscribed
+    /// once dropped.
+    pub fn subscribe(&self) -> Result<mpsc::Receiver<ConfigurationUpdate>> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.lock()?.push(sender);
+        Ok(receiver)
+    }
+
+    /// Calls every registered callback with `update`, and sends it to every
+    /// subscriber.
+    pub fn notify(&self, update: &ConfigurationUpdate) {
+        // Callbacks are called without holding the lock, so they can
+        // register further callbacks.
+        let callbacks = match self.callbacks.lock() {
+            Ok(callbacks) => callbacks.clone(),
+            Err(_) => return,
+        };
+        for callback in callbacks {
+            callback(update);
+        }
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers.retain(|subscriber| subscriber.send(update.clone()).is_ok());
+        }
+    }
+}
+
+impl fmt::Debug for UpdateCallbacks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let callbacks = self.callbacks.lock().map(|callbacks| callbacks.len()).ok();
+        let subscribers = self
+            .subscribers
+            .lock()
+            .map(|subscribers| subscribers.len())
+            .ok();
+        f.debug_struct("UpdateCallbacks")
+            .field("callbacks", &callbacks)
+            .field("subscribers", &subscribers)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_notify_all_callbacks() {
+        let callbacks = UpdateCallbacks::default();
+        let calls = Arc::new(AtomicUsize::new(0));
+        for _ in 0..2 {
+            let calls = calls.clone();
+            callbacks
+                .register(move |update| {
+                    assert_eq!(update.feature_ids, vec!["f1".to_string()]);
+                    calls.fetch_add(1, Ordering::SeqCst);
+                })
+                .unwrap();
+        }
+
+        callbacks.notify(&ConfigurationUpdate {
+            received_at: SystemTime::now(),
+            feature_ids: vec!["f1".to_string()],
+            property_ids: vec![],
+        });
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_register_from_callback() {
+        let callbacks = UpdateCallbacks::default();
+        let inner = callbacks.clone();
+        callbacks
+            .register(move |_| inner.register(|_| {}).unwrap())
+            .unwrap();
+
+        callbacks.notify(&ConfigurationUpdate {
+            received_at: SystemTime::now(),
+            feature_ids: vec![],
+            property_ids: vec![],
+        });
+        assert_eq!(callbacks.callbacks.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_notify_subscribers() {
+        let callbacks = UpdateCallbacks::default();
+        let first = callbacks.subscribe().unwrap();
+        let second = callbacks.subscribe().unwrap();
+        let update = ConfigurationUpdate {
+            received_at: SystemTime::now(),
+            feature_ids: vec!["f1".to_string()],
+            property_ids: vec![],
+        };
+
+        callbacks.notify(&update);
+        assert_eq!(first.try_recv().unwrap(), update);
+        assert_eq!(second.try_recv().unwrap(), update);
+
+        // Dropped receivers are unsubscribed
+        drop(second);
+        callbacks.notify(&update);
+        assert_eq!(first.try_recv().unwrap(), update);
+        assert_eq!(callbacks.subscribers.lock().unwrap().len(), 1);
+    }
+}
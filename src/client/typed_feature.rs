@@ -0,0 +1,120 @@
+// (C) Copyright IBM Corp. 2024.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::marker::PhantomData;
+
+use crate::client::feature::Feature;
+use crate::client::value::{NumericValue, Value};
+use crate::entity::Entity;
+use crate::errors::{ConfigurationAccessError, Error, Result};
+use crate::models::ValueKind;
+
+mod private {
+    pub trait Sealed {}
+    impl Sealed for bool {}
+    impl Sealed for String {}
+    impl Sealed for super::NumericValue {}
+}
+
+/// Rust type of the values of features of a [`ValueKind`], see
+/// [`TypedFeature`].
+pub trait FeatureValue: private::Sealed + Sized {
+    /// The type of features with values of this type.
+    const KIND: ValueKind;
+
+    #[doc(hidden)]
+    fn from_value(value: Value) -> Option<Self>;
+}
+
+impl FeatureValue for bool {
+    const KIND: ValueKind = ValueKind::Boolean;
+
+    fn from_value(value: Value) -> Option<Self> {
+        match value {
+            Value::Boolean(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl FeatureValue for String {
+    const KIND: ValueKind = ValueKind::String;
+
+    fn from_value(value: Value) -> Option<Self> {
+        match value {
+            Value::String(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl FeatureValue for NumericValue {
+    const KIND: ValueKind = ValueKind::Numeric;
+
+    fn from_value(value: Value) -> Option<Self> {
+        match value {
+            Value::Numeric(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+/// A [`Feature`] known to have values of type `T`, retrieved with
+/// [`AppConfigurationClient::get_bool_feature`](crate::client::AppConfigurationClient::get_bool_feature)
+/// and its numeric and string counterparts. The type is checked once when
+/// retrieving the feature, so evaluating it returns a `T` directly.
+#[derive(Debug)]
+pub struct TypedFeature<T> {
+    feature: Feature,
+    value_type: PhantomData<fn() -> T>,
+}
+
+/// A feature with boolean values.
+pub type BoolFeature = TypedFeature<bool>;
+
+/// A feature with numeric values.
+pub type NumericFeature = TypedFeature<NumericValue>;
+
+/// A feature with string values.
+pub type StringFeature = TypedFeature<String>;
+
+impl<T: FeatureValue> TypedFeature<T> {
+    /// Fails with [`ConfigurationAccessError::FeatureTypeMismatch`] if
+    /// `feature`, with id `feature_id`, does not have values of type `T`.
+    pub(crate) fn new(feature_id: &str, feature: Feature) -> Result<Self> {
+        if feature.get_data_type() != T::KIND {
+            return Err(ConfigurationAccessError::FeatureTypeMismatch {
+                feature_id: feature_id.to_string(),
+                expected: T::KIND,
+                actual: feature.get_data_type(),
+            }
+            .into());
+        }
+        Ok(Self {
+            feature,
+            value_type: PhantomData,
+        })
+    }
+
+    /// Like [`Feature::get_value`], returning the value as a `T`.
+    pub fn get_value(&self, entity: &impl Entity) -> Result<T> {
+        // The value is of the type of the feature, see Value::from_config_value
+        T::from_value(self.feature.get_value(entity)?).ok_or(Error::ProtocolError)
+    }
+
+    /// Returns the untyped feature.
+    pub fn feature(&self) -> &Feature {
+        &self.feature
+    }
+}
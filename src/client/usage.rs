@@ -0,0 +1,258 @@
+// (C) Copyright IBM Corp. 2024.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+use crate::client::app_configuration_client::ConfigurationId;
+use crate::client::http;
+use crate::entity::Entity;
+
+/// How often the usage collected by a client is sent to the server.
+pub const USAGE_REPORT_INTERVAL: Duration = Duration::from_secs(600);
+
+/// An evaluation of a feature for an entity.
+struct Evaluation {
+    feature_id: String,
+    entity_id: String,
+    evaluated_at: SystemTime,
+}
+
+/// What is handed over to the reporting thread.
+enum Event {
+    Evaluation(Evaluation),
+    /// The client switched to another environment: evaluations received so
+    /// far are part of the previous one.
+    EnvironmentChange(String),
+}
+
+/// Collects feature evaluations to be reported to the server. Evaluations
+/// are handed over to a background thread, so recording never blocks.
+///
+/// Does nothing unless created with [`start_usage_reporting`].
+#[derive(Debug, Clone, Default)]
+pub(crate) struct UsageRecorder(Option<mpsc::Sender<Event>>);
+
+impl UsageRecorder {
+    pub fn record(&self, feature_id: &str, entity: &impl Entity) {
+        if let Some(sender) = &self.0 {
+            // If the reporting thread is gone, the evaluation is not recorded
+            let _ = sender.send(Event::Evaluation(Evaluation {
+                feature_id: feature_id.to_string(),
+                entity_id: entity.get_id(),
+                evaluated_at: SystemTime::now(),
+            }));
+        }
+    }
+
+    /// Reports evaluations recorded from now on as part of the environment
+    /// `environment_id`.
+    pub fn set_environment(&self, environment_id: &str) {
+        if let Some(sender) = &self.0 {
+            let _ = sender.send(Event::EnvironmentChange(environment_id.to_string()));
+        }
+    }
+}
+
+/// Body of a request to the usage endpoint.
+#[derive(Debug, Serialize, PartialEq)]
+struct UsageReport {
+    collection_id: String,
+    environment_id: String,
+    usages: Vec<FeatureUsage>,
+}
+
+/// How often a feature was evaluated for an entity since the last report.
+#[derive(Debug, Serialize, PartialEq)]
+struct FeatureUsage {
+    feature_id: String,
+    entity_id: String,
+    /// Time of the latest evaluation
+    evaluation_time: String,
+    count: u64,
+}
+
+/// Evaluations counted per feature and entity, with the latest evaluation time.
+type Usages = HashMap<(String, String), (SystemTime, u64)>;
+
+/// Starts a background thread sending the evaluations recorded with the
+/// returned recorder to the server every `interval`. The thread sends the
+/// remaining evaluations and stops once all clones of the recorder are
+/// dropped.
+pub(crate) fn start_usage_reporting(
+    credentials: &http::Credentials,
+    mut configuration_id: ConfigurationId,
+    mut http_options: http::HttpOptions,
+    interval: Duration,
+) -> UsageRecorder {
+    let (sender, receiver) = mpsc::channel::<Event>();
+    let credentials = credentials.clone();
+    // The thread has its own HTTP client, so that it is built and dropped
+    // there only, also for async clients
+    http_options.reset_client();
+
+    thread::spawn(move || {
+        let mut usages = Usages::new();
+        let mut next_report = Instant::now() + interval;
+        loop {
+            match receiver.recv_timeout(next_report.saturating_duration_since(Instant::now())) {
+                Ok(Event::Evaluation(evaluation)) => {
+                    let usage = usages
+                        .entry((evaluation.feature_id, evaluation.entity_id))
+                        .or_insert((evaluation.evaluated_at, 0));
+                    usage.0 = evaluation.evaluated_at;
+                    usage.1 += 1;
+                }
+                Ok(Event::EnvironmentChange(environment_id)) => {
+                    send_usage_report(&credentials, &configuration_id, &http_options, &mut usages);
+                    configuration_id.environment_id = environment_id;
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    send_usage_report(&credentials, &configuration_id, &http_options, &mut usages);
+                    next_report = Instant::now() + interval;
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    send_usage_report(&credentials, &configuration_id, &http_options, &mut usages);
+                    break;
+                }
+            }
+        }
+    });
+
+    UsageRecorder(Some(sender))
+}
+
+/// Sends and clears `usages`. Usages which cannot be sent are dropped.
+fn send_usage_report(
+    credentials: &http::Credentials,
+    configuration_id: &ConfigurationId,
+    http_options: &http::HttpOptions,
+    usages: &mut Usages,
+) {
+    if usages.is_empty() {
+        return;
+    }
+    let report = usage_report(configuration_id, std::mem::take(usages));
+    let result = http::get_access_token(credentials, http_options).and_then(|access_token| {
+        http::post_usage(
+            &access_token.token,
+            &configuration_id.region,
+            &configuration_id.guid,
+            &report,
+            http_options,
+        )
+    });
+    if let Err(e) = result {
+        log::warn!(
+            "Cannot send usage of {} features ({e}). Dropping it.",
+            report.usages.len()
+        );
+    }
+}
+
+fn usage_report(configuration_id: &ConfigurationId, usages: Usages) -> UsageReport {
+    let mut usages = usages
+        .into_iter()
+        .map(
+            |((feature_id, entity_id), (evaluated_at, count))| FeatureUsage {
+                feature_id,
+                entity_id,
+                evaluation_time: format_timestamp(evaluated_at),
+                count,
+            },
+        )
+        .collect::<Vec<_>>();
+    usages.sort_by(|a, b| (&a.feature_id, &a.entity_id).cmp(&(&b.feature_id, &b.entity_id)));
+    UsageReport {
+        collection_id: configuration_id.collection_id.clone(),
+        environment_id: configuration_id.environment_id.clone(),
+        usages,
+    }
+}
+
+/// Formats `time` as an RFC 3339 UTC timestamp, like `2024-09-30T12:34:56Z`.
+fn format_timestamp(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let (days, seconds_of_day) = (seconds / 86400, seconds % 86400);
+
+    // Civil date from days since 1970-01-01, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z / 146097;
+    let day_of_era = z % 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        seconds_of_day / 3600,
+        seconds_of_day / 60 % 60,
+        seconds_of_day % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(0, "1970-01-01T00:00:00Z")]
+    #[case(951782400, "2000-02-29T00:00:00Z")]
+    #[case(1727699696, "2024-09-30T12:34:56Z")]
+    fn test_format_timestamp(#[case] seconds: u64, #[case] expected: &str) {
+        let time = UNIX_EPOCH + Duration::from_secs(seconds);
+        assert_eq!(format_timestamp(time), expected);
+    }
+
+    #[test]
+    fn test_usage_report() {
+        let configuration_id = ConfigurationId {
+            region: "region".to_string(),
+            guid: "guid".to_string(),
+            environment_id: "dev".to_string(),
+            collection_id: "car-rentals".to_string(),
+        };
+        let evaluated_at = UNIX_EPOCH + Duration::from_secs(1727699696);
+        let usages = Usages::from([
+            (("f2".to_string(), "a1".to_string()), (evaluated_at, 1)),
+            (("f1".to_string(), "a1".to_string()), (evaluated_at, 3)),
+        ]);
+
+        let report = usage_report(&configuration_id, usages);
+        assert_eq!(
+            serde_json::to_value(&report).unwrap(),
+            serde_json::json!({
+                "collection_id": "car-rentals",
+                "environment_id": "dev",
+                "usages": [
+                    {"feature_id": "f1", "entity_id": "a1", "evaluation_time": "2024-09-30T12:34:56Z", "count": 3},
+                    {"feature_id": "f2", "entity_id": "a1", "evaluation_time": "2024-09-30T12:34:56Z", "count": 1},
+                ]
+            })
+        );
+    }
+}
@@ -12,7 +12,15 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-#[derive(PartialEq, Debug)]
+use std::cmp::Ordering;
+
+use serde::Serialize;
+
+use crate::errors::{Error, Result};
+use crate::models::{ConfigValue, ValueKind};
+
+/// A number, serialized as a plain JSON number.
+#[derive(Debug, Clone, Serialize)]
 pub struct NumericValue(pub(crate) serde_json::Value);
 
 impl NumericValue {
@@ -25,15 +33,168 @@ impl NumericValue {
     pub fn as_f64(&self) -> Option<f64> {
         self.0.as_f64()
     }
+
+    /// Compares two numbers. Integers are compared without going through
+    /// `f64`, so large integer values keep their precision. Only mixed
+    /// integer/float comparisons fall back to `f64`.
+    ///
+    /// Returns `None` if either value is not a number (like a NaN).
+    pub fn compare(&self, other: &NumericValue) -> Option<Ordering> {
+        if let (Some(a), Some(b)) = (self.as_i64(), other.as_i64()) {
+            return Some(a.cmp(&b));
+        }
+        if let (Some(a), Some(b)) = (self.as_u64(), other.as_u64()) {
+            return Some(a.cmp(&b));
+        }
+        // A negative integer against an integer that doesn't fit in i64
+        if self.0.is_i64() && other.0.is_u64() {
+            return Some(Ordering::Less);
+        }
+        if self.0.is_u64() && other.0.is_i64() {
+            return Some(Ordering::Greater);
+        }
+        self.as_f64()?.partial_cmp(&other.as_f64()?)
+    }
+}
+
+impl From<i64> for NumericValue {
+    fn from(value: i64) -> Self {
+        NumericValue(serde_json::Value::from(value))
+    }
+}
+
+impl From<u64> for NumericValue {
+    fn from(value: u64) -> Self {
+        NumericValue(serde_json::Value::from(value))
+    }
+}
+
+impl From<f64> for NumericValue {
+    fn from(value: f64) -> Self {
+        NumericValue(serde_json::Value::from(value))
+    }
+}
+
+/// Lossless conversions from the remaining primitive numeric types.
+macro_rules! impl_from_for_numeric_value {
+    ($($source:ty => $target:ty),*) => {
+        $(
+            impl From<$source> for NumericValue {
+                fn from(value: $source) -> Self {
+                    NumericValue::from(<$target>::from(value))
+                }
+            }
+        )*
+    };
+}
+
+impl_from_for_numeric_value!(i32 => i64, u32 => u64, f32 => f64);
+
+impl From<usize> for NumericValue {
+    fn from(value: usize) -> Self {
+        // usize is at most 64 bits wide on all supported platforms
+        NumericValue::from(value as u64)
+    }
+}
+
+impl PartialOrd for NumericValue {
+    /// See [`NumericValue::compare`].
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.compare(other)
+    }
+}
+
+impl PartialEq for NumericValue {
+    fn eq(&self, other: &Self) -> bool {
+        match self.partial_cmp(other) {
+            Some(ordering) => ordering == Ordering::Equal,
+            None => self.0 == other.0,
+        }
+    }
 }
 
-#[derive(PartialEq, Debug)]
+/// The value of a feature or property. Serializes to the natural JSON
+/// representation: a number, string or boolean.
+#[derive(PartialEq, Debug, Serialize)]
+#[serde(untagged)]
 pub enum Value {
     Numeric(NumericValue),
     String(String),
     Boolean(bool),
 }
 
+impl<T: Into<NumericValue>> From<T> for Value {
+    fn from(value: T) -> Self {
+        Value::Numeric(value.into())
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::String(value)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::String(value.to_string())
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value::Boolean(value)
+    }
+}
+
+/// Strings, booleans and numbers convert to the corresponding values, other
+/// JSON values fail with [`Error::Other`].
+impl TryFrom<serde_json::Value> for Value {
+    type Error = Error;
+
+    fn try_from(value: serde_json::Value) -> Result<Self> {
+        match value {
+            serde_json::Value::Number(_) => Ok(Value::Numeric(NumericValue(value))),
+            serde_json::Value::String(value) => Ok(Value::String(value)),
+            serde_json::Value::Bool(value) => Ok(Value::Boolean(value)),
+            _ => Err(Error::Other(format!(
+                "Cannot convert JSON value '{value}' to a value"
+            ))),
+        }
+    }
+}
+
+impl Value {
+    /// The inverse of [`Value::from_config_value`].
+    pub(crate) fn into_config_value(self) -> (ValueKind, ConfigValue) {
+        match self {
+            Value::Numeric(value) => (ValueKind::Numeric, ConfigValue(value.0)),
+            Value::String(value) => (ValueKind::String, ConfigValue(value.into())),
+            Value::Boolean(value) => (ValueKind::Boolean, ConfigValue(value.into())),
+        }
+    }
+
+    /// Converts `value` of the feature or property `resource_id`, as received
+    /// from the server, into a value of type `kind`. Fails with
+    /// [`Error::ValueTypeMismatch`] if it is not of that type.
+    pub(crate) fn from_config_value(
+        kind: &ValueKind,
+        value: ConfigValue,
+        resource_id: &str,
+    ) -> Result<Self> {
+        let mismatch = || Error::ValueTypeMismatch {
+            expected: *kind,
+            resource_id: resource_id.to_string(),
+        };
+        Ok(match kind {
+            ValueKind::Numeric if value.0.is_number() => Value::Numeric(NumericValue(value.0)),
+            ValueKind::Numeric => return Err(mismatch()),
+            ValueKind::Boolean => Value::Boolean(value.0.as_bool().ok_or_else(mismatch)?),
+            ValueKind::String => Value::String(value.0.as_str().ok_or_else(mismatch)?.to_string()),
+        })
+    }
+}
+
 #[cfg(test)]
 pub mod tests {
 
@@ -58,4 +219,137 @@ pub mod tests {
         assert!(matches!(value, Value::Numeric(ref v) if v.as_i64().unwrap() == -42i64));
         assert!(matches!(value, Value::Numeric(ref v) if v.as_u64().is_none()));
     }
+
+    #[test]
+    fn test_from_primitive_types() {
+        assert_eq!(
+            Value::from(-42i32),
+            Value::Numeric(NumericValue::from(-42i64))
+        );
+        assert_eq!(
+            Value::from(42u32),
+            Value::Numeric(NumericValue::from(42u64))
+        );
+        assert_eq!(
+            Value::from(42usize),
+            Value::Numeric(NumericValue::from(42u64))
+        );
+        assert_eq!(
+            Value::from(0.5f32),
+            Value::Numeric(NumericValue::from(0.5f64))
+        );
+        assert_eq!(Value::from("heinz"), Value::String("heinz".to_string()));
+        assert_eq!(Value::from(true), Value::Boolean(true));
+    }
+
+    #[test]
+    fn test_serialize() {
+        assert_eq!(serde_json::to_value(Value::from(42)).unwrap(), 42);
+        assert_eq!(serde_json::to_value(Value::from(-0.5)).unwrap(), -0.5);
+        assert_eq!(serde_json::to_value(Value::from("heinz")).unwrap(), "heinz");
+        assert_eq!(
+            serde_json::to_value(Value::from(true)).unwrap(),
+            serde_json::json!(true)
+        );
+        assert_eq!(
+            serde_json::to_string(&Value::from(u64::MAX)).unwrap(),
+            u64::MAX.to_string()
+        );
+    }
+
+    #[test]
+    fn test_numeric_ordering() {
+        assert!(NumericValue::from(42i64) > NumericValue::from(-42i64));
+        assert!(NumericValue::from(42u64) < NumericValue::from(42.5f64));
+        assert_eq!(NumericValue::from(42u64), NumericValue::from(42.0f64));
+        assert!(NumericValue::from(-1i64) < NumericValue::from(u64::MAX));
+        assert!(NumericValue::from(u64::MAX) > NumericValue::from(i64::MIN));
+
+        // 9007199254740993 (2^53 + 1) rounds to 9007199254740992 as f64
+        let large = NumericValue::from(9007199254740993i64);
+        let rounded = NumericValue::from(9007199254740992i64);
+        assert_eq!(9007199254740993i64 as f64, 9007199254740992i64 as f64);
+        assert!(large > rounded);
+        assert_ne!(large, rounded);
+    }
+
+    #[test]
+    fn test_numeric_compare_large_integers() {
+        let compare = |a: NumericValue, b: NumericValue| a.compare(&b);
+
+        // Both round to 2^64 as f64
+        assert_eq!(
+            compare(
+                NumericValue::from(u64::MAX),
+                NumericValue::from(u64::MAX - 1)
+            ),
+            Some(Ordering::Greater)
+        );
+        // Just beyond i64, against the largest i64
+        assert_eq!(
+            compare(
+                NumericValue::from(i64::MAX),
+                NumericValue::from(i64::MAX as u64 + 1)
+            ),
+            Some(Ordering::Less)
+        );
+        assert_eq!(
+            compare(
+                NumericValue::from(i64::MIN),
+                NumericValue::from(i64::MIN + 1)
+            ),
+            Some(Ordering::Less)
+        );
+        assert_eq!(
+            compare(NumericValue::from(i64::MIN), NumericValue::from(u64::MAX)),
+            Some(Ordering::Less)
+        );
+        assert_eq!(
+            compare(NumericValue::from(u64::MAX), NumericValue::from(u64::MAX)),
+            Some(Ordering::Equal)
+        );
+        // NaN is not a JSON number
+        assert_eq!(
+            compare(NumericValue::from(f64::NAN), NumericValue::from(0)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_try_from_json() {
+        let value = |json| Value::try_from(json).unwrap();
+        assert_eq!(value(serde_json::json!(42)), Value::from(42));
+        assert_eq!(value(serde_json::json!(u64::MAX)), Value::from(u64::MAX));
+        assert_eq!(value(serde_json::json!("heinz")), Value::from("heinz"));
+        assert_eq!(value(serde_json::json!(true)), Value::from(true));
+        for json in [
+            serde_json::json!(null),
+            serde_json::json!([1]),
+            serde_json::json!({}),
+        ] {
+            assert!(matches!(Value::try_from(json), Err(Error::Other(_))));
+        }
+    }
+
+    #[test]
+    fn test_from_config_value_type_mismatch() {
+        let value = ConfigValue(serde_json::json!("true"));
+        let result = Value::from_config_value(&ValueKind::Boolean, value.clone(), "f1");
+        assert!(matches!(
+            result,
+            Err(Error::ValueTypeMismatch { expected: ValueKind::Boolean, ref resource_id })
+                if resource_id == "f1"
+        ));
+        let result = Value::from_config_value(&ValueKind::Numeric, value.clone(), "f1");
+        assert!(matches!(
+            result,
+            Err(Error::ValueTypeMismatch {
+                expected: ValueKind::Numeric,
+                ..
+            })
+        ));
+
+        let value = Value::from_config_value(&ValueKind::String, value, "f1").unwrap();
+        assert_eq!(value, Value::from("true"));
+    }
 }
@@ -14,6 +14,9 @@
 
 use std::collections::HashMap;
 
+use crate::client::value::NumericValue;
+use crate::errors::{Error, Result};
+
 /// An object on which evaluate properties and features.
 pub trait Entity {
     /// Gets a unique identifier for the entity.
@@ -24,20 +27,139 @@ pub trait Entity {
     fn get_attributes(&self) -> HashMap<String, AttrValue> {
         HashMap::new()
     }
+
+    /// Gets the attribute `name`, if the entity has it. Segment rules look
+    /// attributes up one by one with this method.
+    ///
+    /// The default takes it from [`Entity::get_attributes`], building the
+    /// whole map for every rule evaluated: its cost grows with the number of
+    /// attributes. Entities should implement it without building the map.
+    fn get_attribute(&self, name: &str) -> Option<AttrValue> {
+        self.get_attributes().remove(name)
+    }
+}
+
+/// An entity given by its id and attributes, for evaluations where no
+/// type implementing [`Entity`] is at hand:
+///
+/// ```
+/// # use appconfiguration_rust_sdk::{Entity, SimpleEntity};
+/// let entity = SimpleEntity::new("user123")
+///     .attr("city", "Bangalore")
+///     .attr("radius", 60u64)
+///     .build();
+/// assert_eq!(entity.get_id(), "user123");
+/// assert_eq!(entity.get_attributes().len(), 2);
+/// ```
+#[derive(Debug, Clone)]
+pub struct SimpleEntity {
+    id: String,
+    attributes: HashMap<String, AttrValue>,
+}
+
+impl SimpleEntity {
+    /// Returns a builder for an entity with id `id`.
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(id: &str) -> EntityBuilder {
+        EntityBuilder {
+            id: id.to_string(),
+            attributes: HashMap::new(),
+        }
+    }
+
+    /// Same as [`SimpleEntity::new`].
+    pub fn builder(id: &str) -> EntityBuilder {
+        Self::new(id)
+    }
+}
+
+impl SimpleEntity {
+    /// Creates an entity with id `id` and the attributes given as a JSON
+    /// object, e.g. taken from the body of a request. Fails if any attribute
+    /// cannot be converted, see [`AttrValue::try_from`].
+    ///
+    /// ```
+    /// # use appconfiguration_rust_sdk::{Entity, SimpleEntity};
+    /// let attributes = serde_json::json!({"city": "Bangalore", "radius": 60});
+    /// let serde_json::Value::Object(attributes) = attributes else {
+    ///     unreachable!()
+    /// };
+    /// let entity = SimpleEntity::from_json("user123", attributes)?;
+    /// assert_eq!(entity.get_attributes().len(), 2);
+    /// # Ok::<(), appconfiguration_rust_sdk::errors::Error>(())
+    /// ```
+    pub fn from_json(
+        id: &str,
+        attributes: serde_json::Map<String, serde_json::Value>,
+    ) -> Result<Self> {
+        Ok(SimpleEntity {
+            id: id.to_string(),
+            attributes: attributes_from_json(attributes)?,
+        })
+    }
 }
 
-/// An attribute value can be of one of three types: numerics, strings, or
-/// booleans.
+impl Entity for SimpleEntity {
+    fn get_id(&self) -> String {
+        self.id.clone()
+    }
+
+    fn get_attributes(&self) -> HashMap<String, AttrValue> {
+        self.attributes.clone()
+    }
+
+    fn get_attribute(&self, name: &str) -> Option<AttrValue> {
+        self.attributes.get(name).cloned()
+    }
+}
+
+/// Builder to create a [`SimpleEntity`].
+#[derive(Debug, Clone)]
+pub struct EntityBuilder {
+    id: String,
+    attributes: HashMap<String, AttrValue>,
+}
+
+impl EntityBuilder {
+    /// Sets the attribute `name` to `value`, replacing any previous value.
+    pub fn attr(mut self, name: &str, value: impl Into<AttrValue>) -> Self {
+        self.attributes.insert(name.to_string(), value.into());
+        self
+    }
+
+    pub fn build(self) -> SimpleEntity {
+        SimpleEntity {
+            id: self.id,
+            attributes: self.attributes,
+        }
+    }
+}
+
+/// An attribute value can be of one of five types: numerics, strings,
+/// booleans, lists of strings, or maps of further attributes. Integer
+/// numerics keep their full precision when compared.
 #[derive(Debug, Clone)]
 pub enum AttrValue {
-    Numeric(f64),
+    Numeric(NumericValue),
     String(String),
     Boolean(bool),
+    /// A multi-valued attribute, like the roles of a user. In segment rules:
+    /// - `contains` (`containsCaseInsensitive`) matches if one of the
+    ///   elements equals the reference value (ignoring case).
+    /// - `is` (`isCaseInsensitive`) matches if the list consists of exactly
+    ///   the reference value, `notEquals` if it doesn't.
+    /// - Other string operators match if they match any element.
+    /// - Numeric operators fail, as for strings.
+    StringList(Vec<String>),
+    /// A structured attribute, like an address. Segment rules refer to its
+    /// elements with dotted names, like `address.city`; operators fail on
+    /// the map itself.
+    Map(HashMap<String, AttrValue>),
 }
 
-impl From<f64> for AttrValue {
-    fn from(value: f64) -> Self {
-        AttrValue::Numeric(value)
+impl<T: Into<NumericValue>> From<T> for AttrValue {
+    fn from(value: T) -> Self {
+        AttrValue::Numeric(value.into())
     }
 }
 
@@ -47,8 +169,126 @@ impl From<String> for AttrValue {
     }
 }
 
+impl From<&str> for AttrValue {
+    fn from(value: &str) -> Self {
+        AttrValue::String(value.to_string())
+    }
+}
+
+impl From<Vec<String>> for AttrValue {
+    fn from(value: Vec<String>) -> Self {
+        AttrValue::StringList(value)
+    }
+}
+
+impl From<Vec<&str>> for AttrValue {
+    fn from(value: Vec<&str>) -> Self {
+        AttrValue::StringList(value.into_iter().map(String::from).collect())
+    }
+}
+
+impl From<HashMap<String, AttrValue>> for AttrValue {
+    fn from(value: HashMap<String, AttrValue>) -> Self {
+        AttrValue::Map(value)
+    }
+}
+
 impl From<bool> for AttrValue {
     fn from(value: bool) -> Self {
         AttrValue::Boolean(value)
     }
 }
+
+/// Converts JSON values to attribute values: strings, booleans and numbers
+/// to the corresponding variants, arrays of strings to
+/// [`AttrValue::StringList`] and objects to [`AttrValue::Map`]. `null` and
+/// other arrays fail with [`Error::Other`].
+impl TryFrom<serde_json::Value> for AttrValue {
+    type Error = Error;
+
+    fn try_from(value: serde_json::Value) -> Result<Self> {
+        let unsupported = |value| {
+            Error::Other(format!(
+                "Cannot convert JSON value '{value}' to an attribute value"
+            ))
+        };
+        match value {
+            serde_json::Value::Number(_) => Ok(AttrValue::Numeric(NumericValue(value))),
+            serde_json::Value::String(value) => Ok(AttrValue::String(value)),
+            serde_json::Value::Bool(value) => Ok(AttrValue::Boolean(value)),
+            serde_json::Value::Array(elements) => elements
+                .into_iter()
+                .map(|element| match element {
+                    serde_json::Value::String(element) => Ok(element),
+                    element => Err(unsupported(element)),
+                })
+                .collect::<Result<_>>()
+                .map(AttrValue::StringList),
+            serde_json::Value::Object(map) => attributes_from_json(map).map(AttrValue::Map),
+            serde_json::Value::Null => Err(unsupported(value)),
+        }
+    }
+}
+
+fn attributes_from_json(
+    attributes: serde_json::Map<String, serde_json::Value>,
+) -> Result<HashMap<String, AttrValue>> {
+    attributes
+        .into_iter()
+        .map(|(name, value)| Ok((name, AttrValue::try_from(value)?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attr_value_try_from_json() {
+        let attr_value = |json| AttrValue::try_from(json).unwrap();
+        assert!(matches!(
+            attr_value(serde_json::json!(-42)),
+            AttrValue::Numeric(ref v) if v.as_i64() == Some(-42)
+        ));
+        assert!(matches!(
+            attr_value(serde_json::json!("heinz")),
+            AttrValue::String(ref v) if v == "heinz"
+        ));
+        assert!(matches!(
+            attr_value(serde_json::json!(false)),
+            AttrValue::Boolean(false)
+        ));
+        assert!(matches!(
+            attr_value(serde_json::json!(["admin", "dev"])),
+            AttrValue::StringList(ref v) if v == &["admin", "dev"]
+        ));
+        assert!(matches!(
+            attr_value(serde_json::json!({"address": {"city": "Bangalore"}})),
+            AttrValue::Map(ref v) if matches!(
+                &v["address"],
+                AttrValue::Map(address) if matches!(&address["city"], AttrValue::String(city) if city == "Bangalore")
+            )
+        ));
+
+        for json in [
+            serde_json::json!(null),
+            serde_json::json!(["admin", 1]),
+            serde_json::json!({"nested": null}),
+        ] {
+            assert!(matches!(AttrValue::try_from(json), Err(Error::Other(_))));
+        }
+    }
+
+    #[test]
+    fn test_simple_entity_from_json() {
+        let serde_json::Value::Object(attributes) = serde_json::json!({"radius": 60}) else {
+            unreachable!()
+        };
+        let entity = SimpleEntity::from_json("user123", attributes).unwrap();
+        assert_eq!(entity.get_id(), "user123");
+        assert!(matches!(
+            entity.get_attributes()["radius"],
+            AttrValue::Numeric(ref v) if v.as_u64() == Some(60)
+        ));
+    }
+}
@@ -26,21 +26,68 @@ pub enum Error {
     #[error(transparent)]
     ReqwestError(#[from] reqwest::Error),
 
+    #[error("Request timed out: {0}")]
+    RequestTimeout(reqwest::Error),
+
+    #[error("Authentication failed (HTTP status {status}): check that the API key is valid and has access to the App Configuration instance. Server response: {body}")]
+    AuthenticationError { status: u16, body: String },
+
     #[error(transparent)]
     TungsteniteError(#[from] tungstenite::Error),
 
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+
     #[error("Protocol error. Unexpected data received from server")]
     ProtocolError,
 
+    #[error("Value of '{resource_id}' is not of its declared type {expected}")]
+    ValueTypeMismatch {
+        expected: crate::models::ValueKind,
+        resource_id: String,
+    },
+
+    #[error("Format of '{resource_id}' is {}, not JSON or YAML", format.as_deref().unwrap_or("not set"))]
+    ValueFormatMismatch {
+        resource_id: String,
+        format: Option<String>,
+    },
+
+    #[error("Value of '{resource_id}' cannot be represented as {target}")]
+    NumericValueOutOfRange {
+        target: &'static str,
+        resource_id: String,
+    },
+
+    #[error("Weights of the values of targeting rule '{order}' of '{resource_id}' add up to {total}, not 100")]
+    InvalidWeightedValues {
+        resource_id: String,
+        order: u32,
+        total: u64,
+    },
+
     #[error(transparent)]
     DeserializationError(#[from] DeserializationError),
 
+    #[error("Unsupported configuration schema (missing {}): the SDK may be outdated", missing_keys.join(", "))]
+    UnsupportedConfigurationSchema { missing_keys: Vec<String> },
+
     #[error("Client is not configured")]
     ClientNotConfigured,
 
+    #[error("Missing required client option '{0}'")]
+    MissingClientOption(&'static str),
+
     #[error(transparent)]
     ConfigurationAccessError(#[from] ConfigurationAccessError),
 
+    /// Boxed, as it is much larger than the other variants.
+    #[error(transparent)]
+    EntityEvaluationError(#[from] Box<EntityEvaluationError>),
+
+    #[error(transparent)]
+    SegmentEvaluationError(#[from] SegmentEvaluationError),
+
     #[error("{0}")]
     Other(String),
 }
@@ -51,6 +98,12 @@ impl<T> From<PoisonError<T>> for Error {
     }
 }
 
+impl From<EntityEvaluationError> for Error {
+    fn from(value: EntityEvaluationError) -> Self {
+        Error::EntityEvaluationError(Box::new(value))
+    }
+}
+
 /// An error that can be returned when deserializing data.
 #[derive(Debug, Error)]
 #[error("Cannot deserialize string '{string}': {source}")]
@@ -64,6 +117,9 @@ pub struct DeserializationError {
 pub enum DeserializationErrorKind {
     #[error(transparent)]
     SerdeError(#[from] serde_json::Error),
+
+    #[error(transparent)]
+    YamlError(#[from] serde_yaml::Error),
 }
 
 #[derive(Debug, Error)]
@@ -76,12 +132,45 @@ pub enum ConfigurationAccessError {
     )]
     EnvironmentNotFound { environment_id: String },
 
+    #[error("Collection `{collection_id}` not found.")]
+    CollectionNotFound { collection_id: String },
+
     #[error("Feature `{feature_id}` not found.")]
     FeatureNotFound { feature_id: String },
 
     #[error("Property `{property_id}` not found.")]
     PropertyNotFound { property_id: String },
 
+    #[error("Feature `{feature_id}` is of type {actual}, not {expected}.")]
+    FeatureTypeMismatch {
+        feature_id: String,
+        expected: crate::models::ValueKind,
+        actual: crate::models::ValueKind,
+    },
+
+    #[error("Property `{property_id}` is of type {actual}, not {expected}.")]
+    PropertyTypeMismatch {
+        property_id: String,
+        expected: crate::models::ValueKind,
+        actual: crate::models::ValueKind,
+    },
+
+    #[error("No {resource_kind} named `{name}` found.")]
+    NameNotFound {
+        resource_kind: ResourceKind,
+        name: String,
+    },
+
+    #[error("Several {resource_kind} ids share the name `{name}`: {}.", ids.join(", "))]
+    AmbiguousName {
+        resource_kind: ResourceKind,
+        name: String,
+        ids: Vec<String>,
+    },
+
+    #[error("Segment `{segment_id}` not found.")]
+    SegmentNotFound { segment_id: String },
+
     #[error("Missing segments for resource '{resource_id}'")]
     MissingSegments { resource_id: String },
 }
@@ -91,3 +180,99 @@ impl<T> From<PoisonError<T>> for ConfigurationAccessError {
         ConfigurationAccessError::LockAcquisitionError
     }
 }
+
+/// An error that can be returned when evaluating an entity against the
+/// targeting rules of a feature or property.
+#[derive(Debug, Error)]
+#[error("Failed to evaluate {resource_kind} '{resource_id}' for entity '{entity_id}' against targeting rule '{order}': {source}")]
+pub struct EntityEvaluationError {
+    pub resource_kind: ResourceKind,
+    pub resource_id: String,
+    pub entity_id: String,
+    pub order: u32,
+    pub source: SegmentEvaluationError,
+}
+
+/// Whether a feature or a property was evaluated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceKind {
+    Feature,
+    Property,
+}
+
+impl std::fmt::Display for ResourceKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Feature => write!(f, "feature"),
+            Self::Property => write!(f, "property"),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum SegmentEvaluationError {
+    #[error("Segment '{segment_id}' not found")]
+    SegmentIdNotFound { segment_id: String },
+
+    #[error("Operation '{attribute_name}' '{operator}' '{value}' failed to evaluate: {source}")]
+    SegmentEvaluationFailed {
+        attribute_name: String,
+        operator: String,
+        value: String,
+        source: CheckOperatorErrorDetail,
+    },
+}
+
+/// Additional information for [`SegmentEvaluationError::SegmentEvaluationFailed`]
+#[derive(Debug, Error)]
+pub enum CheckOperatorErrorDetail {
+    #[error("Entity attribute is not a string.")]
+    StringExpected,
+
+    #[error("Entity attribute is not a number.")]
+    EntityAttrNotANumber,
+
+    #[error(
+        "Entity attribute is a map, rules can only refer to its elements, like 'address.city'."
+    )]
+    EntityAttrIsAMap,
+
+    #[error("Value cannot convert into a bool: {0}")]
+    BooleanExpected(#[from] std::str::ParseBoolError),
+
+    #[error("Value cannot be converted to number: {0}")]
+    NumberExpected(#[from] std::num::ParseFloatError),
+
+    #[error("Invalid regular expression: {0}")]
+    InvalidRegex(#[from] regex::Error),
+
+    #[error("Entity attribute is not a valid semantic version: {0}")]
+    EntityAttrNotASemanticVersion(semver::Error),
+
+    #[error("Value is not a valid semantic version: {0}")]
+    ReferenceValueNotASemanticVersion(semver::Error),
+
+    #[error("Entity attribute is not a valid RFC 3339 timestamp: {0}")]
+    EntityAttrNotATimestamp(chrono::ParseError),
+
+    #[error("Value is not a valid RFC 3339 timestamp: {0}")]
+    ReferenceValueNotATimestamp(chrono::ParseError),
+
+    #[error("Entity attribute is not a valid IP address: {0}")]
+    EntityAttrNotAnIpAddress(std::net::AddrParseError),
+
+    #[error("Value is not a valid CIDR block like '10.0.0.0/8': {0}")]
+    InvalidCidr(ipnet::AddrParseError),
+
+    #[error("Value '{0}' is not a range like 'min,max' or '[min,max]'.")]
+    InvalidRange(String),
+
+    #[error("Referenced entity attribute '{0}' not found.")]
+    ReferencedAttributeNotFound(String),
+
+    #[error("Referenced entity attribute '{0}' is not a single value.")]
+    ReferencedAttributeNotAScalar(String),
+
+    #[error("Operator '{0}' not implemented.")]
+    OperatorNotImplemented(String),
+}
@@ -18,7 +18,8 @@ pub mod models;
 mod segment_evaluation;
 pub mod errors;
 
-pub use entity::{AttrValue, Entity};
+pub use entity::{AttrValue, Entity, EntityBuilder, SimpleEntity};
+pub use segment_evaluation::EvaluationOptions;
 
 #[cfg(test)]
 mod tests;
@@ -14,23 +14,25 @@
 
 use std::fmt::Display;
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize)]
+use crate::errors::{DeserializationError, DeserializationErrorKind, Error, Result};
+
+#[derive(Debug, Deserialize, Serialize)]
 pub(crate) struct Configuration {
     pub environments: Vec<Environment>,
     pub segments: Vec<Segment>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub(crate) struct Environment {
-    name: String,
+    pub name: String,
     pub environment_id: String,
     pub features: Vec<Feature>,
     pub properties: Vec<Property>,
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
 pub(crate) struct Segment {
     pub name: String,
     pub segment_id: String,
@@ -39,39 +41,70 @@ pub(crate) struct Segment {
     pub rules: Vec<SegmentRule>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
 pub(crate) struct Feature {
     pub name: String,
     pub feature_id: String,
-    #[serde(rename(deserialize = "type"))]
+    #[serde(rename = "type")]
     pub kind: ValueKind,
+    pub tags: Option<String>,
     pub format: Option<String>,
     pub enabled_value: ConfigValue,
     pub disabled_value: ConfigValue,
     pub segment_rules: Vec<TargetingRule>,
     pub enabled: bool,
     pub rollout_percentage: u32,
+    /// Collections the feature belongs to. Only listed in configurations
+    /// spanning several collections.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub collections: Vec<CollectionRef>,
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
 pub(crate) struct Property {
     pub name: String,
     pub property_id: String,
-    #[serde(rename(deserialize = "type"))]
+    #[serde(rename = "type")]
     pub kind: ValueKind,
     pub tags: Option<String>,
     pub format: Option<String>,
     pub value: ConfigValue,
     pub segment_rules: Vec<TargetingRule>,
+    /// Collections the property belongs to. Only listed in configurations
+    /// spanning several collections.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub collections: Vec<CollectionRef>,
+}
+
+/// Reference to a collection a feature or property belongs to.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub(crate) struct CollectionRef {
+    pub collection_id: String,
+}
+
+/// Whether a feature or property belonging to `collections` is part of the
+/// collection `collection_id`. Those not listing any collection are part
+/// of all of them.
+pub(crate) fn in_collection(collections: &[CollectionRef], collection_id: &str) -> bool {
+    collections.is_empty()
+        || collections
+            .iter()
+            .any(|collection| collection.collection_id == collection_id)
 }
 
-#[derive(Copy, Clone, Debug, Deserialize, PartialEq)]
+/// Whether `tags`, a comma separated list of tags (as in features,
+/// properties and segments), contains `tag`.
+pub(crate) fn has_tag(tags: Option<&str>, tag: &str) -> bool {
+    tags.is_some_and(|tags| tags.split(',').any(|t| t.trim() == tag))
+}
+
+#[derive(Copy, Clone, Debug, Deserialize, Serialize, PartialEq)]
 pub enum ValueKind {
-    #[serde(rename(deserialize = "NUMERIC"))]
+    #[serde(rename = "NUMERIC")]
     Numeric,
-    #[serde(rename(deserialize = "BOOLEAN"))]
+    #[serde(rename = "BOOLEAN")]
     Boolean,
-    #[serde(rename(deserialize = "STRING"))]
+    #[serde(rename = "STRING")]
     String,
 }
 
@@ -86,7 +119,7 @@ impl Display for ValueKind {
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
 pub struct ConfigValue(pub(crate) serde_json::Value);
 
 impl ConfigValue {
@@ -110,6 +143,8 @@ impl ConfigValue {
         self.0.as_str().map(|s| s.to_string())
     }
 
+    /// Whether this is the `$default` marker. It only has a meaning in
+    /// targeting rules, see [`TargetingRule::value_or_default`].
     pub fn is_default(&self) -> bool {
         if let Some(s) = self.0.as_str() {
             s == "$default"
@@ -117,6 +152,42 @@ impl ConfigValue {
             false
         }
     }
+    /// Parses this value, of the string feature or property `resource_id`
+    /// of format `format`, as structured data. `JSON` values can be given as
+    /// a string, or as JSON directly. `YAML` values are strings.
+    ///
+    /// Fails with [`Error::ValueFormatMismatch`] if the format is not a
+    /// structured one, or with [`Error::DeserializationError`] if the value
+    /// cannot be parsed.
+    pub(crate) fn parse_structured(
+        &self,
+        resource_id: &str,
+        format: Option<&str>,
+    ) -> Result<serde_json::Value> {
+        let deserialization_error = |string: &str, source: DeserializationErrorKind| {
+            Error::from(DeserializationError {
+                string: string.to_string(),
+                source,
+            })
+        };
+        match (format, &self.0) {
+            (Some("JSON"), serde_json::Value::String(s)) => {
+                serde_json::from_str(s).map_err(|e| deserialization_error(s, e.into()))
+            }
+            (Some("JSON"), value) => Ok(value.clone()),
+            (Some("YAML"), serde_json::Value::String(s)) => {
+                serde_yaml::from_str(s).map_err(|e| deserialization_error(s, e.into()))
+            }
+            (Some("YAML"), _) => Err(Error::ValueTypeMismatch {
+                expected: ValueKind::String,
+                resource_id: resource_id.to_string(),
+            }),
+            _ => Err(Error::ValueFormatMismatch {
+                resource_id: resource_id.to_string(),
+                format: format.map(str::to_string),
+            }),
+        }
+    }
 }
 
 impl Display for ConfigValue {
@@ -125,22 +196,154 @@ impl Display for ConfigValue {
     }
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
 pub(crate) struct SegmentRule {
     pub attribute_name: String,
     pub operator: String,
     pub values: Vec<String>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 pub struct TargetingRule {
     pub rules: Vec<Segments>,
     pub value: ConfigValue,
     pub order: u32,
     pub rollout_percentage: Option<ConfigValue>,
+    /// Values of a feature shared out among the entities part of the
+    /// rollout, for experiments. Without them, these entities get `value`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub weighted_values: Vec<WeightedValue>,
+}
+
+/// A value of a targeting rule, for `weight` percent of the entities the
+/// rule applies to. The weights of the values of a rule add up to 100.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct WeightedValue {
+    pub value: ConfigValue,
+    pub weight: u32,
+}
+
+/// The value of a targeting rule, see [`TargetingRule::value_or_default`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigValueOrDefault<'a> {
+    /// The rule uses the value of its feature (the enabled value) or
+    /// property, given as `$default`.
+    Default,
+    /// The rule overrides the value of its feature or property.
+    Value(&'a ConfigValue),
+}
+
+impl<'a> ConfigValueOrDefault<'a> {
+    fn of(value: &'a ConfigValue) -> Self {
+        if value.is_default() {
+            Self::Default
+        } else {
+            Self::Value(value)
+        }
+    }
+
+    /// Returns the overriding value, or `default` if there is none.
+    pub fn unwrap_or(self, default: &'a ConfigValue) -> &'a ConfigValue {
+        match self {
+            Self::Default => default,
+            Self::Value(value) => value,
+        }
+    }
 }
 
-#[derive(Debug, Deserialize, Clone)]
+impl TargetingRule {
+    /// Returns the value of this rule. The `$default` marker is only
+    /// recognized here: a feature or property whose own value is the string
+    /// `"$default"` keeps that value.
+    pub fn value_or_default(&self) -> ConfigValueOrDefault<'_> {
+        ConfigValueOrDefault::of(&self.value)
+    }
+
+    /// Fails with [`Error::InvalidWeightedValues`] if this rule, of the
+    /// feature `resource_id`, has weighted values whose weights don't add up
+    /// to 100.
+    pub(crate) fn check_weighted_values(&self, resource_id: &str) -> Result<()> {
+        if self.weighted_values.is_empty() {
+            return Ok(());
+        }
+        let total = self
+            .weighted_values
+            .iter()
+            .map(|weighted| u64::from(weighted.weight))
+            .sum();
+        if total != 100 {
+            return Err(Error::InvalidWeightedValues {
+                resource_id: resource_id.to_string(),
+                order: self.order,
+                total,
+            });
+        }
+        Ok(())
+    }
+
+    /// Returns the value of this rule, of the feature `resource_id`, for an
+    /// entity in the variant bucket `bucket` (from 0 to 100): the weighted
+    /// values take consecutive buckets, as many as their weight, in the
+    /// order they are listed. Without weighted values, this is the value of
+    /// the rule.
+    ///
+    /// Fails like [`TargetingRule::check_weighted_values`].
+    pub(crate) fn weighted_value_or_default(
+        &self,
+        resource_id: &str,
+        bucket: u32,
+    ) -> Result<ConfigValueOrDefault<'_>> {
+        if self.weighted_values.is_empty() {
+            return Ok(self.value_or_default());
+        }
+        self.check_weighted_values(resource_id)?;
+        // Bucket 100 only exists for the largest hash
+        let bucket = bucket.min(99);
+        let mut upper_bound = 0;
+        for weighted in &self.weighted_values {
+            upper_bound += weighted.weight;
+            if bucket < upper_bound {
+                return Ok(ConfigValueOrDefault::of(&weighted.value));
+            }
+        }
+        Err(Error::ProtocolError)
+    }
+
+    /// Returns the rollout percentage of this rule, where `$default` stands
+    /// for `feature_rollout_percentage`.
+    ///
+    /// Fails with [`Error::ProtocolError`] if the rule has no rollout
+    /// percentage, or it is not an integer from 0 to 100.
+    pub(crate) fn resolve_rollout_percentage(
+        &self,
+        feature_rollout_percentage: u32,
+    ) -> Result<u32> {
+        let rollout_percentage = self
+            .rollout_percentage
+            .as_ref()
+            .ok_or(Error::ProtocolError)?;
+        if rollout_percentage.is_default() {
+            return check_rollout_percentage(feature_rollout_percentage);
+        }
+        rollout_percentage
+            .as_u64()
+            .and_then(|value| u32::try_from(value).ok())
+            .ok_or(Error::ProtocolError)
+            .and_then(check_rollout_percentage)
+    }
+}
+
+/// Returns `rollout_percentage` if it is a percentage (from 0 to 100), or
+/// [`Error::ProtocolError`] otherwise.
+pub(crate) fn check_rollout_percentage(rollout_percentage: u32) -> Result<u32> {
+    if rollout_percentage <= 100 {
+        Ok(rollout_percentage)
+    } else {
+        Err(Error::ProtocolError)
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 pub struct Segments {
     pub segments: Vec<String>,
 }
@@ -175,12 +378,14 @@ pub mod tests {
                     name: "F1".to_string(),
                     feature_id: "f1".to_string(),
                     kind: ValueKind::Numeric,
+                    tags: None,
                     format: None,
                     enabled_value: ConfigValue(serde_json::Value::Number(42.into())),
                     disabled_value: ConfigValue(serde_json::Value::Number((-42).into())),
                     segment_rules: Vec::new(),
                     enabled: true,
                     rollout_percentage: 0,
+                    collections: Vec::new(),
                 }],
                 properties: Vec::new(),
             }],
@@ -202,10 +407,98 @@ pub mod tests {
                     value: ConfigValue(serde_json::Value::Number(42.into())),
                     segment_rules: Vec::new(),
                     tags: None,
+                    collections: Vec::new(),
                 }],
                 features: Vec::new(),
             }],
             segments: Vec::new(),
         }
     }
+
+    #[rstest]
+    #[case(None, "team-a", false)]
+    #[case(Some(""), "team-a", false)]
+    #[case(Some("team-a"), "team-a", true)]
+    #[case(Some("team-b, team-a"), "team-a", true)]
+    #[case(Some("team-ab"), "team-a", false)]
+    fn test_has_tag(#[case] tags: Option<&str>, #[case] tag: &str, #[case] expected: bool) {
+        assert_eq!(has_tag(tags, tag), expected);
+    }
+
+    #[test]
+    fn test_parse_structured_errors() {
+        let result =
+            ConfigValue(serde_json::json!("{not json")).parse_structured("f1", Some("JSON"));
+        assert!(matches!(
+            result,
+            Err(Error::DeserializationError(DeserializationError {
+                ref string,
+                source: DeserializationErrorKind::SerdeError(_),
+            })) if string == "{not json"
+        ));
+
+        let result =
+            ConfigValue(serde_json::json!("key: [1, two")).parse_structured("f1", Some("YAML"));
+        assert!(matches!(
+            result,
+            Err(Error::DeserializationError(DeserializationError {
+                ref string,
+                source: DeserializationErrorKind::YamlError(_),
+            })) if string == "key: [1, two"
+        ));
+
+        let result =
+            ConfigValue(serde_json::json!({"key": 1})).parse_structured("f1", Some("YAML"));
+        assert!(matches!(
+            result,
+            Err(Error::ValueTypeMismatch { expected: ValueKind::String, ref resource_id })
+                if resource_id == "f1"
+        ));
+
+        let result = ConfigValue(serde_json::json!("text")).parse_structured("f1", None);
+        assert!(matches!(
+            result,
+            Err(Error::ValueFormatMismatch { ref resource_id, format: None }) if resource_id == "f1"
+        ));
+    }
+
+    #[rstest]
+    #[case(0, Some("a"))]
+    #[case(29, Some("a"))]
+    #[case(30, None)]
+    #[case(100, None)]
+    fn test_weighted_value_or_default(#[case] bucket: u32, #[case] expected: Option<&str>) {
+        let mut rule = TargetingRule {
+            rules: Vec::new(),
+            value: ConfigValue(serde_json::json!("value")),
+            order: 0,
+            rollout_percentage: Some(ConfigValue(serde_json::json!(100))),
+            weighted_values: vec![
+                WeightedValue {
+                    value: ConfigValue(serde_json::json!("a")),
+                    weight: 30,
+                },
+                WeightedValue {
+                    value: ConfigValue(serde_json::json!("$default")),
+                    weight: 70,
+                },
+            ],
+        };
+        let expected = expected.map(|s| ConfigValue(serde_json::json!(s)));
+        let expected = match &expected {
+            Some(value) => ConfigValueOrDefault::Value(value),
+            None => ConfigValueOrDefault::Default,
+        };
+        assert_eq!(
+            rule.weighted_value_or_default("f1", bucket).unwrap(),
+            expected
+        );
+
+        rule.weighted_values[1].weight = 60;
+        assert!(matches!(
+            rule.weighted_value_or_default("f1", bucket),
+            Err(Error::InvalidWeightedValues { ref resource_id, order: 0, total: 90 })
+                if resource_id == "f1"
+        ));
+    }
 }
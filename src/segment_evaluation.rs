@@ -12,133 +12,711 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
 
+use chrono::DateTime;
+use ipnet::IpNet;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use semver::Version;
+
+use crate::client::value::NumericValue;
+use crate::errors::{
+    self, CheckOperatorErrorDetail, EntityEvaluationError, ResourceKind, SegmentEvaluationError,
+};
 use crate::models::Segment;
 use crate::{
     entity::{AttrValue, Entity},
     models::TargetingRule,
 };
 
-pub(crate) fn find_applicable_segment_rule_for_entity(
-    segments: &HashMap<String, Segment>,
-    segment_rules: impl Iterator<Item = TargetingRule>,
+/// Options controlling how entities are evaluated against segment rules.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EvaluationOptions {
+    /// When `true`, string attributes that parse as numbers are compared
+    /// numerically by the `greaterThan`, `lesserThan`, `greaterThanEquals`
+    /// and `lesserThanEquals` operators, as the Go SDK does. Strings that
+    /// don't parse as numbers still fail with "Entity attribute is not a
+    /// number".
+    ///
+    /// Defaults to `false` (strict): these operators only accept numeric
+    /// attributes.
+    pub coerce_numeric_strings: bool,
+
+    /// Seed of the hash assigning entities to rollout buckets, see
+    /// [`rollout_bucket`](crate::client::rollout_bucket). Defaults to `0`,
+    /// as in other App Configuration SDKs; changing it changes which
+    /// entities are part of partial rollouts.
+    pub rollout_seed: u32,
+}
+
+/// All operators segment rules can use.
+pub(crate) const SUPPORTED_OPERATORS: [&str; 25] = [
+    "exists",
+    "notExists",
+    "is",
+    "notEquals",
+    "in",
+    "notIn",
+    "contains",
+    "startsWith",
+    "endsWith",
+    "isCaseInsensitive",
+    "containsCaseInsensitive",
+    "startsWithCaseInsensitive",
+    "endsWithCaseInsensitive",
+    "matches",
+    "semverGreaterThan",
+    "semverLessThan",
+    "semverEquals",
+    "before",
+    "after",
+    "inCidr",
+    "greaterThan",
+    "lesserThan",
+    "greaterThanEquals",
+    "lesserThanEquals",
+    "between",
+];
+
+const NUMERIC_OPERATORS: [&str; 5] = [
+    "greaterThan",
+    "lesserThan",
+    "greaterThanEquals",
+    "lesserThanEquals",
+    "between",
+];
+
+/// Operators for which an attribute has to differ from all listed values,
+/// instead of matching one of them.
+const NEGATED_OPERATORS: [&str; 2] = ["notEquals", "notIn"];
+
+/// Prefix of rule values referring to another attribute of the entity, like
+/// `$attr:quota`, instead of being a literal.
+const ATTRIBUTE_REFERENCE_PREFIX: &str = "$attr:";
+
+/// Whether an entity belongs to segments, keyed by segment id, so that each
+/// segment is evaluated once per entity however many targeting rules (of
+/// however many features) refer to it. Only valid for a single entity and
+/// configuration.
+#[derive(Debug, Default)]
+pub(crate) struct SegmentMemo(RefCell<HashMap<String, bool>>);
+
+/// How many compiled regular expressions the `matches` operator keeps.
+const REGEX_CACHE_CAPACITY: usize = 256;
+
+/// Compiled regular expressions used by the `matches` operator.
+static REGEX_CACHE: Lazy<Mutex<RegexCache>> = Lazy::new(Default::default);
+
+/// Compiled regular expressions keyed by pattern, with the tick of their
+/// last use. Once full, the least recently used one is evicted.
+#[derive(Debug, Default)]
+struct RegexCache {
+    regexes: HashMap<String, (Regex, u64)>,
+    tick: u64,
+}
+
+impl RegexCache {
+    fn get_or_compile(&mut self, pattern: &str) -> Result<Regex, regex::Error> {
+        self.tick += 1;
+        if let Some((regex, last_use)) = self.regexes.get_mut(pattern) {
+            *last_use = self.tick;
+            return Ok(regex.clone());
+        }
+        let regex = Regex::new(pattern)?;
+        if self.regexes.len() >= REGEX_CACHE_CAPACITY {
+            let least_recently_used = self
+                .regexes
+                .iter()
+                .min_by_key(|(_, (_, last_use))| *last_use)
+                .map(|(pattern, _)| pattern.clone());
+            if let Some(pattern) = least_recently_used {
+                self.regexes.remove(&pattern);
+            }
+        }
+        self.regexes
+            .insert(pattern.to_string(), (regex.clone(), self.tick));
+        Ok(regex)
+    }
+}
+
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(skip_all, fields(entity_id = %entity.get_id()))
+)]
+pub(crate) fn find_applicable_segment_rule_for_entity<'a>(
+    resource_kind: ResourceKind,
+    resource_id: &str,
+    segments: &HashMap<String, Arc<Segment>>,
+    segment_rules: &'a [TargetingRule],
+    entity: &impl Entity,
+    options: &EvaluationOptions,
+) -> errors::Result<Option<&'a TargetingRule>> {
+    find_applicable_segment_rule_for_entity_memoized(
+        resource_kind,
+        resource_id,
+        segments,
+        segment_rules,
+        entity,
+        options,
+        &SegmentMemo::default(),
+    )
+}
+
+/// Like [`find_applicable_segment_rule_for_entity`], reusing (and adding
+/// to) the segment evaluations of `memo`.
+pub(crate) fn find_applicable_segment_rule_for_entity_memoized<'a>(
+    resource_kind: ResourceKind,
+    resource_id: &str,
+    segments: &HashMap<String, Arc<Segment>>,
+    segment_rules: &'a [TargetingRule],
+    entity: &impl Entity,
+    options: &EvaluationOptions,
+    memo: &SegmentMemo,
+) -> errors::Result<Option<&'a TargetingRule>> {
+    for targeting_rule in sorted_by_order(segment_rules) {
+        if targeting_rule_applies(
+            resource_kind,
+            resource_id,
+            segments,
+            targeting_rule,
+            entity,
+            options,
+            memo,
+        )? {
+            return Ok(Some(targeting_rule));
+        }
+    }
+    Ok(None)
+}
+
+/// Like [`find_applicable_segment_rule_for_entity`], returning every
+/// targeting rule matching `entity` in order instead of only the first one,
+/// to explain why a rule was shadowed by another.
+pub(crate) fn find_all_applicable_segment_rules_for_entity(
+    resource_kind: ResourceKind,
+    resource_id: &str,
+    segments: &HashMap<String, Arc<Segment>>,
+    segment_rules: &[TargetingRule],
     entity: &impl Entity,
-) -> Option<TargetingRule> {
-    let mut targeting_rules = segment_rules.collect::<Vec<_>>();
-    targeting_rules.sort_by(|a, b| a.order.cmp(&b.order));
+    options: &EvaluationOptions,
+) -> errors::Result<Vec<TargetingRule>> {
+    let memo = SegmentMemo::default();
+    let mut applicable_rules = Vec::new();
+    for targeting_rule in sorted_by_order(segment_rules) {
+        if targeting_rule_applies(
+            resource_kind,
+            resource_id,
+            segments,
+            targeting_rule,
+            entity,
+            options,
+            &memo,
+        )? {
+            applicable_rules.push(targeting_rule.clone());
+        }
+    }
+    Ok(applicable_rules)
+}
+
+fn sorted_by_order(segment_rules: &[TargetingRule]) -> Vec<&TargetingRule> {
+    let mut targeting_rules = segment_rules.iter().collect::<Vec<_>>();
+    targeting_rules.sort_by_key(|rule| rule.order);
     targeting_rules
-        .into_iter()
-        .find(|targeting_rule| targeting_rule_applies_to_entity(segments, targeting_rule, entity))
+}
+
+/// Like [`targeting_rule_applies_to_entity`], attributing errors to the
+/// feature or property `resource_id`.
+fn targeting_rule_applies(
+    resource_kind: ResourceKind,
+    resource_id: &str,
+    segments: &HashMap<String, Arc<Segment>>,
+    targeting_rule: &TargetingRule,
+    entity: &impl Entity,
+    options: &EvaluationOptions,
+    memo: &SegmentMemo,
+) -> errors::Result<bool> {
+    Ok(
+        targeting_rule_applies_to_entity(segments, targeting_rule, entity, options, memo).map_err(
+            |source| EntityEvaluationError {
+                resource_kind,
+                resource_id: resource_id.to_string(),
+                entity_id: entity.get_id(),
+                order: targeting_rule.order,
+                source,
+            },
+        )?,
+    )
 }
 
 fn targeting_rule_applies_to_entity(
-    segments: &HashMap<String, Segment>,
+    segments: &HashMap<String, Arc<Segment>>,
     targeting_rule: &TargetingRule,
     entity: &impl Entity,
-) -> bool {
-    let rules = &targeting_rule.rules;
-    rules
-        .iter()
-        .any(|rules| segment_applies_to_entity(segments, &rules.segments, entity))
+    options: &EvaluationOptions,
+    memo: &SegmentMemo,
+) -> Result<bool, SegmentEvaluationError> {
+    for rules in targeting_rule.rules.iter() {
+        if segment_applies_to_entity(segments, &rules.segments, entity, options, memo)? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
 }
 
 fn segment_applies_to_entity(
-    segments: &HashMap<String, Segment>,
+    segments: &HashMap<String, Arc<Segment>>,
     segment_ids: &[String],
     entity: &impl Entity,
-) -> bool {
-    segment_ids
-        .iter()
-        .map(|segment_id| {
+    options: &EvaluationOptions,
+    memo: &SegmentMemo,
+) -> Result<bool, SegmentEvaluationError> {
+    for segment_id in segment_ids {
+        let memoized = memo.0.borrow().get(segment_id).copied();
+        if let Some(belongs) = memoized {
+            if belongs {
+                return Ok(true);
+            }
+            continue;
+        }
+        let segment =
             segments
                 .get(segment_id)
-                .unwrap_or_else(|| panic!("Segment {} not found", segment_id))
-        })
-        .any(|segment| belong_to_segment(segment, entity.get_attributes()))
+                .ok_or_else(|| SegmentEvaluationError::SegmentIdNotFound {
+                    segment_id: segment_id.clone(),
+                })?;
+        let belongs = belong_to_segment(segment, entity, options)?;
+        memo.0.borrow_mut().insert(segment_id.clone(), belongs);
+        if belongs {
+            return Ok(true);
+        }
+    }
+    Ok(false)
 }
 
-fn belong_to_segment(segment: &Segment, attrs: HashMap<String, AttrValue>) -> bool {
-    segment.rules.iter().all(|rule| {
+/// Whether `entity` belongs to `segment`, i.e. matches all of its rules.
+pub(crate) fn entity_in_segment(
+    segment: &Segment,
+    entity: &impl Entity,
+    options: &EvaluationOptions,
+) -> Result<bool, SegmentEvaluationError> {
+    belong_to_segment(segment, entity, options)
+}
+
+fn belong_to_segment(
+    segment: &Segment,
+    entity: &impl Entity,
+    options: &EvaluationOptions,
+) -> Result<bool, SegmentEvaluationError> {
+    for rule in segment.rules.iter() {
         let operator = &rule.operator;
         let attr_name = &rule.attribute_name;
-        let attr_value = attrs
-            .get(attr_name)
-            .expect("Attribute does not exist in the entity.");
-        rule.values
+        // These operators only check whether the entity has the attribute,
+        // the values of the rule are ignored
+        let expect_attribute = match operator.as_str() {
+            "exists" => Some(true),
+            "notExists" => Some(false),
+            _ => None,
+        };
+        if let Some(expect_attribute) = expect_attribute {
+            if lookup_attribute(entity, attr_name).is_some() != expect_attribute {
+                return Ok(false);
+            }
+            continue;
+        }
+        let attr_value = match lookup_attribute(entity, attr_name) {
+            Some(attr_value) => attr_value,
+            None => {
+                // A missing attribute never matches the segment
+                log::debug!("Attribute '{attr_name}' not found in entity.");
+                return Ok(false);
+            }
+        };
+        let coerced_value = coerce_numeric_string(&attr_value, operator, options);
+        let attr_value = coerced_value.as_ref().unwrap_or(&attr_value);
+        let check = |value: &String| {
+            resolve_reference_value(value, entity)
+                .and_then(|resolved| {
+                    // Values taken from the entity can be anything, caching
+                    // their regular expressions would grow the cache unbounded
+                    let cache_regex = matches!(resolved, Cow::Borrowed(_));
+                    check_operator_with(attr_value, operator, &resolved, cache_regex)
+                })
+                .map_err(|source| SegmentEvaluationError::SegmentEvaluationFailed {
+                    attribute_name: attr_name.clone(),
+                    operator: operator.clone(),
+                    value: value.clone(),
+                    source,
+                })
+        };
+        // For negated operators the attribute must differ from every listed
+        // value, for all others one of the listed values must match.
+        let negated = NEGATED_OPERATORS.contains(&operator.as_str());
+        let mut rule_result = negated;
+        for value in rule.values.iter() {
+            if check(value)? != negated {
+                rule_result = !negated;
+                break;
+            }
+        }
+        if !rule_result {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Returns the attribute `attr_name` of `entity`. A dotted name like
+/// `address.city` refers to an element of a nested [`AttrValue::Map`],
+/// unless the entity has an attribute of exactly that name.
+fn lookup_attribute(entity: &impl Entity, attr_name: &str) -> Option<AttrValue> {
+    if let Some(attr_value) = entity.get_attribute(attr_name) {
+        return Some(attr_value);
+    }
+    let (root_name, path) = attr_name.split_once('.')?;
+    let root = entity.get_attribute(root_name)?;
+    let mut attr_value = &root;
+    for key in path.split('.') {
+        match attr_value {
+            AttrValue::Map(map) => attr_value = map.get(key)?,
+            _ => return None,
+        }
+    }
+    Some(attr_value.clone())
+}
+
+/// Resolves `value` of a segment rule: a reference to an attribute of the
+/// entity (`$attr:name`) is replaced by the value of that attribute, any
+/// other value is a literal.
+fn resolve_reference_value<'a>(
+    value: &'a str,
+    entity: &impl Entity,
+) -> Result<Cow<'a, str>, CheckOperatorErrorDetail> {
+    let Some(attr_name) = value.strip_prefix(ATTRIBUTE_REFERENCE_PREFIX) else {
+        return Ok(Cow::Borrowed(value));
+    };
+    match lookup_attribute(entity, attr_name) {
+        Some(AttrValue::String(data)) => Ok(Cow::Owned(data)),
+        Some(AttrValue::Numeric(data)) => Ok(Cow::Owned(data.0.to_string())),
+        Some(AttrValue::Boolean(data)) => Ok(Cow::Owned(data.to_string())),
+        Some(AttrValue::StringList(_) | AttrValue::Map(_)) => Err(
+            CheckOperatorErrorDetail::ReferencedAttributeNotAScalar(attr_name.to_string()),
+        ),
+        None => Err(CheckOperatorErrorDetail::ReferencedAttributeNotFound(
+            attr_name.to_string(),
+        )),
+    }
+}
+
+/// Returns the numeric interpretation of a string attribute if numeric string
+/// coercion is enabled and `operator` is a numeric comparison.
+fn coerce_numeric_string(
+    attr_value: &AttrValue,
+    operator: &str,
+    options: &EvaluationOptions,
+) -> Option<AttrValue> {
+    match attr_value {
+        AttrValue::String(data)
+            if options.coerce_numeric_strings && NUMERIC_OPERATORS.contains(&operator) =>
+        {
+            parse_numeric(data).ok().map(AttrValue::Numeric)
+        }
+        _ => None,
+    }
+}
+
+/// Whether `data` matches the regular expression `pattern`, which is taken
+/// from and kept in [`REGEX_CACHE`] if `cache_regex`.
+fn regex_matches(
+    pattern: &str,
+    data: &str,
+    cache_regex: bool,
+) -> Result<bool, CheckOperatorErrorDetail> {
+    let regex = if cache_regex {
+        REGEX_CACHE
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .get_or_compile(pattern)?
+    } else {
+        Regex::new(pattern)?
+    };
+    Ok(regex.is_match(data))
+}
+
+/// Parses a reference value as integer if possible, to compare it against
+/// integer attributes without losing precision.
+fn parse_numeric(reference_value: &str) -> Result<NumericValue, CheckOperatorErrorDetail> {
+    if let Ok(value) = reference_value.parse::<i64>() {
+        Ok(value.into())
+    } else if let Ok(value) = reference_value.parse::<u64>() {
+        Ok(value.into())
+    } else {
+        Ok(reference_value.parse::<f64>()?.into())
+    }
+}
+
+/// Parses a reference value compared with a boolean attribute. Accepts
+/// `true`, `yes`, `on` and `1`, or `false`, `no`, `off` and `0`, ignoring case.
+fn parse_bool(reference_value: &str) -> Result<bool, CheckOperatorErrorDetail> {
+    match reference_value.to_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Ok(true),
+        "false" | "no" | "off" | "0" => Ok(false),
+        // Fails, with the error of the standard parser
+        _ => Ok(reference_value.parse::<bool>()?),
+    }
+}
+
+/// Parses the inclusive range of the `between` operator, given as `min,max`
+/// or as a JSON array `[min,max]`.
+fn parse_range(
+    reference_value: &str,
+) -> Result<(NumericValue, NumericValue), CheckOperatorErrorDetail> {
+    let invalid_range = || CheckOperatorErrorDetail::InvalidRange(reference_value.to_string());
+    let trimmed = reference_value.trim();
+    let bounds = trimmed
+        .strip_prefix('[')
+        .and_then(|bounds| bounds.strip_suffix(']'))
+        .unwrap_or(trimmed);
+    let (min, max) = bounds.split_once(',').ok_or_else(invalid_range)?;
+    let min = parse_numeric(min.trim()).map_err(|_| invalid_range())?;
+    let max = parse_numeric(max.trim()).map_err(|_| invalid_range())?;
+    if min > max {
+        return Err(invalid_range());
+    }
+    Ok((min, max))
+}
+
+fn compare_semantic_versions(
+    data: &str,
+    reference_value: &str,
+) -> Result<Ordering, CheckOperatorErrorDetail> {
+    let data =
+        Version::parse(data).map_err(CheckOperatorErrorDetail::EntityAttrNotASemanticVersion)?;
+    let reference_value = Version::parse(reference_value)
+        .map_err(CheckOperatorErrorDetail::ReferenceValueNotASemanticVersion)?;
+    Ok(data.cmp(&reference_value))
+}
+
+/// Compares two RFC 3339 timestamps like `2024-09-30T12:34:56+02:00`
+/// chronologically, regardless of their offsets.
+fn compare_timestamps(
+    data: &str,
+    reference_value: &str,
+) -> Result<Ordering, CheckOperatorErrorDetail> {
+    let data = DateTime::parse_from_rfc3339(data)
+        .map_err(CheckOperatorErrorDetail::EntityAttrNotATimestamp)?;
+    let reference_value = DateTime::parse_from_rfc3339(reference_value)
+        .map_err(CheckOperatorErrorDetail::ReferenceValueNotATimestamp)?;
+    Ok(data.cmp(&reference_value))
+}
+
+/// Whether the IPv4 or IPv6 address `data` is in the CIDR block
+/// `reference_value`, like `10.0.0.0/8` or `2001:db8::/32`.
+fn ip_in_cidr(data: &str, reference_value: &str) -> Result<bool, CheckOperatorErrorDetail> {
+    let network = reference_value
+        .trim()
+        .parse::<IpNet>()
+        .map_err(CheckOperatorErrorDetail::InvalidCidr)?;
+    let address = data
+        .trim()
+        .parse::<IpAddr>()
+        .map_err(CheckOperatorErrorDetail::EntityAttrNotAnIpAddress)?;
+    Ok(network.contains(&address))
+}
+
+/// Checks `operator` for a list attribute, see [`AttrValue::StringList`].
+fn check_list_operator(
+    list: &[String],
+    operator: &str,
+    reference_value: &str,
+    cache_regex: bool,
+) -> Result<bool, CheckOperatorErrorDetail> {
+    let check_element = |element: &String| {
+        check_operator_with(
+            &AttrValue::String(element.clone()),
+            operator,
+            reference_value,
+            cache_regex,
+        )
+    };
+    match operator {
+        "is" | "isCaseInsensitive" => match list {
+            [element] => check_element(element),
+            _ => Ok(false),
+        },
+        "notEquals" => Ok(!check_list_operator(
+            list,
+            "is",
+            reference_value,
+            cache_regex,
+        )?),
+        "contains" | "in" => Ok(list.iter().any(|element| element == reference_value)),
+        "notIn" => Ok(!check_list_operator(
+            list,
+            "in",
+            reference_value,
+            cache_regex,
+        )?),
+        "containsCaseInsensitive" => Ok(list
             .iter()
-            .any(|value| check_operator(attr_value, operator, value))
-    })
+            .any(|element| element.to_lowercase() == reference_value.to_lowercase())),
+        _ => {
+            for element in list {
+                if check_element(element)? {
+                    return Ok(true);
+                }
+            }
+            Ok(false)
+        }
+    }
 }
 
-fn check_operator(attribute_value: &AttrValue, operator: &str, reference_value: &str) -> bool {
+/// Whether `attribute_value` matches `reference_value` with `operator`. The
+/// regular expression of the `matches` operator is kept in [`REGEX_CACHE`]
+/// only if `cache_regex`.
+fn check_operator_with(
+    attribute_value: &AttrValue,
+    operator: &str,
+    reference_value: &str,
+    cache_regex: bool,
+) -> Result<bool, CheckOperatorErrorDetail> {
+    if let AttrValue::Map(_) = attribute_value {
+        return Err(CheckOperatorErrorDetail::EntityAttrIsAMap);
+    }
+    if let AttrValue::StringList(list) = attribute_value {
+        if NUMERIC_OPERATORS.contains(&operator) {
+            return Err(CheckOperatorErrorDetail::EntityAttrNotANumber);
+        }
+        return check_list_operator(list, operator, reference_value, cache_regex);
+    }
     match operator {
         "is" => match attribute_value {
-            AttrValue::String(data) => *data == reference_value,
-            AttrValue::Boolean(data) => {
-                *data
-                    == reference_value
-                        .parse::<bool>()
-                        .expect("Value cannot convert into a bool.")
-            }
-            AttrValue::Numeric(data) => {
-                *data
-                    == reference_value
-                        .parse::<f64>()
-                        .expect("Value cannot convert into a number.")
+            AttrValue::String(data) => Ok(*data == reference_value),
+            AttrValue::Boolean(data) => Ok(*data == parse_bool(reference_value)?),
+            AttrValue::Numeric(data) => Ok(*data == parse_numeric(reference_value)?),
+            AttrValue::StringList(list) => {
+                check_list_operator(list, operator, reference_value, cache_regex)
             }
+            AttrValue::Map(_) => Err(CheckOperatorErrorDetail::EntityAttrIsAMap),
         },
+        "notEquals" => Ok(!check_operator_with(
+            attribute_value,
+            "is",
+            reference_value,
+            cache_regex,
+        )?),
+        // A rule with these operators lists the whole set, the attribute is
+        // compared with one element of it
+        "in" => check_operator_with(attribute_value, "is", reference_value, cache_regex),
+        "notIn" => Ok(!check_operator_with(
+            attribute_value,
+            "in",
+            reference_value,
+            cache_regex,
+        )?),
         "contains" => match attribute_value {
-            AttrValue::String(data) => data.contains(reference_value),
-            _ => panic!("Entity attribute is not a string."),
+            AttrValue::String(data) => Ok(data.contains(reference_value)),
+            _ => Err(CheckOperatorErrorDetail::StringExpected),
         },
         "startsWith" => match attribute_value {
-            AttrValue::String(data) => data.starts_with(reference_value),
-            _ => panic!("Entity attribute is not a string."),
+            AttrValue::String(data) => Ok(data.starts_with(reference_value)),
+            _ => Err(CheckOperatorErrorDetail::StringExpected),
         },
         "endsWith" => match attribute_value {
-            AttrValue::String(data) => data.ends_with(reference_value),
-            _ => panic!("Entity attribute is not a string."),
+            AttrValue::String(data) => Ok(data.ends_with(reference_value)),
+            _ => Err(CheckOperatorErrorDetail::StringExpected),
         },
-        "greaterThan" => match attribute_value {
-            AttrValue::Numeric(data) => {
-                *data
-                    > reference_value
-                        .parse()
-                        .expect("Value cannot be converted to number.")
+        "isCaseInsensitive" => match attribute_value {
+            AttrValue::String(data) => Ok(data.to_lowercase() == reference_value.to_lowercase()),
+            _ => Err(CheckOperatorErrorDetail::StringExpected),
+        },
+        "containsCaseInsensitive" => match attribute_value {
+            AttrValue::String(data) => Ok(data
+                .to_lowercase()
+                .contains(&reference_value.to_lowercase())),
+            _ => Err(CheckOperatorErrorDetail::StringExpected),
+        },
+        "startsWithCaseInsensitive" => match attribute_value {
+            AttrValue::String(data) => Ok(data
+                .to_lowercase()
+                .starts_with(&reference_value.to_lowercase())),
+            _ => Err(CheckOperatorErrorDetail::StringExpected),
+        },
+        "endsWithCaseInsensitive" => match attribute_value {
+            AttrValue::String(data) => Ok(data
+                .to_lowercase()
+                .ends_with(&reference_value.to_lowercase())),
+            _ => Err(CheckOperatorErrorDetail::StringExpected),
+        },
+        "matches" => match attribute_value {
+            AttrValue::String(data) => regex_matches(reference_value, data, cache_regex),
+            _ => Err(CheckOperatorErrorDetail::StringExpected),
+        },
+        "semverGreaterThan" => match attribute_value {
+            AttrValue::String(data) => {
+                Ok(compare_semantic_versions(data, reference_value)? == Ordering::Greater)
             }
-            _ => panic!("Entity attribute is not a number."),
+            _ => Err(CheckOperatorErrorDetail::StringExpected),
         },
-        "lesserThan" => match attribute_value {
-            AttrValue::Numeric(data) => {
-                *data
-                    < reference_value
-                        .parse()
-                        .expect("Value cannot be converted to number.")
+        "semverLessThan" => match attribute_value {
+            AttrValue::String(data) => {
+                Ok(compare_semantic_versions(data, reference_value)? == Ordering::Less)
             }
-            _ => panic!("Entity attribute is not a number."),
+            _ => Err(CheckOperatorErrorDetail::StringExpected),
         },
-        "greaterThanEquals" => match attribute_value {
-            AttrValue::Numeric(data) => {
-                *data
-                    >= reference_value
-                        .parse()
-                        .expect("Value cannot be converted to number.")
+        "semverEquals" => match attribute_value {
+            AttrValue::String(data) => {
+                Ok(compare_semantic_versions(data, reference_value)? == Ordering::Equal)
+            }
+            _ => Err(CheckOperatorErrorDetail::StringExpected),
+        },
+        "before" => match attribute_value {
+            AttrValue::String(data) => {
+                Ok(compare_timestamps(data, reference_value)? == Ordering::Less)
+            }
+            _ => Err(CheckOperatorErrorDetail::StringExpected),
+        },
+        "after" => match attribute_value {
+            AttrValue::String(data) => {
+                Ok(compare_timestamps(data, reference_value)? == Ordering::Greater)
             }
-            _ => panic!("Entity attribute is not a number."),
+            _ => Err(CheckOperatorErrorDetail::StringExpected),
+        },
+        "inCidr" => match attribute_value {
+            AttrValue::String(data) => ip_in_cidr(data, reference_value),
+            _ => Err(CheckOperatorErrorDetail::StringExpected),
+        },
+        "greaterThan" => match attribute_value {
+            AttrValue::Numeric(data) => Ok(*data > parse_numeric(reference_value)?),
+            _ => Err(CheckOperatorErrorDetail::EntityAttrNotANumber),
+        },
+        "lesserThan" => match attribute_value {
+            AttrValue::Numeric(data) => Ok(*data < parse_numeric(reference_value)?),
+            _ => Err(CheckOperatorErrorDetail::EntityAttrNotANumber),
+        },
+        "greaterThanEquals" => match attribute_value {
+            AttrValue::Numeric(data) => Ok(*data >= parse_numeric(reference_value)?),
+            _ => Err(CheckOperatorErrorDetail::EntityAttrNotANumber),
         },
         "lesserThanEquals" => match attribute_value {
+            AttrValue::Numeric(data) => Ok(*data <= parse_numeric(reference_value)?),
+            _ => Err(CheckOperatorErrorDetail::EntityAttrNotANumber),
+        },
+        "between" => match attribute_value {
             AttrValue::Numeric(data) => {
-                *data
-                    <= reference_value
-                        .parse()
-                        .expect("Value cannot be converted to number.")
+                let (min, max) = parse_range(reference_value)?;
+                Ok(min <= *data && *data <= max)
             }
-            _ => panic!("Entity attribute is not a number."),
+            _ => Err(CheckOperatorErrorDetail::EntityAttrNotANumber),
         },
-        v => {
-            panic!("{} not implemented yet", v);
-        }
+        v => Err(CheckOperatorErrorDetail::OperatorNotImplemented(
+            v.to_string(),
+        )),
     }
 }
 
@@ -149,13 +727,920 @@ pub mod tests {
         models::{ConfigValue, Segment, SegmentRule, Segments, TargetingRule},
         AttrValue,
     };
+    use rstest::rstest;
+
+    fn check_operator(
+        attribute_value: &AttrValue,
+        operator: &str,
+        reference_value: &str,
+    ) -> Result<bool, CheckOperatorErrorDetail> {
+        check_operator_with(attribute_value, operator, reference_value, true)
+    }
+
+    fn with_attributes(attributes: HashMap<String, AttrValue>) -> crate::tests::GenericEntity {
+        crate::tests::GenericEntity {
+            id: "a1".into(),
+            attributes,
+        }
+    }
+
+    #[test]
+    fn test_supported_operators_are_implemented() {
+        let attr_value = AttrValue::from("heinz");
+        for operator in SUPPORTED_OPERATORS {
+            if operator == "exists" || operator == "notExists" {
+                continue;
+            }
+            let result = check_operator(&attr_value, operator, "heinz");
+            assert!(
+                !matches!(
+                    result,
+                    Err(CheckOperatorErrorDetail::OperatorNotImplemented(_))
+                ),
+                "{operator}"
+            );
+        }
+        assert!(matches!(
+            check_operator(&attr_value, "equals", "heinz"),
+            Err(CheckOperatorErrorDetail::OperatorNotImplemented(_))
+        ));
+    }
+
+    #[rstest]
+    #[case(AttrValue::String("heinz".into()), "heinz", true)]
+    #[case(AttrValue::String("heinz".into()), "heinzz", false)]
+    #[case(AttrValue::Boolean(true), "true", true)]
+    #[case(AttrValue::Boolean(true), "false", false)]
+    #[case(AttrValue::from(42.0), "42", true)]
+    #[case(AttrValue::from(42.0), "42.5", false)]
+    fn test_operator_is_and_not_equals(
+        #[case] attr_value: AttrValue,
+        #[case] reference_value: &str,
+        #[case] expected: bool,
+    ) {
+        assert_eq!(
+            check_operator(&attr_value, "is", reference_value).unwrap(),
+            expected
+        );
+        assert_eq!(
+            check_operator(&attr_value, "notEquals", reference_value).unwrap(),
+            !expected
+        );
+    }
+
+    #[rstest]
+    #[case("True", true)]
+    #[case("1", true)]
+    #[case("YES", true)]
+    #[case("no", false)]
+    #[case("Off", false)]
+    #[case("0", false)]
+    fn test_operator_is_boolean_reference_values(
+        #[case] reference_value: &str,
+        #[case] expected: bool,
+    ) {
+        assert_eq!(
+            check_operator(&AttrValue::Boolean(true), "is", reference_value).unwrap(),
+            expected
+        );
+        assert_eq!(
+            check_operator(&AttrValue::Boolean(false), "is", reference_value).unwrap(),
+            !expected
+        );
+    }
+
+    #[test]
+    fn test_operator_is_invalid_boolean_reference_value() {
+        let result = check_operator(&AttrValue::Boolean(true), "is", "maybe");
+        assert!(matches!(
+            result,
+            Err(CheckOperatorErrorDetail::BooleanExpected(_))
+        ));
+    }
+
+    #[rstest]
+    #[case("contains", vec!["admin", "billing"], "billing", true)]
+    #[case("contains", vec!["admin", "billing"], "bill", false)]
+    #[case("containsCaseInsensitive", vec!["admin", "billing"], "BILLING", true)]
+    #[case("is", vec!["admin"], "admin", true)]
+    #[case("is", vec!["admin", "billing"], "admin", false)]
+    #[case("is", vec![], "admin", false)]
+    #[case("isCaseInsensitive", vec!["Admin"], "admin", true)]
+    #[case("notEquals", vec!["admin", "billing"], "admin", true)]
+    #[case("notEquals", vec!["admin"], "admin", false)]
+    #[case("startsWith", vec!["admin", "billing"], "bil", true)]
+    #[case("endsWith", vec!["admin", "billing"], "xyz", false)]
+    #[case("matches", vec!["admin", "billing"], "^b.*g$", true)]
+    fn test_list_operators(
+        #[case] operator: &str,
+        #[case] list: Vec<&str>,
+        #[case] reference_value: &str,
+        #[case] expected: bool,
+    ) {
+        assert_eq!(
+            check_operator(&AttrValue::from(list), operator, reference_value).unwrap(),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case(AttrValue::from(18), "18,65", true)]
+    #[case(AttrValue::from(65), "18,65", true)]
+    #[case(AttrValue::from(17), "18,65", false)]
+    #[case(AttrValue::from(66), "18,65", false)]
+    #[case(AttrValue::from(40.5), "[18, 65]", true)]
+    #[case(AttrValue::from(65.1), "[18,65]", false)]
+    #[case(AttrValue::from(-5), "-10,0", true)]
+    fn test_operator_between(
+        #[case] attr_value: AttrValue,
+        #[case] reference_value: &str,
+        #[case] expected: bool,
+    ) {
+        assert_eq!(
+            check_operator(&attr_value, "between", reference_value).unwrap(),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case("18")]
+    #[case("18,")]
+    #[case("a,b")]
+    #[case("18,65,70")]
+    #[case("[18,65")]
+    #[case("65,18")]
+    fn test_operator_between_invalid_range(#[case] reference_value: &str) {
+        let result = check_operator(&AttrValue::from(20), "between", reference_value);
+        assert!(
+            matches!(result, Err(CheckOperatorErrorDetail::InvalidRange(ref v)) if v == reference_value)
+        );
+    }
+
+    #[rstest]
+    #[case("2024-09-30T12:34:56Z", "before", "2024-10-01T00:00:00Z", true)]
+    #[case("2024-09-30T12:34:56Z", "after", "2024-10-01T00:00:00Z", false)]
+    // Same instant, in different offsets
+    #[case("2024-09-30T14:34:56+02:00", "before", "2024-09-30T12:34:56Z", false)]
+    #[case("2024-09-30T14:34:56+02:00", "after", "2024-09-30T12:34:56Z", false)]
+    #[case("2024-09-30T14:34:56+02:00", "after", "2024-09-30T12:34:55Z", true)]
+    #[case("2024-09-30T08:00:00-05:00", "after", "2024-09-30T12:00:00Z", true)]
+    #[case(
+        "2024-09-30T08:00:00-05:00",
+        "before",
+        "2024-09-30T15:00:00+01:00",
+        true
+    )]
+    fn test_timestamp_operators(
+        #[case] attr_value: &str,
+        #[case] operator: &str,
+        #[case] reference_value: &str,
+        #[case] expected: bool,
+    ) {
+        assert_eq!(
+            check_operator(&AttrValue::from(attr_value), operator, reference_value).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_timestamp_operators_invalid_operands() {
+        let result = check_operator(
+            &AttrValue::from("2024-09-30"),
+            "before",
+            "2024-10-01T00:00:00Z",
+        );
+        assert!(matches!(
+            result,
+            Err(CheckOperatorErrorDetail::EntityAttrNotATimestamp(_))
+        ));
+        let result = check_operator(
+            &AttrValue::from("2024-09-30T12:34:56Z"),
+            "after",
+            "yesterday",
+        );
+        assert!(matches!(
+            result,
+            Err(CheckOperatorErrorDetail::ReferenceValueNotATimestamp(_))
+        ));
+        let result = check_operator(
+            &AttrValue::from(1727699696),
+            "after",
+            "2024-10-01T00:00:00Z",
+        );
+        assert!(matches!(
+            result,
+            Err(CheckOperatorErrorDetail::StringExpected)
+        ));
+    }
+
+    #[rstest]
+    #[case("10.1.2.3", "10.0.0.0/8", true)]
+    #[case("11.1.2.3", "10.0.0.0/8", false)]
+    #[case("192.168.1.255", "192.168.1.0/24", true)]
+    #[case("192.168.2.0", "192.168.1.0/24", false)]
+    #[case("2001:db8::1", "2001:db8::/32", true)]
+    #[case("2001:db9::1", "2001:db8::/32", false)]
+    // Addresses of one family are never in networks of the other
+    #[case("10.1.2.3", "::/0", false)]
+    fn test_operator_in_cidr(
+        #[case] attr_value: &str,
+        #[case] reference_value: &str,
+        #[case] expected: bool,
+    ) {
+        assert_eq!(
+            check_operator(&AttrValue::from(attr_value), "inCidr", reference_value).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_operator_in_cidr_invalid_operands() {
+        let result = check_operator(&AttrValue::from("10.1.2.3"), "inCidr", "10.0.0.0");
+        assert!(matches!(
+            result,
+            Err(CheckOperatorErrorDetail::InvalidCidr(_))
+        ));
+        let result = check_operator(&AttrValue::from("10.1.2.3"), "inCidr", "10.0.0.0/33");
+        assert!(matches!(
+            result,
+            Err(CheckOperatorErrorDetail::InvalidCidr(_))
+        ));
+        let result = check_operator(&AttrValue::from("localhost"), "inCidr", "10.0.0.0/8");
+        assert!(matches!(
+            result,
+            Err(CheckOperatorErrorDetail::EntityAttrNotAnIpAddress(_))
+        ));
+        let result = check_operator(&AttrValue::from(10), "inCidr", "10.0.0.0/8");
+        assert!(matches!(
+            result,
+            Err(CheckOperatorErrorDetail::StringExpected)
+        ));
+    }
+
+    #[test]
+    fn test_list_numeric_operator() {
+        let result = check_operator(&AttrValue::from(vec!["1", "2"]), "greaterThan", "0");
+        assert!(matches!(
+            result,
+            Err(CheckOperatorErrorDetail::EntityAttrNotANumber)
+        ));
+    }
+
+    #[rstest]
+    #[case("isCaseInsensitive", "Bangalore", "bangalore", true)]
+    #[case("isCaseInsensitive", "BANGALORE", "Bangalore", true)]
+    #[case("isCaseInsensitive", "Bangalore", "Bangalor", false)]
+    #[case("containsCaseInsensitive", "Bangalore", "GAL", true)]
+    #[case("containsCaseInsensitive", "Bangalore", "xyz", false)]
+    #[case("startsWithCaseInsensitive", "Bangalore", "bAnG", true)]
+    #[case("startsWithCaseInsensitive", "Bangalore", "lore", false)]
+    #[case("endsWithCaseInsensitive", "Bangalore", "LoRe", true)]
+    #[case("endsWithCaseInsensitive", "Bangalore", "bang", false)]
+    fn test_case_insensitive_operators(
+        #[case] operator: &str,
+        #[case] attr_value: &str,
+        #[case] reference_value: &str,
+        #[case] expected: bool,
+    ) {
+        let attr_value = AttrValue::String(attr_value.into());
+        assert_eq!(
+            check_operator(&attr_value, operator, reference_value).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_case_sensitive_operators_reject_mixed_case() {
+        let attr_value = AttrValue::String("bangalore".into());
+        assert!(!check_operator(&attr_value, "is", "Bangalore").unwrap());
+        assert!(!check_operator(&attr_value, "startsWith", "Bang").unwrap());
+    }
+
+    #[test]
+    fn test_case_insensitive_operator_non_string_attribute() {
+        let result = check_operator(&AttrValue::from(42.0), "isCaseInsensitive", "42");
+        assert!(matches!(
+            result,
+            Err(CheckOperatorErrorDetail::StringExpected)
+        ));
+    }
+
+    #[rstest]
+    #[case("^[a-z0-9.]+@ibm\\.com$", "heinz@ibm.com", true)]
+    #[case("^[a-z0-9.]+@ibm\\.com$", "heinz@ibm.org", false)]
+    #[case("^/api/v[0-9]+/", "/api/v2/features", true)]
+    #[case("^/api/v[0-9]+/", "/web/api/v2/features", false)]
+    fn test_operator_matches(
+        #[case] reference_value: &str,
+        #[case] attr_value: &str,
+        #[case] expected: bool,
+    ) {
+        let attr_value = AttrValue::String(attr_value.into());
+        assert_eq!(
+            check_operator(&attr_value, "matches", reference_value).unwrap(),
+            expected
+        );
+        // Second evaluation is served from the regex cache
+        assert_eq!(
+            check_operator(&attr_value, "matches", reference_value).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_operator_matches_invalid_regex() {
+        let attr_value = AttrValue::String("heinz".into());
+        let result = check_operator(&attr_value, "matches", "(unclosed");
+        assert!(matches!(
+            result,
+            Err(CheckOperatorErrorDetail::InvalidRegex(_))
+        ));
+        assert!(result.unwrap_err().to_string().contains("(unclosed"));
+    }
+
+    #[test]
+    fn test_regex_cache_evicts_least_recently_used() {
+        let mut cache = RegexCache::default();
+        for i in 0..REGEX_CACHE_CAPACITY {
+            cache.get_or_compile(&format!("^{i}$")).unwrap();
+        }
+        // Using the oldest pattern again makes the second one the least recently used
+        cache.get_or_compile("^0$").unwrap();
+        cache.get_or_compile("^new$").unwrap();
+
+        assert_eq!(cache.regexes.len(), REGEX_CACHE_CAPACITY);
+        assert!(cache.regexes.contains_key("^0$"));
+        assert!(!cache.regexes.contains_key("^1$"));
+        assert!(cache.regexes.contains_key("^new$"));
+    }
+
+    #[test]
+    fn test_operator_matches_non_string_attribute() {
+        let result = check_operator(&AttrValue::Boolean(true), "matches", "true");
+        assert!(matches!(
+            result,
+            Err(CheckOperatorErrorDetail::StringExpected)
+        ));
+    }
+
+    #[rstest]
+    #[case("semverGreaterThan", "2.14.0", "2.9.0", true)]
+    #[case("semverGreaterThan", "2.9.0", "2.14.0", false)]
+    #[case("semverGreaterThan", "2.14.0", "2.14.0", false)]
+    #[case("semverGreaterThan", "2.14.0", "2.14.0-beta.1", true)]
+    #[case("semverLessThan", "2.9.0", "2.14.0", true)]
+    #[case("semverLessThan", "2.14.0", "2.14.0", false)]
+    #[case("semverLessThan", "1.0.0-alpha", "1.0.0", true)]
+    #[case("semverEquals", "2.14.0", "2.14.0", true)]
+    #[case("semverEquals", "2.14.0", "2.14.1", false)]
+    fn test_semver_operators(
+        #[case] operator: &str,
+        #[case] attr_value: &str,
+        #[case] reference_value: &str,
+        #[case] expected: bool,
+    ) {
+        let attr_value = AttrValue::String(attr_value.into());
+        assert_eq!(
+            check_operator(&attr_value, operator, reference_value).unwrap(),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case("is", "9007199254740993", true)]
+    #[case("is", "9007199254740992", false)]
+    #[case("greaterThan", "9007199254740992", true)]
+    #[case("greaterThanEquals", "9007199254740993", true)]
+    #[case("lesserThan", "9007199254740993", false)]
+    #[case("lesserThanEquals", "9007199254740992", false)]
+    #[case("greaterThan", "-1", true)]
+    fn test_numeric_operators_preserve_integer_precision(
+        #[case] operator: &str,
+        #[case] reference_value: &str,
+        #[case] expected: bool,
+    ) {
+        // 9007199254740993 (2^53 + 1) is not representable as f64
+        let attr_value = AttrValue::from(9007199254740993u64);
+        assert_eq!(
+            check_operator(&attr_value, operator, reference_value).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_numeric_operators_invalid_reference_value() {
+        let result = check_operator(&AttrValue::from(42i64), "greaterThan", "forty");
+        assert!(matches!(
+            result,
+            Err(CheckOperatorErrorDetail::NumberExpected(_))
+        ));
+    }
+
+    #[rstest]
+    #[case("greaterThan", "42", vec!["10"], true)]
+    #[case("greaterThan", "42", vec!["100"], false)]
+    #[case("lesserThanEquals", "42.5", vec!["42.5"], true)]
+    #[case("greaterThanEquals", "9007199254740993", vec!["9007199254740993"], true)]
+    fn test_numeric_string_coercion(
+        #[case] operator: &str,
+        #[case] attr_value: &str,
+        #[case] values: Vec<&str>,
+        #[case] expected: bool,
+    ) {
+        let segment = Segment {
+            name: "".into(),
+            segment_id: "".into(),
+            description: "".into(),
+            tags: None,
+            rules: vec![SegmentRule {
+                attribute_name: "radius".into(),
+                operator: operator.into(),
+                values: values.into_iter().map(String::from).collect(),
+            }],
+        };
+        let attrs = HashMap::from([("radius".into(), AttrValue::from(attr_value.to_string()))]);
+
+        let options = EvaluationOptions {
+            coerce_numeric_strings: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            belong_to_segment(&segment, &with_attributes(attrs.clone()), &options).unwrap(),
+            expected
+        );
+
+        // The strict (default) behavior rejects string attributes
+        let result = belong_to_segment(
+            &segment,
+            &with_attributes(attrs),
+            &EvaluationOptions::default(),
+        );
+        assert!(matches!(
+            result,
+            Err(SegmentEvaluationError::SegmentEvaluationFailed {
+                source: CheckOperatorErrorDetail::EntityAttrNotANumber,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_numeric_string_coercion_not_a_number() {
+        let segment = Segment {
+            name: "".into(),
+            segment_id: "".into(),
+            description: "".into(),
+            tags: None,
+            rules: vec![SegmentRule {
+                attribute_name: "radius".into(),
+                operator: "greaterThan".into(),
+                values: vec!["10".into()],
+            }],
+        };
+        let attrs = HashMap::from([("radius".into(), AttrValue::from("far".to_string()))]);
+        let options = EvaluationOptions {
+            coerce_numeric_strings: true,
+            ..Default::default()
+        };
+        let result = belong_to_segment(&segment, &with_attributes(attrs), &options);
+        assert!(matches!(
+            result,
+            Err(SegmentEvaluationError::SegmentEvaluationFailed {
+                source: CheckOperatorErrorDetail::EntityAttrNotANumber,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_semver_operators_invalid_versions() {
+        let result = check_operator(&AttrValue::String("2.14".into()), "semverEquals", "2.14.0");
+        assert!(matches!(
+            result,
+            Err(CheckOperatorErrorDetail::EntityAttrNotASemanticVersion(_))
+        ));
+
+        let result = check_operator(&AttrValue::String("2.14.0".into()), "semverEquals", "v2");
+        assert!(matches!(
+            result,
+            Err(CheckOperatorErrorDetail::ReferenceValueNotASemanticVersion(
+                _
+            ))
+        ));
+
+        let result = check_operator(&AttrValue::from(2.14), "semverGreaterThan", "2.0.0");
+        assert!(matches!(
+            result,
+            Err(CheckOperatorErrorDetail::StringExpected)
+        ));
+    }
+
+    #[test]
+    fn test_invalid_regex_in_segment_rule() {
+        let segments = HashMap::from([(
+            "some_segment_id_1".into(),
+            Arc::new(Segment {
+                name: "".into(),
+                segment_id: "some_segment_id_1".into(),
+                description: "".into(),
+                tags: None,
+                rules: vec![SegmentRule {
+                    attribute_name: "email".into(),
+                    operator: "matches".into(),
+                    values: vec!["[a-z".into()],
+                }],
+            }),
+        )]);
+        let segment_rules = vec![TargetingRule {
+            rules: vec![Segments {
+                segments: vec!["some_segment_id_1".into()],
+            }],
+            value: ConfigValue(serde_json::Value::Number((-48).into())),
+            order: 3,
+            rollout_percentage: Some(ConfigValue(serde_json::Value::Number((100).into()))),
+            weighted_values: Vec::new(),
+        }];
+        let entity = crate::tests::GenericEntity {
+            id: "a2".into(),
+            attributes: HashMap::from([(
+                "email".into(),
+                AttrValue::from("heinz@ibm.com".to_string()),
+            )]),
+        };
+        let result = find_applicable_segment_rule_for_entity(
+            ResourceKind::Feature,
+            "f1",
+            &segments,
+            &segment_rules,
+            &entity,
+            &EvaluationOptions::default(),
+        );
+        let errors::Error::EntityEvaluationError(error) = result.unwrap_err() else {
+            panic!("Expected an EntityEvaluationError");
+        };
+        assert_eq!(error.resource_kind, ResourceKind::Feature);
+        assert_eq!(error.resource_id, "f1");
+        assert_eq!(error.entity_id, "a2");
+        assert_eq!(error.order, 3);
+        assert!(error.to_string().starts_with(
+            "Failed to evaluate feature 'f1' for entity 'a2' against targeting rule '3'"
+        ));
+        assert!(matches!(
+            error.source,
+            SegmentEvaluationError::SegmentEvaluationFailed { ref operator, ref value, .. }
+                if operator == "matches" && value == "[a-z"
+        ));
+    }
+
+    #[rstest]
+    #[case("is", vec!["heinz", "karl"], "heinz", true)]
+    #[case("is", vec!["heinz", "karl"], "fritz", false)]
+    #[case("notEquals", vec!["heinz", "karl"], "heinz", false)]
+    #[case("notEquals", vec!["heinz", "karl"], "karl", false)]
+    #[case("notEquals", vec!["heinz", "karl"], "fritz", true)]
+    fn test_belong_to_segment_multiple_values(
+        #[case] operator: &str,
+        #[case] values: Vec<&str>,
+        #[case] name: &str,
+        #[case] expected: bool,
+    ) {
+        let segment = Segment {
+            name: "".into(),
+            segment_id: "".into(),
+            description: "".into(),
+            tags: None,
+            rules: vec![SegmentRule {
+                attribute_name: "name".into(),
+                operator: operator.into(),
+                values: values.into_iter().map(String::from).collect(),
+            }],
+        };
+        let attrs = HashMap::from([("name".into(), AttrValue::from(name.to_string()))]);
+        assert_eq!(
+            belong_to_segment(
+                &segment,
+                &with_attributes(attrs),
+                &EvaluationOptions::default()
+            )
+            .unwrap(),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case("in", AttrValue::from("karl"), true)]
+    #[case("in", AttrValue::from("fritz"), false)]
+    #[case("in", AttrValue::from(42), true)]
+    #[case("in", AttrValue::from(true), true)]
+    #[case("in", AttrValue::from(vec!["fritz", "karl"]), true)]
+    #[case("notIn", AttrValue::from("karl"), false)]
+    #[case("notIn", AttrValue::from("fritz"), true)]
+    #[case("notIn", AttrValue::from(43), true)]
+    #[case("notIn", AttrValue::from(false), true)]
+    #[case("notIn", AttrValue::from(vec!["fritz", "karl"]), false)]
+    #[case("notIn", AttrValue::from(vec!["fritz"]), true)]
+    fn test_in_and_not_in_operators(
+        #[case] operator: &str,
+        #[case] attr_value: AttrValue,
+        #[case] expected: bool,
+    ) {
+        let values = match attr_value {
+            AttrValue::Numeric(_) => vec!["42", "7"],
+            AttrValue::Boolean(_) => vec!["true"],
+            _ => vec!["heinz", "karl"],
+        };
+        let segment = Segment {
+            name: "".into(),
+            segment_id: "".into(),
+            description: "".into(),
+            tags: None,
+            rules: vec![SegmentRule {
+                attribute_name: "name".into(),
+                operator: operator.into(),
+                values: values.into_iter().map(String::from).collect(),
+            }],
+        };
+        let attrs = HashMap::from([("name".into(), attr_value)]);
+        assert_eq!(
+            belong_to_segment(
+                &segment,
+                &with_attributes(attrs),
+                &EvaluationOptions::default()
+            )
+            .unwrap(),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case("exists", Some(AttrValue::from("heinz")), true)]
+    #[case("exists", None, false)]
+    #[case("notExists", Some(AttrValue::from(false)), false)]
+    #[case("notExists", None, true)]
+    fn test_exists_operators(
+        #[case] operator: &str,
+        #[case] attr_value: Option<AttrValue>,
+        #[case] expected: bool,
+    ) {
+        let segment = Segment {
+            name: "".into(),
+            segment_id: "".into(),
+            description: "".into(),
+            tags: None,
+            rules: vec![
+                SegmentRule {
+                    attribute_name: "name".into(),
+                    operator: operator.into(),
+                    values: vec![],
+                },
+                SegmentRule {
+                    attribute_name: "city".into(),
+                    operator: "is".into(),
+                    values: vec!["Bangalore".into()],
+                },
+            ],
+        };
+        let mut attrs = HashMap::from([("city".into(), AttrValue::from("Bangalore"))]);
+        if let Some(attr_value) = attr_value {
+            attrs.insert("name".into(), attr_value);
+        }
+        assert_eq!(
+            belong_to_segment(
+                &segment,
+                &with_attributes(attrs),
+                &EvaluationOptions::default()
+            )
+            .unwrap(),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case("greaterThan", AttrValue::from(120), AttrValue::from(100), true)]
+    #[case("greaterThan", AttrValue::from(80), AttrValue::from(100), false)]
+    #[case("lesserThanEquals", AttrValue::from(99.5), AttrValue::from(100), true)]
+    #[case("is", AttrValue::from("Bangalore"), AttrValue::from("Bangalore"), true)]
+    #[case("is", AttrValue::from("Bangalore"), AttrValue::from("Mumbai"), false)]
+    #[case(
+        "startsWith",
+        AttrValue::from("admin@ibm.com"),
+        AttrValue::from("admin"),
+        true
+    )]
+    #[case("is", AttrValue::from(true), AttrValue::from(true), true)]
+    fn test_attribute_reference(
+        #[case] operator: &str,
+        #[case] attr_value: AttrValue,
+        #[case] referenced_value: AttrValue,
+        #[case] expected: bool,
+    ) {
+        let segment = Segment {
+            name: "".into(),
+            segment_id: "".into(),
+            description: "".into(),
+            tags: None,
+            rules: vec![SegmentRule {
+                attribute_name: "usage".into(),
+                operator: operator.into(),
+                values: vec!["$attr:quota".into()],
+            }],
+        };
+        let attrs = HashMap::from([
+            ("usage".into(), attr_value),
+            ("quota".into(), referenced_value),
+        ]);
+        assert_eq!(
+            belong_to_segment(
+                &segment,
+                &with_attributes(attrs),
+                &EvaluationOptions::default()
+            )
+            .unwrap(),
+            expected
+        );
+    }
+
+    // Patterns taken from the entity are matched, but not cached
+    #[test]
+    fn test_attribute_reference_regex_not_cached() {
+        let segment = Segment {
+            name: "".into(),
+            segment_id: "".into(),
+            description: "".into(),
+            tags: None,
+            rules: vec![SegmentRule {
+                attribute_name: "email".into(),
+                operator: "matches".into(),
+                values: vec!["$attr:pattern".into()],
+            }],
+        };
+        let pattern = "^[a-z]+@uncached-reference\\.example$";
+        let attrs = HashMap::from([
+            (
+                "email".into(),
+                AttrValue::from("heinz@uncached-reference.example"),
+            ),
+            ("pattern".into(), AttrValue::from(pattern)),
+        ]);
+        assert!(belong_to_segment(
+            &segment,
+            &with_attributes(attrs),
+            &EvaluationOptions::default()
+        )
+        .unwrap());
+        assert!(!REGEX_CACHE.lock().unwrap().regexes.contains_key(pattern));
+    }
+
+    #[test]
+    fn test_attribute_reference_invalid() {
+        let segment = Segment {
+            name: "".into(),
+            segment_id: "".into(),
+            description: "".into(),
+            tags: None,
+            rules: vec![SegmentRule {
+                attribute_name: "usage".into(),
+                operator: "greaterThan".into(),
+                values: vec!["$attr:quota".into()],
+            }],
+        };
+        let attrs = HashMap::from([("usage".into(), AttrValue::from(120))]);
+        let result = belong_to_segment(
+            &segment,
+            &with_attributes(attrs.clone()),
+            &EvaluationOptions::default(),
+        );
+        assert!(matches!(
+            result,
+            Err(SegmentEvaluationError::SegmentEvaluationFailed {
+                source: CheckOperatorErrorDetail::ReferencedAttributeNotFound(ref name),
+                ..
+            }) if name == "quota"
+        ));
+
+        let mut attrs = attrs;
+        attrs.insert("quota".into(), AttrValue::from(vec!["100"]));
+        let result = belong_to_segment(
+            &segment,
+            &with_attributes(attrs),
+            &EvaluationOptions::default(),
+        );
+        assert!(matches!(
+            result,
+            Err(SegmentEvaluationError::SegmentEvaluationFailed {
+                source: CheckOperatorErrorDetail::ReferencedAttributeNotAScalar(_),
+                ..
+            })
+        ));
+    }
+
+    // Entities can provide their attributes one by one, without a map.
+    #[test]
+    fn test_belong_to_segment_with_get_attribute() {
+        struct LookupEntity;
+        impl Entity for LookupEntity {
+            fn get_id(&self) -> String {
+                "a1".into()
+            }
+            fn get_attribute(&self, name: &str) -> Option<AttrValue> {
+                match name {
+                    "quota" => Some(AttrValue::from(10u64)),
+                    "used" => Some(AttrValue::from(5u64)),
+                    _ => None,
+                }
+            }
+        }
+        let segment = Segment {
+            name: "".into(),
+            segment_id: "".into(),
+            description: "".into(),
+            tags: None,
+            rules: vec![
+                SegmentRule {
+                    attribute_name: "quota".into(),
+                    operator: "greaterThan".into(),
+                    values: vec!["$attr:used".into()],
+                },
+                SegmentRule {
+                    attribute_name: "plan".into(),
+                    operator: "notExists".into(),
+                    values: vec![],
+                },
+            ],
+        };
+        assert!(belong_to_segment(&segment, &LookupEntity, &EvaluationOptions::default()).unwrap());
+    }
+
+    #[rstest]
+    // Flat names keep working, also if they contain dots
+    #[case("city", "Bangalore", true)]
+    #[case("address.zip", "560001", true)]
+    #[case("address.city", "Bangalore", true)]
+    #[case("address.city", "Mumbai", false)]
+    #[case("address.geo.country", "IN", true)]
+    // Paths to missing elements, or into values which are not maps
+    #[case("address.street", "MG Road", false)]
+    #[case("city.name", "Bangalore", false)]
+    fn test_nested_attributes(
+        #[case] attribute_name: &str,
+        #[case] value: &str,
+        #[case] expected: bool,
+    ) {
+        let segment = Segment {
+            name: "".into(),
+            segment_id: "".into(),
+            description: "".into(),
+            tags: None,
+            rules: vec![SegmentRule {
+                attribute_name: attribute_name.into(),
+                operator: "is".into(),
+                values: vec![value.into()],
+            }],
+        };
+        let address = HashMap::from([
+            ("city".to_string(), AttrValue::from("Bangalore")),
+            (
+                "geo".to_string(),
+                AttrValue::from(HashMap::from([(
+                    "country".to_string(),
+                    AttrValue::from("IN"),
+                )])),
+            ),
+        ]);
+        let attrs = HashMap::from([
+            ("city".to_string(), AttrValue::from("Bangalore")),
+            ("address".to_string(), AttrValue::from(address)),
+            ("address.zip".to_string(), AttrValue::from("560001")),
+        ]);
+        assert_eq!(
+            belong_to_segment(
+                &segment,
+                &with_attributes(attrs),
+                &EvaluationOptions::default()
+            )
+            .unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_map_attribute_cannot_be_compared() {
+        let attr_value = AttrValue::from(HashMap::from([(
+            "city".to_string(),
+            AttrValue::from("Bangalore"),
+        )]));
+        let result = check_operator(&attr_value, "is", "Bangalore");
+        assert!(matches!(
+            result,
+            Err(CheckOperatorErrorDetail::EntityAttrIsAMap)
+        ));
+    }
 
-    #[ignore] // This fails, probably a bug. Need to cross check expectation with go impl.
     #[test]
     fn test_missing_attribute() {
         let segments = HashMap::from([(
             "some_segment_id_1".into(),
-            Segment {
+            Arc::new(Segment {
                 name: "".into(),
                 segment_id: "".into(),
                 description: "".into(),
@@ -165,7 +1650,7 @@ pub mod tests {
                     operator: "is".into(),
                     values: vec!["heinz".into()],
                 }],
-            },
+            }),
         )]);
         let segment_rules = vec![TargetingRule {
             rules: vec![Segments {
@@ -174,13 +1659,149 @@ pub mod tests {
             value: ConfigValue(serde_json::Value::Number((-48).into())),
             order: 0,
             rollout_percentage: Some(ConfigValue(serde_json::Value::Number((100).into()))),
+            weighted_values: Vec::new(),
         }];
         let entity = crate::tests::GenericEntity {
             id: "a2".into(),
             attributes: HashMap::from([("name2".into(), AttrValue::from("heinz".to_string()))]),
         };
-        let rule =
-            find_applicable_segment_rule_for_entity(&segments, segment_rules.into_iter(), &entity);
-        assert!(rule.is_none());
+        let rule = find_applicable_segment_rule_for_entity(
+            ResourceKind::Feature,
+            "f1",
+            &segments,
+            &segment_rules,
+            &entity,
+            &EvaluationOptions::default(),
+        );
+        assert!(rule.unwrap().is_none());
+    }
+
+    #[test]
+    fn test_find_all_applicable_segment_rules() {
+        let segment = |segment_id: &str, attribute_name: &str, value: &str| {
+            (
+                segment_id.to_string(),
+                Arc::new(Segment {
+                    name: segment_id.into(),
+                    segment_id: segment_id.into(),
+                    description: "".into(),
+                    tags: None,
+                    rules: vec![SegmentRule {
+                        attribute_name: attribute_name.into(),
+                        operator: "is".into(),
+                        values: vec![value.into()],
+                    }],
+                }),
+            )
+        };
+        let segments = HashMap::from([
+            segment("by_name", "name", "heinz"),
+            segment("by_country", "country", "de"),
+            segment("by_plan", "plan", "free"),
+        ]);
+        let targeting_rule = |segment_id: &str, order: u32| TargetingRule {
+            rules: vec![Segments {
+                segments: vec![segment_id.into()],
+            }],
+            value: ConfigValue(serde_json::Value::Number(order.into())),
+            order,
+            rollout_percentage: None,
+            weighted_values: Vec::new(),
+        };
+        let segment_rules = vec![
+            targeting_rule("by_country", 2),
+            targeting_rule("by_plan", 3),
+            targeting_rule("by_name", 1),
+        ];
+        let entity = crate::tests::GenericEntity {
+            id: "a1".into(),
+            attributes: HashMap::from([
+                ("name".into(), AttrValue::from("heinz")),
+                ("country".into(), AttrValue::from("de")),
+                ("plan".into(), AttrValue::from("paid")),
+            ]),
+        };
+
+        let rules = find_all_applicable_segment_rules_for_entity(
+            ResourceKind::Feature,
+            "f1",
+            &segments,
+            &segment_rules,
+            &entity,
+            &EvaluationOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            rules.iter().map(|rule| rule.order).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+
+        let rule = find_applicable_segment_rule_for_entity(
+            ResourceKind::Feature,
+            "f1",
+            &segments,
+            &segment_rules,
+            &entity,
+            &EvaluationOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(rule, Some(&rules[0]));
+    }
+
+    // A segment shared by several targeting rules, of one or several
+    // features, is only evaluated once per entity with a memo.
+    #[test]
+    fn test_segment_memo() {
+        struct CountingEntity(std::cell::Cell<usize>);
+        impl Entity for CountingEntity {
+            fn get_id(&self) -> String {
+                "a1".into()
+            }
+            fn get_attributes(&self) -> HashMap<String, AttrValue> {
+                self.0.set(self.0.get() + 1);
+                HashMap::from([("name".into(), AttrValue::from("heinz"))])
+            }
+        }
+        let segments = HashMap::from([(
+            "by_plan".to_string(),
+            Arc::new(Segment {
+                name: "".into(),
+                segment_id: "by_plan".into(),
+                description: "".into(),
+                tags: None,
+                rules: vec![SegmentRule {
+                    attribute_name: "plan".into(),
+                    operator: "exists".into(),
+                    values: vec![],
+                }],
+            }),
+        )]);
+        let targeting_rule = |order: u32| TargetingRule {
+            rules: vec![Segments {
+                segments: vec!["by_plan".into()],
+            }],
+            value: ConfigValue(serde_json::json!(order)),
+            order,
+            rollout_percentage: None,
+            weighted_values: Vec::new(),
+        };
+        let segment_rules = vec![targeting_rule(1), targeting_rule(2)];
+        let entity = CountingEntity(std::cell::Cell::new(0));
+        let memo = SegmentMemo::default();
+
+        for feature_id in ["f1", "f2"] {
+            let rule = find_applicable_segment_rule_for_entity_memoized(
+                ResourceKind::Feature,
+                feature_id,
+                &segments,
+                &segment_rules,
+                &entity,
+                &EvaluationOptions::default(),
+                &memo,
+            )
+            .unwrap();
+            assert_eq!(rule, None);
+        }
+        assert_eq!(entity.0.get(), 1);
     }
 }
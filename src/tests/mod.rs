@@ -14,10 +14,12 @@
 
 use std::collections::HashMap;
 
+mod test_from_file;
 mod test_get_feature;
 mod test_get_feature_ids;
 mod test_get_property;
 mod test_get_property_ids;
+mod test_segments;
 mod test_using_example_data;
 
 use crate::client::cache::ConfigurationSnapshot;
@@ -26,7 +28,6 @@ use crate::models::tests::example_configuration_enterprise;
 use crate::models::Configuration;
 use crate::{AttrValue, Entity};
 use rstest::fixture;
-use std::sync::{Arc, Mutex};
 
 pub struct TrivialEntity;
 
@@ -53,18 +54,17 @@ impl Entity for GenericEntity {
     fn get_attributes(&self) -> HashMap<String, AttrValue> {
         self.attributes.clone()
     }
+
+    fn get_attribute(&self, name: &str) -> Option<AttrValue> {
+        self.attributes.get(name).cloned()
+    }
 }
 
 #[fixture]
 fn client_enterprise(example_configuration_enterprise: Configuration) -> AppConfigurationClient {
     let configuration_snapshot =
-        ConfigurationSnapshot::new("dev", example_configuration_enterprise).unwrap();
+        ConfigurationSnapshot::new("dev", None, example_configuration_enterprise).unwrap();
 
     // Create the client
-    let (sender, _) = std::sync::mpsc::channel();
-
-    AppConfigurationClient {
-        latest_config_snapshot: Arc::new(Mutex::new(configuration_snapshot)),
-        _thread_terminator: sender,
-    }
+    AppConfigurationClient::new_offline(configuration_snapshot)
 }
@@ -0,0 +1,261 @@
+// (C) Copyright IBM Corp. 2024.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::client::value::Value;
+use crate::client::{evaluate_feature, evaluate_property, AppConfigurationClient};
+use crate::errors::{ConfigurationAccessError, Error};
+use crate::AttrValue;
+
+fn enterprise_data_dump() -> PathBuf {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("data/data-dump-enterprise-plan-sdk-testing.json");
+    path
+}
+
+#[test]
+fn test_from_file() {
+    let client =
+        AppConfigurationClient::from_file(&enterprise_data_dump(), "dev", "blue-charge").unwrap();
+
+    let mut features = client.get_feature_ids().unwrap();
+    features.sort();
+    assert_eq!(features, vec!["f1", "f2", "f3", "f4", "f6"]);
+
+    let entity = super::TrivialEntity {};
+    let feature = client.get_feature("f1").unwrap();
+    let value = feature.get_value(&entity).unwrap();
+    assert!(matches!(value, Value::Numeric(ref v) if v.as_i64().unwrap() == 5));
+
+    let property = client.get_property_proxy("p1").unwrap();
+    assert_eq!(property.get_value().unwrap().as_u64().unwrap(), 5);
+}
+
+#[test]
+fn test_from_file_filters_collection() {
+    // The dump spans the collections blue-charge, ci-pipeline and my-dashboard
+    let client =
+        AppConfigurationClient::from_file(&enterprise_data_dump(), "dev", "my-dashboard").unwrap();
+
+    let mut features = client.get_feature_ids().unwrap();
+    features.sort();
+    assert_eq!(features, vec!["f5", "f6"]);
+    let mut properties = client.get_property_ids().unwrap();
+    properties.sort();
+    assert_eq!(properties, vec!["p3", "p4"]);
+
+    let client =
+        AppConfigurationClient::from_file(&enterprise_data_dump(), "dev", "car-rentals").unwrap();
+    assert!(client.get_feature_ids().unwrap().is_empty());
+}
+
+#[test]
+fn test_from_file_environment_not_found() {
+    let result =
+        AppConfigurationClient::from_file(&enterprise_data_dump(), "does-not-exist", "car-rentals");
+    assert!(matches!(
+        result.unwrap_err(),
+        Error::ConfigurationAccessError(ConfigurationAccessError::EnvironmentNotFound { ref environment_id })
+        if environment_id == "does-not-exist"
+    ));
+}
+
+#[test]
+fn test_set_environment() {
+    let client =
+        AppConfigurationClient::from_file(&enterprise_data_dump(), "dev", "blue-charge").unwrap();
+    let entity = super::TrivialEntity {};
+    let f1_value = |client: &AppConfigurationClient| {
+        let value = client
+            .get_feature("f1")
+            .unwrap()
+            .get_value(&entity)
+            .unwrap();
+        let Value::Numeric(value) = value else {
+            panic!("Expected a numeric value");
+        };
+        value.as_i64().unwrap()
+    };
+
+    client.set_environment("prod").unwrap();
+    assert_eq!(client.environment_id(), "prod");
+    assert_eq!(f1_value(&client), -1);
+
+    let result = client.set_environment("does-not-exist");
+    assert!(matches!(
+        result.unwrap_err(),
+        Error::ConfigurationAccessError(ConfigurationAccessError::EnvironmentNotFound { ref environment_id })
+        if environment_id == "does-not-exist"
+    ));
+    assert_eq!(client.environment_id(), "prod");
+
+    client.set_environment("stage").unwrap();
+    assert_eq!(f1_value(&client), 1);
+    client.set_environment("dev").unwrap();
+    assert_eq!(f1_value(&client), 5);
+}
+
+#[test]
+fn test_set_environment_shared_by_clones() {
+    let client =
+        AppConfigurationClient::from_file(&enterprise_data_dump(), "dev", "blue-charge").unwrap();
+    let clone = client.clone();
+    let notified = Arc::new(Mutex::new(Vec::new()));
+    let notified_in_callback = notified.clone();
+    client
+        .on_configuration_update(move |update| {
+            notified_in_callback
+                .lock()
+                .unwrap()
+                .push((thread::current().id(), update.feature_ids.len()));
+        })
+        .unwrap();
+
+    clone.set_environment("prod").unwrap();
+    assert_eq!(client.environment_id(), "prod");
+    assert_eq!(clone.environment_id(), "prod");
+    // Callbacks are called synchronously, on the thread switching
+    let notified = notified.lock().unwrap();
+    assert_eq!(notified.len(), 1);
+    assert_eq!(notified[0].0, thread::current().id());
+    assert_eq!(notified[0].1, client.get_feature_ids().unwrap().len());
+}
+
+#[test]
+fn test_from_file_missing_file() {
+    let result = AppConfigurationClient::from_file(
+        &PathBuf::from("does/not/exist.json"),
+        "dev",
+        "car-rentals",
+    );
+    assert!(matches!(result.unwrap_err(), Error::IoError(_)));
+}
+
+#[test]
+fn test_from_file_invalid_content() {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("Cargo.toml");
+    let result = AppConfigurationClient::from_file(&path, "dev", "car-rentals");
+    assert!(matches!(
+        result.unwrap_err(),
+        Error::DeserializationError(_)
+    ));
+}
+
+#[test]
+fn test_from_configuration_json() {
+    let json = r#"{
+        "environments": [{
+            "name": "Dev",
+            "environment_id": "dev",
+            "features": [{
+                "name": "F1",
+                "feature_id": "f1",
+                "type": "NUMERIC",
+                "enabled_value": 5,
+                "disabled_value": 0,
+                "segment_rules": [{
+                    "rules": [{"segments": ["s1"]}],
+                    "value": 40,
+                    "order": 1,
+                    "rollout_percentage": 100
+                }],
+                "enabled": true,
+                "rollout_percentage": 100
+            }],
+            "properties": []
+        }],
+        "segments": [{
+            "name": "S1",
+            "segment_id": "s1",
+            "description": "",
+            "rules": [{"attribute_name": "name", "operator": "is", "values": ["heinz"]}]
+        }]
+    }"#;
+    let client =
+        AppConfigurationClient::from_configuration_json(json, "dev", "car-rentals").unwrap();
+    let feature = client.get_feature("f1").unwrap();
+
+    let entity = super::GenericEntity {
+        id: "a1".into(),
+        attributes: HashMap::from([("name".into(), AttrValue::from("heinz".to_string()))]),
+    };
+    let value = feature.get_value(&entity).unwrap();
+    assert!(matches!(value, Value::Numeric(ref v) if v.as_i64().unwrap() == 40));
+
+    let value = feature.get_value(&super::TrivialEntity {}).unwrap();
+    assert!(matches!(value, Value::Numeric(ref v) if v.as_i64().unwrap() == 5));
+}
+
+#[test]
+fn test_from_configuration_json_invalid_content() {
+    let result = AppConfigurationClient::from_configuration_json("{}", "dev", "car-rentals");
+    assert!(matches!(
+        result.unwrap_err(),
+        Error::UnsupportedConfigurationSchema { .. }
+    ));
+
+    let result = AppConfigurationClient::from_configuration_json("[]", "dev", "car-rentals");
+    assert!(matches!(
+        result.unwrap_err(),
+        Error::DeserializationError(_)
+    ));
+}
+
+#[test]
+fn test_evaluate_without_client() {
+    let path = enterprise_data_dump();
+    let json = std::fs::read_to_string(&path).unwrap();
+    let client = AppConfigurationClient::from_file(&path, "dev", "blue-charge").unwrap();
+    let entity = super::GenericEntity {
+        id: "a1".into(),
+        attributes: HashMap::from([("name".into(), AttrValue::from("heinz".to_string()))]),
+    };
+
+    let value = evaluate_feature(&json, "dev", "f1", &entity).unwrap();
+    let expected = client
+        .get_feature("f1")
+        .unwrap()
+        .get_value(&entity)
+        .unwrap();
+    assert_eq!(value, expected);
+    let value = evaluate_property(&json, "dev", "p1", &entity).unwrap();
+    let expected = client
+        .get_property("p1")
+        .unwrap()
+        .get_value(&entity)
+        .unwrap();
+    assert_eq!(value, expected);
+
+    assert!(matches!(
+        evaluate_feature(&json, "dev", "missing", &entity),
+        Err(Error::ConfigurationAccessError(
+            ConfigurationAccessError::FeatureNotFound { .. }
+        ))
+    ));
+    assert!(matches!(
+        evaluate_property(&json, "qa", "p1", &entity),
+        Err(Error::ConfigurationAccessError(
+            ConfigurationAccessError::EnvironmentNotFound { .. }
+        ))
+    ));
+    assert!(matches!(
+        evaluate_feature("{}", "dev", "f1", &entity),
+        Err(Error::UnsupportedConfigurationSchema { .. })
+    ));
+}
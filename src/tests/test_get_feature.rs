@@ -12,14 +12,18 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::models::Configuration;
+use crate::models::{ConfigValue, Configuration, ValueKind};
 
 use crate::client::cache::ConfigurationSnapshot;
 use crate::client::AppConfigurationClient;
+use crate::errors::{ConfigurationAccessError, Error, ResourceKind};
+use crate::AttrValue;
 use rstest::*;
+use std::collections::HashMap;
+use std::sync::Arc;
 
 use super::client_enterprise;
-use crate::models::tests::configuration_feature1_enabled;
+use crate::models::tests::{configuration_feature1_enabled, example_configuration_enterprise};
 
 #[rstest]
 fn test_get_feature_persistence(
@@ -33,8 +37,8 @@ fn test_get_feature_persistence(
 
     // We simulate an update of the configuration:
     let configuration_snapshot =
-        ConfigurationSnapshot::new("environment_id", configuration_feature1_enabled).unwrap();
-    *client_enterprise.latest_config_snapshot.lock().unwrap() = configuration_snapshot;
+        ConfigurationSnapshot::new("environment_id", None, configuration_feature1_enabled).unwrap();
+    *client_enterprise.latest_config_snapshot.write().unwrap() = configuration_snapshot;
     // The feature value should not have changed (as we did not retrieve it again)
     let feature_value2 = feature.get_value(&entity).unwrap();
     assert_eq!(feature_value2, feature_value1);
@@ -46,6 +50,32 @@ fn test_get_feature_persistence(
     assert_ne!(feature_value3, feature_value1);
 }
 
+#[rstest]
+fn test_refresh_feature(
+    client_enterprise: AppConfigurationClient,
+    configuration_feature1_enabled: Configuration,
+) {
+    let mut feature = client_enterprise.get_feature("f1").unwrap();
+    let entity = super::TrivialEntity {};
+    let feature_value1 = feature.get_value(&entity).unwrap();
+
+    let configuration_snapshot =
+        ConfigurationSnapshot::new("environment_id", None, configuration_feature1_enabled).unwrap();
+    *client_enterprise.latest_config_snapshot.write().unwrap() = configuration_snapshot;
+    feature.refresh(&client_enterprise).unwrap();
+    assert_ne!(feature.get_value(&entity).unwrap(), feature_value1);
+
+    // A feature which no longer exists is left unchanged
+    client_enterprise
+        .latest_config_snapshot
+        .write()
+        .unwrap()
+        .features
+        .clear();
+    assert!(feature.refresh(&client_enterprise).is_err());
+    assert!(feature.get_value(&entity).is_ok());
+}
+
 #[rstest]
 fn test_get_feature_doesnt_exist(client_enterprise: AppConfigurationClient) {
     let feature = client_enterprise.get_feature("non-existing");
@@ -55,3 +85,215 @@ fn test_get_feature_doesnt_exist(client_enterprise: AppConfigurationClient) {
         "Feature `non-existing` not found."
     );
 }
+
+#[rstest]
+fn test_get_features(client_enterprise: AppConfigurationClient) {
+    let features = client_enterprise.get_features(&["f3", "f1", "f2"]).unwrap();
+    assert_eq!(features.len(), 3);
+
+    let entity = super::TrivialEntity {};
+    for (feature, feature_id) in features.iter().zip(["f3", "f1", "f2"]) {
+        let expected = client_enterprise.get_feature(feature_id).unwrap();
+        assert_eq!(
+            feature.get_value(&entity).unwrap(),
+            expected.get_value(&entity).unwrap()
+        );
+    }
+}
+
+#[rstest]
+fn test_get_features_one_doesnt_exist(client_enterprise: AppConfigurationClient) {
+    let features = client_enterprise.get_features(&["f1", "non-existing"]);
+    assert_eq!(
+        features.unwrap_err().to_string(),
+        "Feature `non-existing` not found."
+    );
+}
+
+#[rstest]
+fn test_get_values(client_enterprise: AppConfigurationClient) {
+    let feature = client_enterprise.get_feature("f1").unwrap();
+    let entities = ["a1", "a2", "a3"].map(|id| super::GenericEntity {
+        id: id.to_string(),
+        attributes: HashMap::new(),
+    });
+    let values = feature.get_values(&entities.each_ref());
+    assert_eq!(values.len(), 3);
+    for (value, entity) in values.into_iter().zip(&entities) {
+        assert_eq!(value.unwrap(), feature.get_value(entity).unwrap());
+    }
+    let no_entities: [&super::TrivialEntity; 0] = [];
+    assert!(feature.get_values(&no_entities).is_empty());
+}
+
+#[rstest]
+fn test_evaluate_all_features(client_enterprise: AppConfigurationClient) {
+    let entity = crate::tests::GenericEntity {
+        id: "a2".into(),
+        attributes: HashMap::from([("name".into(), AttrValue::from("heinz".to_string()))]),
+    };
+    let values = client_enterprise.evaluate_all_features(&entity).unwrap();
+
+    let mut feature_ids = values.keys().cloned().collect::<Vec<_>>();
+    feature_ids.sort();
+    let mut expected_ids = client_enterprise.get_feature_ids().unwrap();
+    expected_ids.sort();
+    assert_eq!(feature_ids, expected_ids);
+    for (feature_id, value) in values {
+        let feature = client_enterprise.get_feature(&feature_id).unwrap();
+        assert_eq!(value, feature.get_value(&entity).unwrap(), "{feature_id}");
+    }
+}
+
+#[rstest]
+fn test_get_feature_typed_values(client_enterprise: AppConfigurationClient) {
+    let entity = super::TrivialEntity {};
+
+    let feature = client_enterprise.get_feature("f1").unwrap();
+    assert!(feature.get_value_as_i64(&entity).is_ok());
+    assert!(matches!(
+        feature.get_value_as_bool(&entity),
+        Err(Error::ConfigurationAccessError(ConfigurationAccessError::FeatureTypeMismatch {
+            ref feature_id,
+            expected: ValueKind::Boolean,
+            actual: ValueKind::Numeric,
+        })) if feature_id == "f1"
+    ));
+
+    let feature = client_enterprise.get_feature("f2").unwrap();
+    assert!(feature.get_value_as_string(&entity).is_ok());
+    assert!(matches!(
+        feature.get_value_as_i64(&entity),
+        Err(Error::ConfigurationAccessError(ConfigurationAccessError::FeatureTypeMismatch {
+            ref feature_id,
+            expected: ValueKind::Numeric,
+            actual: ValueKind::String,
+        })) if feature_id == "f2"
+    ));
+
+    let feature = client_enterprise.get_feature("f3").unwrap();
+    assert!(feature.get_value_as_bool(&entity).is_ok());
+    assert!(matches!(
+        feature.get_value_as_string(&entity),
+        Err(Error::ConfigurationAccessError(ConfigurationAccessError::FeatureTypeMismatch {
+            ref feature_id,
+            expected: ValueKind::String,
+            actual: ValueKind::Boolean,
+        })) if feature_id == "f3"
+    ));
+}
+
+#[rstest]
+fn test_get_typed_features(client_enterprise: AppConfigurationClient) {
+    let entity = super::TrivialEntity {};
+
+    let feature = client_enterprise.get_numeric_feature("f1").unwrap();
+    assert_eq!(feature.get_value(&entity).unwrap().as_i64(), Some(5));
+    let feature = client_enterprise.get_string_feature("f2").unwrap();
+    assert_eq!(feature.get_value(&entity).unwrap(), "inherited-enabled");
+    let feature = client_enterprise.get_bool_feature("f3").unwrap();
+    assert!(feature.get_value(&entity).unwrap());
+    assert_eq!(feature.feature().get_data_type(), ValueKind::Boolean);
+
+    let result = client_enterprise.get_bool_feature("f1");
+    assert!(matches!(
+        result,
+        Err(Error::ConfigurationAccessError(ConfigurationAccessError::FeatureTypeMismatch {
+            ref feature_id,
+            expected: ValueKind::Boolean,
+            actual: ValueKind::Numeric,
+        })) if feature_id == "f1"
+    ));
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "Feature `f1` is of type NUMERIC, not BOOLEAN."
+    );
+}
+
+#[rstest]
+fn test_get_typed_values_from_client(client_enterprise: AppConfigurationClient) {
+    let entity = super::TrivialEntity {};
+
+    assert!(client_enterprise.get_bool("f3", &entity).unwrap());
+    assert_eq!(
+        client_enterprise.get_string("f2", &entity).unwrap(),
+        "inherited-enabled"
+    );
+    assert_eq!(client_enterprise.get_numeric_i64("f1", &entity).unwrap(), 5);
+    assert_eq!(
+        client_enterprise.get_numeric_f64("f1", &entity).unwrap(),
+        5.0
+    );
+
+    assert!(matches!(
+        client_enterprise.get_bool("f2", &entity),
+        Err(Error::ConfigurationAccessError(ConfigurationAccessError::FeatureTypeMismatch {
+            ref feature_id,
+            expected: ValueKind::Boolean,
+            actual: ValueKind::String,
+        })) if feature_id == "f2"
+    ));
+    assert!(matches!(
+        client_enterprise.get_numeric_i64("f3", &entity),
+        Err(Error::ConfigurationAccessError(
+            ConfigurationAccessError::FeatureTypeMismatch { .. }
+        ))
+    ));
+    assert!(matches!(
+        client_enterprise.get_string("missing", &entity),
+        Err(Error::ConfigurationAccessError(
+            ConfigurationAccessError::FeatureNotFound { .. }
+        ))
+    ));
+}
+
+#[rstest]
+fn test_get_numeric_i64_of_float_value(mut example_configuration_enterprise: Configuration) {
+    let feature = &mut example_configuration_enterprise.environments[0].features[0];
+    assert_eq!(feature.feature_id, "f1");
+    feature.enabled_value = ConfigValue(serde_json::json!(2.5));
+    let snapshot =
+        ConfigurationSnapshot::new("dev", None, example_configuration_enterprise).unwrap();
+    let client = AppConfigurationClient::new_offline(snapshot);
+    let entity = super::TrivialEntity {};
+
+    assert_eq!(client.get_numeric_f64("f1", &entity).unwrap(), 2.5);
+    let result = client.get_numeric_i64("f1", &entity);
+    assert!(matches!(
+        result,
+        Err(Error::NumericValueOutOfRange { target: "i64", ref resource_id }) if resource_id == "f1"
+    ));
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "Value of 'f1' cannot be represented as i64"
+    );
+}
+
+#[rstest]
+fn test_get_feature_by_name(client_enterprise: AppConfigurationClient) {
+    let entity = super::TrivialEntity {};
+    let feature = client_enterprise.get_feature_by_name("F2").unwrap();
+    assert_eq!(
+        feature.get_value_as_string(&entity).unwrap(),
+        "inherited-enabled"
+    );
+
+    let result = client_enterprise.get_feature_by_name("f2");
+    assert!(matches!(
+        result,
+        Err(Error::ConfigurationAccessError(ConfigurationAccessError::NameNotFound {
+            resource_kind: ResourceKind::Feature,
+            ref name,
+        })) if name == "f2"
+    ));
+
+    // Names aren't unique
+    let mut snapshot = client_enterprise.latest_config_snapshot.write().unwrap();
+    Arc::make_mut(snapshot.features.get_mut("f3").unwrap()).name = "F2".to_string();
+    drop(snapshot);
+    let result = client_enterprise.get_feature_by_name("F2");
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "Several feature ids share the name `F2`: f2, f3."
+    );
+}
@@ -15,6 +15,7 @@
 use super::client_enterprise;
 use crate::client::AppConfigurationClient;
 use rstest::*;
+use std::sync::Arc;
 
 #[rstest]
 fn test_get_feature_ids(client_enterprise: AppConfigurationClient) {
@@ -32,3 +33,22 @@ fn test_get_feature_ids(client_enterprise: AppConfigurationClient) {
         ]
     );
 }
+
+#[rstest]
+fn test_get_feature_ids_by_tag(client_enterprise: AppConfigurationClient) {
+    {
+        let mut snapshot = client_enterprise.latest_config_snapshot.write().unwrap();
+        Arc::make_mut(snapshot.features.get_mut("f2").unwrap()).tags = Some("team-a,team-b".into());
+        Arc::make_mut(snapshot.features.get_mut("f5").unwrap()).tags = Some("team-b".into());
+    }
+
+    let mut features = client_enterprise.get_feature_ids_by_tag("team-b").unwrap();
+    features.sort();
+    assert_eq!(features, vec!["f2".to_string(), "f5".to_string()]);
+
+    let features = client_enterprise.get_feature_ids_by_tag("team-a").unwrap();
+    assert_eq!(features, vec!["f2".to_string()]);
+
+    let features = client_enterprise.get_feature_ids_by_tag("team-c").unwrap();
+    assert!(features.is_empty());
+}
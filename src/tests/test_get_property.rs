@@ -12,10 +12,11 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::models::Configuration;
+use crate::models::{Configuration, ValueKind};
 
 use crate::client::cache::ConfigurationSnapshot;
 use crate::client::AppConfigurationClient;
+use crate::errors::{ConfigurationAccessError, Error, ResourceKind};
 use rstest::*;
 
 use super::client_enterprise;
@@ -33,8 +34,9 @@ fn test_get_property_persistence(
 
     // We simulate an update of the configuration:
     let configuration_snapshot =
-        ConfigurationSnapshot::new("environment_id", configuration_property1_enabled).unwrap();
-    *client_enterprise.latest_config_snapshot.lock().unwrap() = configuration_snapshot;
+        ConfigurationSnapshot::new("environment_id", None, configuration_property1_enabled)
+            .unwrap();
+    *client_enterprise.latest_config_snapshot.write().unwrap() = configuration_snapshot;
     // The property value should not have changed (as we did not retrieve it again)
     let property_value2 = property.get_value(&entity).unwrap();
     assert_eq!(property_value2, property_value1);
@@ -46,6 +48,33 @@ fn test_get_property_persistence(
     assert_ne!(property_value3, property_value1);
 }
 
+#[rstest]
+fn test_refresh_property(
+    client_enterprise: AppConfigurationClient,
+    configuration_property1_enabled: Configuration,
+) {
+    let mut property = client_enterprise.get_property("p1").unwrap();
+    let entity = super::TrivialEntity {};
+    let property_value1 = property.get_value(&entity).unwrap();
+
+    let configuration_snapshot =
+        ConfigurationSnapshot::new("environment_id", None, configuration_property1_enabled)
+            .unwrap();
+    *client_enterprise.latest_config_snapshot.write().unwrap() = configuration_snapshot;
+    property.refresh(&client_enterprise).unwrap();
+    assert_ne!(property.get_value(&entity).unwrap(), property_value1);
+
+    // A property which no longer exists is left unchanged
+    client_enterprise
+        .latest_config_snapshot
+        .write()
+        .unwrap()
+        .properties
+        .clear();
+    assert!(property.refresh(&client_enterprise).is_err());
+    assert!(property.get_value(&entity).is_ok());
+}
+
 #[rstest]
 fn test_get_property_doesnt_exist(client_enterprise: AppConfigurationClient) {
     let property = client_enterprise.get_property("non-existing");
@@ -55,3 +84,64 @@ fn test_get_property_doesnt_exist(client_enterprise: AppConfigurationClient) {
         "Property `non-existing` not found."
     );
 }
+
+#[rstest]
+fn test_get_property_typed_values(client_enterprise: AppConfigurationClient) {
+    let entity = super::TrivialEntity {};
+
+    let property = client_enterprise.get_property("p1").unwrap();
+    assert_eq!(property.get_value_as_i64(&entity).unwrap(), 5);
+    assert!(matches!(
+        property.get_value_as_string(&entity),
+        Err(Error::ConfigurationAccessError(ConfigurationAccessError::PropertyTypeMismatch {
+            ref property_id,
+            expected: ValueKind::String,
+            actual: ValueKind::Numeric,
+        })) if property_id == "p1"
+    ));
+
+    let property = client_enterprise.get_property("p2").unwrap();
+    assert_eq!(
+        property.get_value_as_string(&entity).unwrap(),
+        "inherited-property"
+    );
+    assert!(matches!(
+        property.get_value_as_bool(&entity),
+        Err(Error::ConfigurationAccessError(ConfigurationAccessError::PropertyTypeMismatch {
+            ref property_id,
+            expected: ValueKind::Boolean,
+            actual: ValueKind::String,
+        })) if property_id == "p2"
+    ));
+
+    let property = client_enterprise.get_property("p3").unwrap();
+    assert!(property.get_value_as_bool(&entity).unwrap());
+    assert!(matches!(
+        property.get_value_as_i64(&entity),
+        Err(Error::ConfigurationAccessError(ConfigurationAccessError::PropertyTypeMismatch {
+            ref property_id,
+            expected: ValueKind::Numeric,
+            actual: ValueKind::Boolean,
+        })) if property_id == "p3"
+    ));
+}
+
+#[rstest]
+fn test_get_property_by_name(client_enterprise: AppConfigurationClient) {
+    let entity = super::TrivialEntity {};
+    let property = client_enterprise.get_property_by_name("p2").unwrap();
+    let expected = client_enterprise.get_property("p2").unwrap();
+    assert_eq!(
+        property.get_value(&entity).unwrap(),
+        expected.get_value(&entity).unwrap()
+    );
+
+    let result = client_enterprise.get_property_by_name("P2");
+    assert!(matches!(
+        result,
+        Err(Error::ConfigurationAccessError(ConfigurationAccessError::NameNotFound {
+            resource_kind: ResourceKind::Property,
+            ref name,
+        })) if name == "P2"
+    ));
+}
@@ -15,6 +15,7 @@
 use super::client_enterprise;
 use crate::client::AppConfigurationClient;
 use rstest::*;
+use std::sync::Arc;
 
 #[rstest]
 fn test_get_property_ids(client_enterprise: AppConfigurationClient) {
@@ -30,3 +31,23 @@ fn test_get_property_ids(client_enterprise: AppConfigurationClient) {
         ]
     );
 }
+
+#[rstest]
+fn test_get_property_ids_by_tag(client_enterprise: AppConfigurationClient) {
+    {
+        let mut snapshot = client_enterprise.latest_config_snapshot.write().unwrap();
+        Arc::make_mut(snapshot.properties.get_mut("p1").unwrap()).tags =
+            Some("team-a,team-b".into());
+        Arc::make_mut(snapshot.properties.get_mut("p3").unwrap()).tags = Some("team-b".into());
+    }
+
+    let mut properties = client_enterprise.get_property_ids_by_tag("team-b").unwrap();
+    properties.sort();
+    assert_eq!(properties, vec!["p1".to_string(), "p3".to_string()]);
+
+    let properties = client_enterprise.get_property_ids_by_tag("team-a").unwrap();
+    assert_eq!(properties, vec!["p1".to_string()]);
+
+    let properties = client_enterprise.get_property_ids_by_tag("team-c").unwrap();
+    assert!(properties.is_empty());
+}
@@ -0,0 +1,117 @@
+// (C) Copyright IBM Corp. 2024.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use super::{client_enterprise, GenericEntity};
+use crate::client::segment::SegmentRule;
+use crate::client::AppConfigurationClient;
+use crate::errors::{ConfigurationAccessError, Error};
+use crate::AttrValue;
+use rstest::*;
+
+#[rstest]
+fn test_entity_in_segment(client_enterprise: AppConfigurationClient) {
+    let entity = GenericEntity {
+        id: "a1".into(),
+        attributes: HashMap::from([("code".into(), AttrValue::from("CHG123"))]),
+    };
+    assert!(client_enterprise
+        .entity_in_segment(&entity, "l2dfo8do")
+        .unwrap());
+
+    let entity = GenericEntity {
+        id: "a2".into(),
+        attributes: HashMap::from([("code".into(), AttrValue::from("XYZ"))]),
+    };
+    assert!(!client_enterprise
+        .entity_in_segment(&entity, "l2dfo8do")
+        .unwrap());
+}
+
+#[rstest]
+fn test_entity_in_segment_not_found(client_enterprise: AppConfigurationClient) {
+    let result = client_enterprise.entity_in_segment(&super::TrivialEntity, "does-not-exist");
+    assert!(matches!(
+        result,
+        Err(Error::ConfigurationAccessError(ConfigurationAccessError::SegmentNotFound { ref segment_id }))
+        if segment_id == "does-not-exist"
+    ));
+}
+
+#[rstest]
+fn test_get_segment_ids(client_enterprise: AppConfigurationClient) {
+    let mut segment_ids = client_enterprise.get_segment_ids().unwrap();
+    segment_ids.sort();
+    assert_eq!(segment_ids, vec!["l2dfo8do", "l2dfos8y", "l2dfr61d"]);
+}
+
+#[rstest]
+fn test_get_segment(client_enterprise: AppConfigurationClient) {
+    let segment = client_enterprise.get_segment("l2dfos8y").unwrap();
+    assert_eq!(segment.get_id(), "l2dfos8y");
+    assert_eq!(segment.get_name(), "test1");
+    assert_eq!(
+        segment.get_rules(),
+        vec![
+            SegmentRule {
+                attribute_name: "size".into(),
+                operator: "is".into(),
+                values: vec!["10UK".into(), "11UK".into()],
+            },
+            SegmentRule {
+                attribute_name: "number".into(),
+                operator: "greaterThan".into(),
+                values: vec!["20".into()],
+            },
+        ]
+    );
+
+    assert!(matches!(
+        client_enterprise.get_segment("does-not-exist"),
+        Err(Error::ConfigurationAccessError(
+            ConfigurationAccessError::SegmentNotFound { .. }
+        ))
+    ));
+}
+
+#[rstest]
+fn test_explain_feature(client_enterprise: AppConfigurationClient) {
+    // Belongs to the segments of the first and second rule of f1
+    let entity = GenericEntity {
+        id: "a1".into(),
+        attributes: HashMap::from([
+            ("code".into(), AttrValue::from("CHG123")),
+            ("size".into(), AttrValue::from("10UK")),
+            ("number".into(), AttrValue::from(30.0)),
+        ]),
+    };
+    let rules = client_enterprise.explain_feature(&entity, "f1").unwrap();
+    assert_eq!(
+        rules.iter().map(|rule| rule.order).collect::<Vec<_>>(),
+        vec![1, 2]
+    );
+
+    let entity = GenericEntity {
+        id: "a2".into(),
+        attributes: HashMap::from([("code".into(), AttrValue::from("XYZ"))]),
+    };
+    assert!(client_enterprise
+        .explain_feature(&entity, "f1")
+        .unwrap()
+        .is_empty());
+    assert!(client_enterprise
+        .explain_feature(&entity, "does-not-exist")
+        .is_err());
+}
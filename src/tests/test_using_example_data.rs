@@ -13,6 +13,9 @@
 // limitations under the License.
 
 use crate::client::AppConfigurationClient;
+use crate::errors::{ConfigurationAccessError, Error};
+use crate::AttrValue;
+use std::collections::HashMap;
 
 use rstest::*;
 
@@ -23,14 +26,83 @@ fn test_get_a_specific_feature(client_enterprise: AppConfigurationClient) {
     use crate::models::ValueKind;
     let specific_feature = client_enterprise.get_feature_proxy("f1").unwrap();
 
-    let name = specific_feature.get_name();
-    let data_type = specific_feature.get_data_type();
-    let is_enabled = specific_feature.is_enabled();
+    let name = specific_feature.get_name().unwrap();
+    let data_type = specific_feature.get_data_type().unwrap();
+    let is_enabled = specific_feature.is_enabled().unwrap();
 
     assert_eq!(name, "F1".to_string());
     assert_eq!(data_type, ValueKind::Numeric);
     assert_eq!(is_enabled, true);
-    assert_eq!(specific_feature.get_enabled_value().as_i64().unwrap(), 5);
+    assert_eq!(
+        specific_feature
+            .get_enabled_value()
+            .unwrap()
+            .as_i64()
+            .unwrap(),
+        5
+    );
+}
+
+#[rstest]
+fn test_feature_proxy_with_snapshot(client_enterprise: AppConfigurationClient) {
+    let proxy = client_enterprise.get_feature_proxy("f1").unwrap();
+
+    let (data_type, enabled_value) = proxy
+        .with_snapshot(|feature| (feature.get_data_type(), feature.get_enabled_value()))
+        .unwrap();
+    assert_eq!(data_type, crate::models::ValueKind::Numeric);
+    assert_eq!(enabled_value.unwrap(), crate::client::value::Value::from(5));
+
+    let proxy = client_enterprise.get_feature_proxy("unknown").unwrap();
+    assert!(proxy.with_snapshot(|_| ()).is_err());
+}
+
+#[rstest]
+fn test_get_proxy_checked(client_enterprise: AppConfigurationClient) {
+    assert!(client_enterprise.get_feature_proxy_checked("f1").is_ok());
+    assert!(matches!(
+        client_enterprise.get_feature_proxy_checked("typo"),
+        Err(Error::ConfigurationAccessError(
+            ConfigurationAccessError::FeatureNotFound { ref feature_id }
+        )) if feature_id == "typo"
+    ));
+    assert!(client_enterprise.get_property_proxy_checked("p1").is_ok());
+    assert!(matches!(
+        client_enterprise.get_property_proxy_checked("typo"),
+        Err(Error::ConfigurationAccessError(
+            ConfigurationAccessError::PropertyNotFound { ref property_id }
+        )) if property_id == "typo"
+    ));
+}
+
+#[rstest]
+fn test_proxies_of_removed_resources(client_enterprise: AppConfigurationClient) {
+    let feature = client_enterprise.get_feature_proxy_checked("f1").unwrap();
+    let property = client_enterprise.get_property_proxy_checked("p1").unwrap();
+    {
+        let mut snapshot = client_enterprise.latest_config_snapshot.write().unwrap();
+        snapshot.features.remove("f1");
+        snapshot.properties.remove("p1");
+    }
+
+    assert!(matches!(
+        feature.get_name(),
+        Err(Error::ConfigurationAccessError(
+            ConfigurationAccessError::FeatureNotFound { ref feature_id }
+        )) if feature_id == "f1"
+    ));
+    assert!(feature
+        .get_current_value(&crate::tests::TrivialEntity {})
+        .is_err());
+    assert!(matches!(
+        property.get_value(),
+        Err(Error::ConfigurationAccessError(
+            ConfigurationAccessError::PropertyNotFound { ref property_id }
+        )) if property_id == "p1"
+    ));
+    assert!(property
+        .get_current_value(&crate::tests::TrivialEntity {})
+        .is_err());
 }
 
 #[rstest]
@@ -38,10 +110,37 @@ fn test_get_a_specific_property(client_enterprise: AppConfigurationClient) {
     use crate::models::ValueKind;
     let property = client_enterprise.get_property_proxy("p1").unwrap();
 
-    let name = property.get_name();
-    let data_type = property.get_data_type();
+    let name = property.get_name().unwrap();
+    let data_type = property.get_data_type().unwrap();
 
     assert_eq!(name, "p1");
     assert_eq!(data_type, ValueKind::Numeric);
-    assert_eq!(property.get_value().as_u64().unwrap(), 5);
+    assert_eq!(property.get_value().unwrap().as_u64().unwrap(), 5);
+}
+
+// Proxies and features share their evaluation.
+#[rstest]
+fn test_feature_proxy_evaluates_like_feature(client_enterprise: AppConfigurationClient) {
+    let entities = [
+        crate::tests::GenericEntity {
+            id: "a1".into(),
+            attributes: HashMap::new(),
+        },
+        crate::tests::GenericEntity {
+            id: "a2".into(),
+            attributes: HashMap::from([("name".into(), AttrValue::from("heinz".to_string()))]),
+        },
+    ];
+    for feature_id in client_enterprise.get_feature_ids().unwrap() {
+        let feature = client_enterprise.get_feature(&feature_id).unwrap();
+        let proxy = client_enterprise.get_feature_proxy(&feature_id).unwrap();
+        for entity in &entities {
+            assert_eq!(
+                serde_json::to_value(proxy.get_current_value(entity).unwrap()).unwrap(),
+                serde_json::to_value(feature.get_value(entity).unwrap()).unwrap(),
+                "{feature_id} for {}",
+                entity.id
+            );
+        }
+    }
 }
@@ -46,9 +46,9 @@ fn test_get_a_specific_feature(setup_client: AppConfigurationClient) {
         .get_feature_proxy("test-feature-flag-1")
         .unwrap();
 
-    let name = specific_feature.get_name();
-    let data_type = specific_feature.get_data_type();
-    let is_enabled = specific_feature.is_enabled();
+    let name = specific_feature.get_name().unwrap();
+    let data_type = specific_feature.get_data_type().unwrap();
+    let is_enabled = specific_feature.is_enabled().unwrap();
 
     assert_eq!(name, "test feature flag 1".to_string());
     assert_eq!(data_type, ValueKind::Boolean);
@@ -66,8 +66,8 @@ fn test_get_list_of_properties(setup_client: AppConfigurationClient) {
 fn test_get_a_specific_property(setup_client: AppConfigurationClient) {
     let property = setup_client.get_property_proxy("test-property-1").unwrap();
 
-    let name = property.get_name();
-    let data_type = property.get_data_type();
+    let name = property.get_name().unwrap();
+    let data_type = property.get_data_type().unwrap();
 
     assert_eq!(name, "Test Property 1");
     assert_eq!(data_type, ValueKind::Boolean);